@@ -1,5 +1,7 @@
 pub const CLOCK_FREQ: usize = 12500000;
 pub const MEMORY_END: usize = 0x88000000;
+/// harts brought up at most, each has a boot stack in `entry.asm`
+pub const MAX_HARTS: usize = 4;
 
 pub const MMIO: &[(usize, usize)] = &[
     //(0x0010_0000, 0x00_2000), // VIRT_TEST/RTC  in virt machine
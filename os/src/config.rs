@@ -2,15 +2,30 @@
 #[allow(unused)]
 
 pub const USER_STACK_SIZE: usize = 4096 * 2;
+/// the largest user stack RLIMIT_STACK can ask for, its pages are allocated on first access
+pub const USER_STACK_MAX: usize = 0x80_0000;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
+/// boot stack of each hart, set up in `entry.asm`
+pub const BOOT_STACK_SIZE: usize = 4096 * 16;
 pub const KERNEL_HEAP_SIZE: usize = 0x20_0000;
 //ztr_brk
-pub const USER_HEAP_SIZE: usize = PAGE_SIZE * 20;
+/// virtual space reserved for the heap, how much of it `brk` can use is
+/// bounded by RLIMIT_DATA and RLIMIT_AS
+pub const USER_HEAP_MAX: usize = 0x400_0000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
 
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
-pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
+/// user page where signal handlers return to, it calls `rt_sigreturn`
+pub const SIGRETURN_TRAMPOLINE: usize = TRAMPOLINE - PAGE_SIZE;
+pub const TRAP_CONTEXT: usize = SIGRETURN_TRAMPOLINE - PAGE_SIZE;
+/// end of the lower half of SV39, user mappings stay below it, away from the
+/// trampolines and trap contexts at the top
+pub const USER_SPACE_END: usize = 1 << 38;
+/// load address of position-independent executables
+pub const ELF_PIE_BASE: usize = 0x10_0000;
+/// load address of the dynamic linker named by PT_INTERP
+pub const ELF_INTERP_BASE: usize = 0x20_0000_0000;
 
-pub use crate::board::{CLOCK_FREQ, MEMORY_END, MMIO};
+pub use crate::board::{CLOCK_FREQ, MAX_HARTS, MEMORY_END, MMIO};
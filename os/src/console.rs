@@ -1,22 +1,35 @@
 //! SBI console driver, for text output
 use crate::sbi::console_putchar;
+use crate::sync::SpinLock;
 use core::fmt::{self, Write};
 
+/// keeps the lines printed by different harts apart
+static STDOUT_LOCK: SpinLock<()> = SpinLock::new(());
+
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
+        for byte in s.bytes() {
+            console_putchar(byte as usize);
         }
         Ok(())
     }
 }
 
 pub fn print(args: fmt::Arguments) {
+    let _guard = STDOUT_LOCK.lock();
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Print bytes as they are, what user programs write need not be UTF-8
+pub fn print_bytes<'a>(chunks: impl Iterator<Item = &'a [u8]>) {
+    let _guard = STDOUT_LOCK.lock();
+    for byte in chunks.flatten() {
+        console_putchar(*byte as usize);
+    }
+}
+
 #[macro_export]
 /// print string macro
 macro_rules! print {
@@ -1,12 +1,19 @@
     .section .text.entry
     .globl _start
 _start:
+    # a0: hart id, kept in tp while running in the kernel
+    mv tp, a0
+    # every hart has its own boot stack, BOOT_STACK_SIZE bytes below the previous one
     la sp, boot_stack_top
+    li t0, {boot_stack_size}
+    mul t0, t0, a0
+    sub sp, sp, t0
     call rust_main
 
     .section .bss.stack
     .globl boot_stack_lower_bound
 boot_stack_lower_bound:
-    .space 4096 * 16
+    # BOOT_STACK_SIZE for each of MAX_HARTS harts
+    .space {boot_stack_size} * {max_harts}
     .globl boot_stack_top
 boot_stack_top:
\ No newline at end of file
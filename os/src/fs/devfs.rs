@@ -0,0 +1,254 @@
+//! devfs：字符设备文件，打开后得到的是设备本身（[`File`]），而不是
+//! [`OSInode`](super::OSInode)
+//!
+//! The devices keep no state of their own, every mount lists the same
+//! devices.
+
+use super::dir::DirEntry;
+use super::stat::Kstat;
+use super::stdio::{Stdin, Stdout};
+use super::vfs::{DiskInodeType, Inode, SuperBlock, Vfs};
+use super::File;
+use crate::mm::UserBuffer;
+use crate::random::fill_random;
+use crate::syscall::{Errno, SysResult};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy)]
+enum DevKind {
+    /// reads end at once, writes are discarded
+    Null,
+    /// reads give zeros, writes are discarded
+    Zero,
+    /// reads give zeros, writes fail with `ENOSPC`
+    Full,
+    /// the console, like stdin and stdout
+    Tty,
+    /// reads give bytes of the kernel PRNG, writes are discarded
+    URandom,
+}
+
+/// The devices in /dev
+const DEVICES: [(&str, DevKind); 5] = [
+    ("null", DevKind::Null),
+    ("zero", DevKind::Zero),
+    ("full", DevKind::Full),
+    ("tty", DevKind::Tty),
+    ("urandom", DevKind::URandom),
+];
+
+/// A character device
+pub struct CharDevice {
+    name: &'static str,
+    kind: DevKind,
+}
+
+impl File for CharDevice {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, Errno> {
+        match self.kind {
+            DevKind::Null => Ok(0),
+            DevKind::Zero | DevKind::Full => {
+                for slice in buf.buffers.iter_mut() {
+                    slice.fill(0);
+                }
+                Ok(buf.len())
+            }
+            DevKind::Tty => Stdin.read(buf),
+            DevKind::URandom => {
+                for slice in buf.buffers.iter_mut() {
+                    fill_random(slice);
+                }
+                Ok(buf.len())
+            }
+        }
+    }
+    /// `/dev/full` takes nothing, writers looping until all is written stop
+    /// on the error
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        match self.kind {
+            DevKind::Full => Err(Errno::ENOSPC),
+            DevKind::Tty => Stdout.write(buf),
+            _ => Ok(buf.len()),
+        }
+    }
+    fn get_fstat(&self, kstat: &mut Kstat) {
+        kstat.init(0, 512, 0);
+    }
+    #[allow(unused_variables)]
+    fn get_dirent(&self, dirent: &mut DirEntry) -> isize {
+        -1
+    }
+    fn get_name(&self) -> String {
+        String::from("/dev/") + self.name
+    }
+    /// Devices have no offset, so mapping `/dev/zero` still reads zeros
+    #[allow(unused_variables)]
+    fn set_offset(&self, offset: usize) {}
+    /// Seeking does nothing but on the terminal, where it fails
+    #[allow(unused_variables)]
+    fn lseek(&self, offset: isize, whence: usize) -> SysResult {
+        match self.kind {
+            DevKind::Tty => Err(Errno::ESPIPE),
+            _ => Ok(0),
+        }
+    }
+    /// Devices have no offset, positioned I/O is plain I/O but on the terminal
+    #[allow(unused_variables)]
+    fn pread(&self, offset: usize, buf: UserBuffer) -> Result<usize, Errno> {
+        match self.kind {
+            DevKind::Tty => Err(Errno::ESPIPE),
+            _ => self.read(buf),
+        }
+    }
+    #[allow(unused_variables)]
+    fn pwrite(&self, offset: usize, buf: UserBuffer) -> Result<usize, Errno> {
+        match self.kind {
+            DevKind::Tty => Err(Errno::ESPIPE),
+            _ => self.write(buf),
+        }
+    }
+    fn ioctl(&self, request: usize, arg: usize) -> SysResult {
+        match self.kind {
+            DevKind::Tty => Stdin.ioctl(request, arg),
+            _ => Err(Errno::ENOTTY),
+        }
+    }
+}
+
+/// The inode of a device file, reading and writing go to [`Inode::device`]
+struct DevInode {
+    device: Arc<CharDevice>,
+}
+
+impl Inode for DevInode {
+    fn name(&self) -> String {
+        String::from(self.device.name)
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    #[allow(unused_variables)]
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        0
+    }
+    #[allow(unused_variables)]
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+    fn clear(&self) {}
+    fn stat(&self, kstat: &mut Kstat) {
+        self.device.get_fstat(kstat);
+    }
+    #[allow(unused_variables)]
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    #[allow(unused_variables)]
+    fn create(&self, name: &str, dtype: DiskInodeType) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    #[allow(unused_variables)]
+    fn unlink(&self, name: &str) -> Result<(), Errno> {
+        Err(Errno::ENOTDIR)
+    }
+    #[allow(unused_variables)]
+    fn dirent(&self, offset: usize) -> Option<(String, usize)> {
+        None
+    }
+    fn device(&self) -> Option<Arc<dyn File + Send + Sync>> {
+        Some(self.device.clone())
+    }
+}
+
+/// /dev itself, its entries are fixed
+struct DevRoot {
+    devices: Vec<Arc<DevInode>>,
+}
+
+impl Inode for DevRoot {
+    fn name(&self) -> String {
+        String::from("/")
+    }
+    fn is_dir(&self) -> bool {
+        true
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    #[allow(unused_variables)]
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        0
+    }
+    #[allow(unused_variables)]
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+    fn clear(&self) {}
+    fn stat(&self, kstat: &mut Kstat) {
+        kstat.init(0, 512, 0);
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        self.devices
+            .iter()
+            .find(|inode| inode.device.name == name)
+            .map(|inode| inode.clone() as Arc<dyn Inode>)
+    }
+    #[allow(unused_variables)]
+    fn create(&self, name: &str, dtype: DiskInodeType) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    #[allow(unused_variables)]
+    fn unlink(&self, name: &str) -> Result<(), Errno> {
+        Err(Errno::EPERM)
+    }
+    fn dirent(&self, offset: usize) -> Option<(String, usize)> {
+        self.devices
+            .get(offset)
+            .map(|inode| (String::from(inode.device.name), offset + 1))
+    }
+}
+
+pub struct DevSuperBlock {
+    root: Arc<DevRoot>,
+}
+
+impl SuperBlock for DevSuperBlock {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+    fn fstype(&self) -> &'static str {
+        "devfs"
+    }
+}
+
+pub struct DevFs;
+
+impl Vfs for DevFs {
+    fn name(&self) -> &'static str {
+        "devfs"
+    }
+    fn mount(&self, _special: &str, _flags: u32) -> Result<Arc<dyn SuperBlock>, Errno> {
+        let devices = DEVICES
+            .iter()
+            .map(|&(name, kind)| {
+                Arc::new(DevInode {
+                    device: Arc::new(CharDevice { name, kind }),
+                })
+            })
+            .collect();
+        Ok(Arc::new(DevSuperBlock {
+            root: Arc::new(DevRoot { devices }),
+        }))
+    }
+}
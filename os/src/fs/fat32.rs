@@ -0,0 +1,133 @@
+//! FAT32 behind the VFS: [`Inode`] over `easy_fs::VFile`
+//!
+//! There is a single block device, every mount of "vfat" shares the
+//! `FAT32Manager` opened on it, whatever `special` names.
+
+use super::stat::Kstat;
+use super::vfs::{DiskInodeType, Inode, SuperBlock, Vfs};
+use crate::drivers::BLOCK_DEVICE;
+use crate::syscall::Errno;
+use alloc::string::String;
+use alloc::sync::Arc;
+use easy_fs::{FAT32Manager, VFile, ATTRIBUTE_ARCHIVE, ATTRIBUTE_DIRECTORY, DIRENT_SZ};
+use lazy_static::*;
+
+pub struct Fat32Inode {
+    vfile: Arc<VFile>,
+}
+
+impl Fat32Inode {
+    fn new(vfile: Arc<VFile>) -> Arc<dyn Inode> {
+        Arc::new(Self { vfile })
+    }
+}
+
+impl Inode for Fat32Inode {
+    fn name(&self) -> String {
+        String::from(self.vfile.get_name())
+    }
+    fn is_dir(&self) -> bool {
+        self.vfile.is_dir()
+    }
+    fn size(&self) -> usize {
+        self.vfile.get_size() as usize
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.vfile.read_at(offset, buf)
+    }
+    /// 文件大小记录在 32 位的目录项中
+    fn max_size(&self) -> usize {
+        u32::MAX as usize
+    }
+    /// 从末尾之后写入时，新分配的簇中残留着旧数据，先把空洞写成 0
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        if offset.saturating_add(buf.len()) > self.max_size() {
+            return 0;
+        }
+        let zeros = [0u8; 512];
+        let mut pos = self.size();
+        while pos < offset {
+            let len = (offset - pos).min(zeros.len());
+            let write_size = self.vfile.write_at(pos, &zeros[..len]);
+            if write_size == 0 {
+                return 0;
+            }
+            pos += write_size;
+        }
+        self.vfile.write_at(offset, buf)
+    }
+    fn clear(&self) {
+        self.vfile.clear();
+    }
+    fn stat(&self, kstat: &mut Kstat) {
+        let (st_size, st_blksize, st_blocks) = self.vfile.stat();
+        kstat.init(st_size, st_blksize, st_blocks);
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if !self.is_dir() {
+            return None;
+        }
+        self.vfile
+            .find_vfile_byname(name)
+            .map(|vfile| Fat32Inode::new(Arc::new(vfile)))
+    }
+    fn create(&self, name: &str, dtype: DiskInodeType) -> Option<Arc<dyn Inode>> {
+        if !self.is_dir() {
+            return None;
+        }
+        let attribute = match dtype {
+            DiskInodeType::Directory => ATTRIBUTE_DIRECTORY,
+            DiskInodeType::File => ATTRIBUTE_ARCHIVE,
+        };
+        self.vfile.create(name, attribute).map(Fat32Inode::new)
+    }
+    fn unlink(&self, name: &str) -> Result<(), Errno> {
+        if !self.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        let vfile = self.vfile.find_vfile_byname(name).ok_or(Errno::ENOENT)?;
+        vfile.remove();
+        Ok(())
+    }
+    fn dirent(&self, offset: usize) -> Option<(String, usize)> {
+        // 返回的是短目录项的偏移，下一项紧随其后
+        self.vfile
+            .dirent_info(offset)
+            .map(|(name, off, _, _)| (name, off as usize + DIRENT_SZ))
+    }
+}
+
+pub struct Fat32SuperBlock {
+    root: Arc<VFile>,
+}
+
+impl SuperBlock for Fat32SuperBlock {
+    fn root(&self) -> Arc<dyn Inode> {
+        Fat32Inode::new(self.root.clone())
+    }
+    fn fstype(&self) -> &'static str {
+        "vfat"
+    }
+}
+
+lazy_static! {
+    /// 块设备上的 FAT32 文件系统，在 `/` 挂载
+    pub static ref FAT32_SUPER: Arc<Fat32SuperBlock> = {
+        let fat32_manager = FAT32Manager::open(BLOCK_DEVICE.clone());
+        let manager_reader = fat32_manager.read();
+        Arc::new(Fat32SuperBlock {
+            root: Arc::new(manager_reader.get_root_vfile(&fat32_manager)),
+        })
+    };
+}
+
+pub struct Fat32Fs;
+
+impl Vfs for Fat32Fs {
+    fn name(&self) -> &'static str {
+        "vfat"
+    }
+    fn mount(&self, _special: &str, _flags: u32) -> Result<Arc<dyn SuperBlock>, Errno> {
+        Ok(FAT32_SUPER.clone())
+    }
+}
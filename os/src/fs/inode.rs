@@ -1,58 +1,50 @@
-//! `Arc<Inode>` -> `OSInodeInner`: In order to open files concurrently
+//! `Arc<dyn Inode>` -> `OSInodeInner`: In order to open files concurrently
 //! we need to wrap `Inode` into `Arc`,but `Mutex` in `Inode` prevents
 //! file systems from being accessed simultaneously
 //!
-//! `UPSafeCell<OSInodeInner>` -> `OSInode`: for static `ROOT_INODE`,we
-//! need to wrap `OSInodeInner` into `UPSafeCell`
+//! `SpinLock<OSInodeInner>` -> `OSInode`: an open file keeps its own
+//! offset, the inode may be shared
 use super::dir::DirEntry;
 use super::stat::Kstat;
+use super::vfs::{absolute_path, lookup, split_path, DiskInodeType, Inode};
 
-use super::File;
-use crate::{drivers::BLOCK_DEVICE, console::print};
+use super::{File, FileType, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::console::print;
 use crate::mm::UserBuffer;
-use crate::sync::UPSafeCell;
-use _core::str::FromStr;
+use crate::sync::SpinLock;
+use crate::syscall::{Errno, SysResult};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::*;
-//ztr_file
-use easy_fs::{FAT32Manager, VFile, ATTRIBUTE_ARCHIVE, ATTRIBUTE_DIRECTORY};
-//use easy_fs::{EasyFileSystem, Inode};
-use lazy_static::*;
 use alloc::string::String;
 /// A wrapper around a filesystem inode
 /// to implement File trait atop
-//ztr_file
-pub enum DiskInodeType {
-    File,
-    Directory,
-}
-
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPSafeCell<OSInodeInner>,
+    /// 打开时的绝对路径
+    path: String,
+    inner: SpinLock<OSInodeInner>,
 }
-/// The OS inode inner in 'UPSafeCell'
+/// The OS inode inner in 'SpinLock'
 pub struct OSInodeInner {
     offset: usize,
-    //inode: Arc<Inode>,
-    //ztr_file
-    inode: Arc<VFile>,
+    inode: Arc<dyn Inode>,
 }
 
 impl OSInode {
     /// Construct an OS inode from a inode
-    pub fn new(readable: bool, writable: bool, inode: Arc<VFile>) -> Self {
+    pub fn new(readable: bool, writable: bool, inode: Arc<dyn Inode>, path: String) -> Self {
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            path,
+            inner: SpinLock::new(OSInodeInner { offset: 0, inode }),
         }
     }
     /// Read all data inside a inode into vector
     pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         let mut buffer = [0u8; 512];
         let mut v: Vec<u8> = Vec::new();
         loop {
@@ -66,48 +58,100 @@ impl OSInode {
         
         v
     }
+    /// Read from `offset` without moving the file offset
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let inner = self.inner.lock();
+        inner.inode.read_at(offset, buf)
+    }
+    /// Write at `offset` without moving the file offset
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let inner = self.inner.lock();
+        inner.inode.write_at(offset, buf)
+    }
+    /// Size of the file in bytes
+    pub fn size(&self) -> usize {
+        let inner = self.inner.lock();
+        inner.inode.size()
+    }
+    /// Set the size of the file to `len`, as `ftruncate`
+    pub fn truncate(&self, len: usize) -> Result<(), Errno> {
+        let inode = self.inner.lock().inode.clone();
+        inode.truncate(len)
+    }
     pub fn is_dir(&self) -> bool {
-        let inner = self.inner.exclusive_access();
-        inner.inode.is_dir().clone()
+        let inner = self.inner.lock();
+        inner.inode.is_dir()
     }
-    //ztr_open
+    /// A device file opens as the device, not as the inode
+    pub fn file_type(self: Arc<Self>) -> FileType {
+        let device = self.inner.lock().inode.device();
+        match device {
+            Some(device) => FileType::Abstr(device),
+            None => FileType::File(self),
+        }
+    }
+    /// Absolute path the file was opened by, relative paths of `*at`
+    /// syscalls start from it
     pub fn get_name(&self) -> String {
-        let inner = &self.inner.exclusive_access();
-        let inode = &inner.inode;
-        inode.name.clone()
+        self.path.clone()
+    }
+    /// Read from `offset` leaving the offset of the file alone, as `pread64`
+    pub fn pread(&self, offset: usize, buf: UserBuffer) -> usize {
+        let inode = self.inner.lock().inode.clone();
+        read_inode(inode.as_ref(), offset, buf)
+    }
+    /// Write at `offset` leaving the offset of the file alone, as `pwrite64`
+    pub fn pwrite(&self, offset: usize, buf: UserBuffer) -> Result<usize, Errno> {
+        let inode = self.inner.lock().inode.clone();
+        write_inode(inode.as_ref(), offset, buf)
     }
 }
-//ztr_file
-lazy_static! {
-    pub static ref ROOT_INODE: Arc<VFile> = {
-        let fat32_manager = FAT32Manager::open(BLOCK_DEVICE.clone());
-        let manager_reader = fat32_manager.read();
-        Arc::new(manager_reader.get_root_vfile(&fat32_manager))
-    };
+
+fn read_inode(inode: &dyn Inode, offset: usize, mut buf: UserBuffer) -> usize {
+    let mut total_read_size = 0usize;
+    for slice in buf.buffers.iter_mut() {
+        let read_size = inode.read_at(offset + total_read_size, *slice);
+        if read_size == 0 {
+            break;
+        }
+        total_read_size += read_size;
+    }
+    total_read_size
 }
 
-// lazy_static! {
-//     pub static ref ROOT_INODE: Arc<Inode> = {
-//         let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
-//         Arc::new(EasyFileSystem::root_inode(&efs))
-//     };
-// }
-/// List all files in the filesystems
-// pub fn list_apps() {
-//     println!("/**** APPS ****");
-//     for app in ROOT_INODE.ls() {
-//         println!("{}", app);
-//     }
-//     println!("**************/");
-// }
+/// `EFBIG` if the file would grow past the largest size of its filesystem,
+/// `ENOSPC` if it is full before a single byte is written
+fn write_inode(inode: &dyn Inode, offset: usize, buf: UserBuffer) -> Result<usize, Errno> {
+    if buf.len() > 0 && offset.checked_add(buf.len()).map_or(true, |end| end > inode.max_size()) {
+        return Err(Errno::EFBIG);
+    }
+    let mut total_write_size = 0usize;
+    for slice in buf.buffers.iter() {
+        let write_size = inode.write_at(offset + total_write_size, *slice);
+        total_write_size += write_size;
+        // 文件系统已满
+        if write_size < slice.len() {
+            break;
+        }
+    }
+    if total_write_size == 0 && buf.len() > 0 {
+        return Err(Errno::ENOSPC);
+    }
+    Ok(total_write_size)
+}
 
-//ztr_file
+/// List all files in the root directory
 pub fn list_apps() {
     println!("/**** APPS ****");
-    for app in ROOT_INODE.ls_lite().unwrap() {
-        if app.1 & ATTRIBUTE_DIRECTORY == 0 {
-            println!("{}", app.0);
+    let root = lookup("/").unwrap();
+    let mut offset = 0;
+    while let Some((name, next)) = root.dirent(offset) {
+        if let Some(inode) = root.lookup(name.as_str()) {
+            if !inode.is_dir() {
+                println!("{}", name);
+            }
         }
+        offset = next;
     }
     println!("**************/")
 }
@@ -144,87 +188,44 @@ impl OpenFlags {
     }
 }
 ///Open file with flags
-// pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
-//     let (readable, writable) = flags.read_write();
-//     if flags.contains(OpenFlags::CREATE) {
-//         if let Some(inode) = ROOT_INODE.find(name) {
-//             // clear size
-//             inode.clear();
-//             Some(Arc::new(OSInode::new(readable, writable, inode)))
-//         } else {
-//             // create file
-//             ROOT_INODE
-//                 .create(name)
-//                 .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
-//         }
-//     } else {
-//         ROOT_INODE.find(name).map(|inode| {
-//             if flags.contains(OpenFlags::TRUNC) {
-//                 inode.clear();
-//             }
-//             Arc::new(OSInode::new(readable, writable, inode))
-//         })
-//     }
-// }
-
-//ztr_file
 pub fn open_file(
     work_path: &str,
     path: &str,
     flags: OpenFlags,
     dtype: DiskInodeType,
 ) -> Option<Arc<OSInode>> {
-    // 找到当前路径的inode(file, directory)
-    let cur_inode = {
-        if work_path == "/" {
-            ROOT_INODE.clone()
-        } else {
-            let wpath: Vec<&str> = work_path.split('/').collect();
-            ROOT_INODE.find_vfile_bypath(wpath).unwrap()
-        }
-    };
-    let mut pathv: Vec<&str> = path.split('/').collect();
+    let path = absolute_path(work_path, path);
     let (readable, writeable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = cur_inode.find_vfile_bypath(pathv.clone()) {
-            inode.remove();
+    let inode = if flags.contains(OpenFlags::CREATE) {
+        let (parent_path, name) = split_path(path.as_str());
+        let parent = lookup(parent_path)?;
+        // 已存在的同名文件先删除，设备文件则直接打开
+        match parent.lookup(name) {
+            Some(inode) if inode.device().is_some() => inode,
+            Some(_) => {
+                parent.unlink(name).ok()?;
+                parent.create(name, dtype)?
+            }
+            None => parent.create(name, dtype)?,
         }
-        {
-            // create file
-            let name = pathv.pop().unwrap();
-            if let Some(temp_inode) = cur_inode.find_vfile_bypath(pathv.clone()) {
-                let attribute = {
-                    match dtype {
-                        DiskInodeType::Directory => ATTRIBUTE_DIRECTORY,
-                        DiskInodeType::File => ATTRIBUTE_ARCHIVE,
-                    }
-                };
-                temp_inode
-                    .create(name, attribute)
-                    .map(|inode| Arc::new(OSInode::new(readable, writeable, inode)))
-            } else {
-                None
+    } else if flags.contains(OpenFlags::O_DIRECTROY) {
+        // 目录不存在时创建
+        match lookup(path.as_str()) {
+            Some(inode) if inode.is_dir() => inode,
+            Some(_) => return None,
+            None => {
+                let (parent_path, name) = split_path(path.as_str());
+                lookup(parent_path)?.create(name, DiskInodeType::Directory)?
             }
         }
-    } 
-    else if flags.contains(OpenFlags::O_DIRECTROY) {
-        let name = pathv.pop().unwrap();
-        if let Some(temp_inode) = cur_inode.find_vfile_bypath(pathv.clone()) {
-            temp_inode
-                .create(name, ATTRIBUTE_DIRECTORY)
-                .map(|inode| Arc::new(OSInode::new(readable, writeable, inode)))
-        } else {
-            None
+    } else {
+        let inode = lookup(path.as_str())?;
+        if flags.contains(OpenFlags::TRUNC) {
+            inode.clear();
         }
-    }
-    else {
-        cur_inode.find_vfile_bypath(pathv).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
-                inode.clear();
-            }
-            Arc::new(OSInode::new(readable, writeable, inode))
-        })
-    }
+        inode
+    };
+    Some(Arc::new(OSInode::new(readable, writeable, inode, path)))
 }
 
 impl File for OSInode {
@@ -234,37 +235,21 @@ impl File for OSInode {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, mut buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let mut total_read_size = 0usize;
-        for slice in buf.buffers.iter_mut() {
-            let read_size = inner.inode.read_at(inner.offset, *slice);
-            if read_size == 0 {
-                break;
-            }
-            inner.offset += read_size;
-            total_read_size += read_size;
-        }
-        total_read_size
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        let mut inner = self.inner.lock();
+        let read_size = read_inode(inner.inode.as_ref(), inner.offset, buf);
+        inner.offset += read_size;
+        Ok(read_size)
     }
-    fn write(&self, buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let mut total_write_size = 0usize;
-        for slice in buf.buffers.iter() {
-            let write_size = inner.inode.write_at(inner.offset, *slice);
-            assert_eq!(write_size, slice.len());
-            inner.offset += write_size;
-            total_write_size += write_size;
-        }
-        total_write_size
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        let mut inner = self.inner.lock();
+        let write_size = write_inode(inner.inode.as_ref(), inner.offset, buf)?;
+        inner.offset += write_size;
+        Ok(write_size)
     }
     fn get_fstat(&self, kstat: &mut Kstat) {
-        let inner = self.inner.exclusive_access();
-        let vfile = inner.inode.clone();
-        // todo
-        let (st_size, st_blksize, st_blocks) = vfile.stat();
-        drop(inner);
-        kstat.init(st_size, st_blksize, st_blocks);
+        let inode = self.inner.lock().inode.clone();
+        inode.stat(kstat);
     }
 
     fn get_name(&self) -> String {
@@ -272,19 +257,34 @@ impl File for OSInode {
     }
 
     fn set_offset(&self, offset: usize){
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         inner.offset = offset;
         drop(inner);
     }
+    /// The offset may go past the end, a later write fills the gap with zeros
+    fn lseek(&self, offset: isize, whence: usize) -> SysResult {
+        let mut inner = self.inner.lock();
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => inner.offset,
+            SEEK_END => inner.inode.size(),
+            _ => return Err(Errno::EINVAL),
+        };
+        let new_offset = match (base as isize).checked_add(offset) {
+            Some(new_offset) if new_offset >= 0 => new_offset,
+            _ => return Err(Errno::EINVAL),
+        };
+        inner.offset = new_offset as usize;
+        Ok(new_offset)
+    }
     fn get_dirent(&self, dirent: &mut DirEntry) -> isize {
         if !self.is_dir() {
             return -1;
         }
-        let mut inner = self.inner.exclusive_access();
-        let offset = inner.offset as u32;
-        if let Some((name, off, _, _)) = inner.inode.dirent_info(offset as usize) {
+        let mut inner = self.inner.lock();
+        if let Some((name, next)) = inner.inode.dirent(inner.offset) {
             dirent.set_name(name.as_str());
-            inner.offset = off as usize;
+            inner.offset = next;
             let len = (name.len() + 8 * 4) as isize;
             drop(inner);
             len
@@ -294,27 +294,12 @@ impl File for OSInode {
     }
 }
 //ztr_chdir
+/// The new working directory if `path` names a directory
 pub fn chdir(work_path: &str, path: &str) -> Option<String> {
-    let current_inode = {
-        if path.chars().nth(0).unwrap() == '/' {
-            // 传入路径是绝对路径
-            ROOT_INODE.clone()
-        } else {
-            // 传入路径是相对路径
-            let current_work_pathv: Vec<&str> = work_path.split('/').collect();
-            ROOT_INODE.find_vfile_bypath(current_work_pathv).unwrap()
-        }
-    };
-    let pathv: Vec<&str> = path.split('/').collect();
-    if let Some(_) = current_inode.find_vfile_bypath(pathv) {
-        let new_current_path = String::from_str("/").unwrap() + &String::from_str(path).unwrap();
-        if current_inode.get_name() == "/" {
-            Some(new_current_path)
-        } else {
-            Some(String::from_str(current_inode.get_name()).unwrap() + &new_current_path)
-        }
-    } else {
-        None
+    let path = absolute_path(work_path, path);
+    match lookup(path.as_str()) {
+        Some(inode) if inode.is_dir() => Some(path),
+        _ => None,
     }
 }
 //ztr_test
@@ -332,7 +317,7 @@ pub fn add_initproc_shell() {
             core::slice::from_raw_parts_mut(app_start[0] as *mut u8, app_start[1] - app_start[0])
         });
         println!("Start write initproc ");
-        inode.write(UserBuffer::new(data));
+        inode.write(UserBuffer::new(data)).unwrap();
         println!("initproc OK");
     } else {
         panic!("initproc create fail!");
@@ -345,7 +330,7 @@ pub fn add_initproc_shell() {
             core::slice::from_raw_parts_mut(app_start[1] as *mut u8, app_start[2] - app_start[1])
         });
         println!("Start write user_shell ");
-        inode.write(UserBuffer::new(data));
+        inode.write(UserBuffer::new(data)).unwrap();
         println!("User_shell OK");
     } else {
         panic!("user_shell create fail!");
@@ -4,12 +4,18 @@ mod pipe;
 mod stdio;
 mod stat;
 mod mount;
+mod vfs;
+mod fat32;
+mod tmpfs;
+mod devfs;
+mod procfs;
 
 use crate::mm::UserBuffer;
+use crate::syscall::{Errno, SysResult};
 use alloc::sync::Arc;
 use alloc::string::String;
 pub use stat::Kstat;
-pub use mount::MNT_TABLE;
+pub use mount::{init_mounts, Mount, MNT_TABLE};
 
 #[derive(Clone)]
 pub struct FileDescriptor {
@@ -34,6 +40,11 @@ impl FileDescriptor {
     }
 }
 
+/// `lseek` 的 whence：相对文件开头、当前偏移或文件末尾
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
 /// 文件类型
 #[derive(Clone)]
 pub enum FileType {
@@ -44,8 +55,12 @@ pub enum FileType {
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
-    fn read(&self, buf: UserBuffer) -> usize;
-    fn write(&self, buf: UserBuffer) -> usize;
+    /// The bytes read, a read waiting for input ends early or with `EINTR`
+    /// on a signal
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno>;
+    /// The bytes written, a write waiting for room ends early or with
+    /// `EINTR` on a signal
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno>;
     fn get_fstat(&self, kstat: &mut Kstat);
 
     fn get_dirent(&self, dirent: &mut DirEntry) -> isize;
@@ -54,9 +69,34 @@ pub trait File: Send + Sync {
 
     fn set_offset(&self, offset: usize);
 
+    /// Move the offset as `lseek` does and return it, pipes and terminals
+    /// can not seek
+    #[allow(unused_variables)]
+    fn lseek(&self, offset: isize, whence: usize) -> SysResult {
+        Err(Errno::ESPIPE)
+    }
+
+    /// Read at `offset` as `pread64` does, pipes and terminals can not
+    #[allow(unused_variables)]
+    fn pread(&self, offset: usize, buf: UserBuffer) -> Result<usize, Errno> {
+        Err(Errno::ESPIPE)
+    }
+
+    /// Write at `offset` as `pwrite64` does, pipes and terminals can not
+    #[allow(unused_variables)]
+    fn pwrite(&self, offset: usize, buf: UserBuffer) -> Result<usize, Errno> {
+        Err(Errno::ESPIPE)
+    }
+
+    /// Device specific control, files which are not terminals have none
+    #[allow(unused_variables)]
+    fn ioctl(&self, request: usize, arg: usize) -> SysResult {
+        Err(Errno::ENOTTY)
+    }
 }
 
 pub use dir::{DirEntry, DT_DIR, DT_REG, DT_UNKNOWN};
-pub use inode::{list_apps, open_file, DiskInodeType, OSInode, OpenFlags, add_initproc_shell,chdir};
+pub use inode::{list_apps, open_file, OSInode, OpenFlags, add_initproc_shell,chdir};
+pub use vfs::{absolute_path, find_filesystem, lookup, split_path, DiskInodeType, Inode, SuperBlock, Vfs};
 pub use pipe::{make_pipe, Pipe};
-pub use stdio::{Stdin, Stdout};
+pub use stdio::{poll_console, Stdin, Stdout};
@@ -1,52 +1,121 @@
 use alloc::{string::String, sync::Arc, vec::Vec};
 use lazy_static::*;
 
-use crate::sync::UPSafeCell;
+use super::fat32::FAT32_SUPER;
+use super::vfs::{find_filesystem, lookup, DiskInodeType, Inode, SuperBlock};
+use crate::sync::SpinLock;
+use crate::syscall::Errno;
 
 const MNT_MAXLEN: usize = 16;
 
+/// 一个挂载点：`sb` 挂载在绝对路径 `dir`
+pub struct Mount {
+    pub special: String,
+    pub dir: String,
+    pub fstype: String,
+    pub flags: u32,
+    pub sb: Arc<dyn SuperBlock>,
+}
+
 pub struct MountTable {
-    mnt_list: Vec<(String, String, String)>, // special, dir, fstype
+    mnt_list: Vec<Mount>,
+}
+
+/// Whether the absolute `path` is `dir` or lies below it
+fn is_below(path: &str, dir: &str) -> bool {
+    dir == "/" || path == dir || (path.starts_with(dir) && path.as_bytes()[dir.len()] == b'/')
 }
 
 impl MountTable {
-    pub fn mount(&mut self, special: String, dir: String, fstype: String, flags: u32) -> isize {
+    pub fn mount(&mut self, special: String, dir: String, fstype: String, flags: u32, sb: Arc<dyn SuperBlock>) -> Result<(), Errno> {
         if self.mnt_list.len() == MNT_MAXLEN {
-            return -1;
+            return Err(Errno::EBUSY);
         }
-        // 已存在
-        if self.mnt_list.iter().find(|&(_, d, _)| *d == dir).is_some() {
-            return 0;
+        // 已有文件系统挂载在此
+        if self.mnt_list.iter().any(|mnt| mnt.dir == dir) {
+            return Err(Errno::EBUSY);
         }
+        self.mnt_list.push(Mount {
+            special,
+            dir,
+            fstype,
+            flags,
+            sb,
+        });
+        Ok(())
+    }
 
+    /// Detach the filesystem mounted at the absolute path `target`, or from
+    /// the device `target`
+    pub fn umount(&mut self, target: &str, flags: u32) -> Result<(), Errno> {
         // todo
         _ = flags;
 
-        self.mnt_list.push((special, dir, fstype));
-        0
+        // 根据系统调用规范应该是 mnt.dir == target
+        // 然而测试程序也可能传 special，因此两者都接受
+        let i = self
+            .mnt_list
+            .iter()
+            .position(|mnt| mnt.dir == target || mnt.special == target)
+            .ok_or(Errno::EINVAL)?;
+        let dir = &self.mnt_list[i].dir;
+        // 根文件系统与其下还有挂载点的文件系统不能卸载
+        if dir == "/" || self.mnt_list.iter().any(|mnt| mnt.dir != *dir && is_below(&mnt.dir, dir)) {
+            return Err(Errno::EBUSY);
+        }
+        self.mnt_list.remove(i);
+        Ok(())
     }
 
-    pub fn umount(&mut self, special: String, flags: u32) -> isize {
-        let len = self.mnt_list.len();
+    /// The filesystems mounted, `/` first
+    pub fn mounts(&self) -> &[Mount] {
+        &self.mnt_list
+    }
 
-        // todo
-        _ = flags;
+    /// Whether a filesystem is mounted at the absolute `path`
+    pub fn is_mount_point(&self, path: &str) -> bool {
+        self.mnt_list.iter().any(|mnt| mnt.dir == path)
+    }
 
-        for i in 0..len {
-            // 根据系统调用规范应该是 self.mnt_list[i].0 == special
-            // 然而测试程序传的是 dir，因此这里加了一个或运算
-            if self.mnt_list[i].0 == special || self.mnt_list[i].1 == special {
-                self.mnt_list.remove(i);
-                return 0;
-            }
-        }
-        -1
+    /// The root of the filesystem the absolute `path` is in, and the rest of
+    /// `path` below that root
+    pub fn resolve<'a>(&self, path: &'a str) -> (Arc<dyn Inode>, &'a str) {
+        let mnt = self
+            .mnt_list
+            .iter()
+            .filter(|mnt| is_below(path, &mnt.dir))
+            .max_by_key(|mnt| mnt.dir.len())
+            .expect("no filesystem mounted at /");
+        let rest = if mnt.dir == "/" { path } else { &path[mnt.dir.len()..] };
+        (mnt.sb.root(), rest)
     }
 }
 
 lazy_static! {
-    pub static ref MNT_TABLE: Arc<UPSafeCell<MountTable>> = {
-        let mnt_table = MountTable { mnt_list: Vec::new() };
-        Arc::new(unsafe {UPSafeCell::new(mnt_table)})
+    pub static ref MNT_TABLE: Arc<SpinLock<MountTable>> = {
+        let root = Mount {
+            special: String::from("/dev/vda"),
+            dir: String::from("/"),
+            fstype: String::from("vfat"),
+            flags: 0,
+            sb: FAT32_SUPER.clone(),
+        };
+        let mnt_table = MountTable { mnt_list: alloc::vec![root] };
+        Arc::new(SpinLock::new(mnt_table))
     };
-}
\ No newline at end of file
+}
+
+/// 启动时的挂载：tmpfs 挂载在 /tmp，devfs 挂载在 /dev，procfs 挂载在 /proc，
+/// 目录不存在时先创建
+pub fn init_mounts() {
+    for (fstype, dir) in [("tmpfs", "/tmp"), ("devfs", "/dev"), ("proc", "/proc")] {
+        if lookup(dir).is_none() {
+            lookup("/").unwrap().create(&dir[1..], DiskInodeType::Directory);
+        }
+        let sb = find_filesystem(fstype).unwrap().mount(fstype, 0).unwrap();
+        MNT_TABLE
+            .lock()
+            .mount(String::from(fstype), String::from(dir), String::from(fstype), 0, sb)
+            .unwrap();
+    }
+}
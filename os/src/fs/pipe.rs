@@ -2,7 +2,8 @@ use super::dir::DirEntry;
 
 use super::{File, stat::Kstat};
 use crate::mm::UserBuffer;
-use crate::task::suspend_current_and_run_next;
+use crate::syscall::Errno;
+use crate::task::{current_has_signal, suspend_current_and_run_next};
 use alloc::sync::{Arc, Weak};
 use spin::Mutex;
 use alloc::string::String;
@@ -109,6 +110,15 @@ pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
     (read_end, write_end)
 }
 
+/// A signal ends the waiting with the bytes moved so far, `EINTR` if none
+fn interrupted(size: usize) -> Result<usize, Errno> {
+    if size > 0 {
+        Ok(size)
+    } else {
+        Err(Errno::EINTR)
+    }
+}
+
 impl File for Pipe {
     fn readable(&self) -> bool {
         self.readable
@@ -116,7 +126,7 @@ impl File for Pipe {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, buf: UserBuffer) -> usize {
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno> {
         assert_eq!(self.readable(), true);
         let mut buf_iter = buf.into_iter();
         let mut read_size = 0usize;
@@ -125,9 +135,13 @@ impl File for Pipe {
             let loop_read = ring_buffer.available_read();
             if loop_read == 0 {
                 if ring_buffer.all_write_ends_closed() {
-                    return read_size;
+                    return Ok(read_size);
                 }
                 drop(ring_buffer);
+                // exit_group 与 SIGKILL 也是以信号送达的
+                if current_has_signal() {
+                    return interrupted(read_size);
+                }
                 suspend_current_and_run_next();
                 continue;
             }
@@ -139,12 +153,12 @@ impl File for Pipe {
                     }
                     read_size += 1;
                 } else {
-                    return read_size;
+                    return Ok(read_size);
                 }
             }
         }
     }
-    fn write(&self, buf: UserBuffer) -> usize {
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno> {
         assert_eq!(self.writable(), true);
         let mut buf_iter = buf.into_iter();
         let mut write_size = 0usize;
@@ -153,6 +167,9 @@ impl File for Pipe {
             let loop_write = ring_buffer.available_write();
             if loop_write == 0 {
                 drop(ring_buffer);
+                if current_has_signal() {
+                    return interrupted(write_size);
+                }
                 suspend_current_and_run_next();
                 continue;
             }
@@ -162,7 +179,7 @@ impl File for Pipe {
                     ring_buffer.write_byte(unsafe { *byte_ref });
                     write_size += 1;
                 } else {
-                    return write_size;
+                    return Ok(write_size);
                 }
             }
         }
@@ -0,0 +1,383 @@
+//! procfs：/proc 下的文件在第一次读取时由内核状态生成
+//!
+//! There is a directory per process, named after its pid, with `stat`,
+//! `status`, `cmdline`, `cwd` and `maps`, and the system wide `meminfo`,
+//! `mounts` and `uptime`. The content is generated on the first read
+//! rather than at lookup, as `openat` looks the path up holding the task of
+//! the caller.
+
+use super::stat::Kstat;
+use super::vfs::{DiskInodeType, Inode, SuperBlock, Vfs};
+use super::MNT_TABLE;
+use crate::config::PAGE_SIZE;
+use crate::mm::{frame_stats, heap_stats, MapPermission};
+use crate::sync::SpinLock;
+use crate::syscall::Errno;
+use crate::task::{pid2task, process_cpu_times, processes, thread_group, TaskStatus};
+use crate::timer::get_time_ms;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// clock ticks per second, the unit of the times in `stat`
+const USER_HZ: usize = 100;
+
+#[derive(Clone, Copy)]
+enum ProcKind {
+    Meminfo,
+    Mounts,
+    Uptime,
+    Stat(usize),
+    Status(usize),
+    Cmdline(usize),
+    Cwd(usize),
+    Maps(usize),
+}
+
+const SYSTEM_FILES: [(&str, ProcKind); 3] = [
+    ("meminfo", ProcKind::Meminfo),
+    ("mounts", ProcKind::Mounts),
+    ("uptime", ProcKind::Uptime),
+];
+
+const PROCESS_FILES: [&str; 5] = ["stat", "status", "cmdline", "cwd", "maps"];
+
+fn process_file(name: &str, pid: usize) -> Option<ProcKind> {
+    match name {
+        "stat" => Some(ProcKind::Stat(pid)),
+        "status" => Some(ProcKind::Status(pid)),
+        "cmdline" => Some(ProcKind::Cmdline(pid)),
+        "cwd" => Some(ProcKind::Cwd(pid)),
+        "maps" => Some(ProcKind::Maps(pid)),
+        _ => None,
+    }
+}
+
+fn meminfo() -> String {
+    let (frames, free_frames) = frame_stats();
+    let (heap_total, heap_used) = heap_stats();
+    let mut text = String::new();
+    for (name, bytes) in [
+        ("MemTotal:", frames * PAGE_SIZE),
+        ("MemFree:", free_frames * PAGE_SIZE),
+        ("KernelHeapTotal:", heap_total),
+        ("KernelHeapUsed:", heap_used),
+    ] {
+        writeln!(text, "{:<16}{:>8} kB", name, bytes / 1024).unwrap();
+    }
+    text
+}
+
+fn mounts() -> String {
+    let mut text = String::new();
+    for mnt in MNT_TABLE.lock().mounts() {
+        writeln!(text, "{} {} {} rw 0 0", mnt.special, mnt.dir, mnt.fstype).unwrap();
+    }
+    text
+}
+
+fn uptime() -> String {
+    let ms = get_time_ms();
+    // 不统计空闲时间
+    format!("{}.{:02} 0.00\n", ms / 1000, ms % 1000 / 10)
+}
+
+/// The content of a file of task `pid`, `None` if it has gone
+fn process_info(kind: ProcKind, pid: usize) -> Option<String> {
+    let task = pid2task(pid)?;
+    let tgid = task.gettgid();
+    let threads = thread_group(tgid).len();
+    let times = process_cpu_times(tgid);
+    let ppid = task
+        .inner_exclusive_access()
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+        .map_or(0, |parent| parent.getpid());
+    let inner = task.inner_exclusive_access();
+    // 进程名为 argv[0] 去掉目录，最长 15 个字符，在字符边界截断
+    let comm = inner.cmdline.first().map_or("", |arg0| arg0.rsplit('/').next().unwrap());
+    let comm = comm.char_indices().nth(15).map_or(comm, |(end, _)| &comm[..end]);
+    let (state, state_name) = match inner.task_status {
+        // 退出时已从 PID2TCB 中移除，只可能是刚刚退出
+        TaskStatus::Zombie => return None,
+        _ if inner.stopped => ('T', "stopped"),
+        TaskStatus::Ready | TaskStatus::Running => ('R', "running"),
+        TaskStatus::Blocked => ('S', "sleeping"),
+    };
+    let memory_set = inner.memory_set.lock();
+    let vsize = memory_set.mapped_size();
+    let rss = memory_set.resident_pages();
+    let mut text = String::new();
+    match kind {
+        ProcKind::Stat(_) => {
+            let ticks = |ns: usize| ns / (1_000_000_000 / USER_HZ);
+            writeln!(
+                text,
+                "{} ({}) {} {} {} {} 0 -1 0 0 0 0 0 {} {} {} {} {} {} {} 0 0 {} {}",
+                pid,
+                comm,
+                state,
+                ppid,
+                inner.pgid,
+                inner.sid,
+                ticks(times.utime),
+                ticks(times.stime),
+                ticks(times.cutime),
+                ticks(times.cstime),
+                inner.sched.nice + 20,
+                inner.sched.nice,
+                threads,
+                vsize,
+                rss
+            )
+            .unwrap();
+        }
+        ProcKind::Status(_) => {
+            writeln!(text, "Name:\t{}", comm).unwrap();
+            writeln!(text, "State:\t{} ({})", state, state_name).unwrap();
+            writeln!(text, "Tgid:\t{}", tgid).unwrap();
+            writeln!(text, "Pid:\t{}", pid).unwrap();
+            writeln!(text, "PPid:\t{}", ppid).unwrap();
+            writeln!(text, "Threads:\t{}", threads).unwrap();
+            writeln!(text, "VmSize:\t{} kB", vsize / 1024).unwrap();
+            writeln!(text, "VmRSS:\t{} kB", rss * PAGE_SIZE / 1024).unwrap();
+        }
+        ProcKind::Cmdline(_) => {
+            for arg in inner.cmdline.iter() {
+                text.push_str(arg);
+                text.push('\0');
+            }
+        }
+        ProcKind::Cwd(_) => writeln!(text, "{}", inner.work_path).unwrap(),
+        ProcKind::Maps(_) => {
+            for map in memory_set.maps() {
+                let flag = |perm: MapPermission, c: char| if map.perm.contains(perm) { c } else { '-' };
+                writeln!(
+                    text,
+                    "{:08x}-{:08x} {}{}{}{} {:08x} 00:00 0 {}",
+                    map.start,
+                    map.end,
+                    flag(MapPermission::R, 'r'),
+                    flag(MapPermission::W, 'w'),
+                    flag(MapPermission::X, 'x'),
+                    if map.shared { 's' } else { 'p' },
+                    map.offset,
+                    map.name
+                )
+                .unwrap();
+            }
+        }
+        ProcKind::Meminfo | ProcKind::Mounts | ProcKind::Uptime => return None,
+    }
+    Some(text)
+}
+
+/// A file of /proc, generated the first time it is read
+struct ProcFile {
+    name: &'static str,
+    kind: ProcKind,
+    data: SpinLock<Option<Vec<u8>>>,
+}
+
+impl ProcFile {
+    fn new(name: &'static str, kind: ProcKind) -> Arc<dyn Inode> {
+        Arc::new(Self {
+            name,
+            kind,
+            data: SpinLock::new(None),
+        })
+    }
+    /// Generate the content if not done yet, and use it
+    fn with_data<V>(&self, f: impl FnOnce(&[u8]) -> V) -> V {
+        let mut data = self.data.lock();
+        if data.is_none() {
+            let text = match self.kind {
+                ProcKind::Meminfo => meminfo(),
+                ProcKind::Mounts => mounts(),
+                ProcKind::Uptime => uptime(),
+                ProcKind::Stat(pid)
+                | ProcKind::Status(pid)
+                | ProcKind::Cmdline(pid)
+                | ProcKind::Cwd(pid)
+                | ProcKind::Maps(pid) => process_info(self.kind, pid).unwrap_or_default(),
+            };
+            *data = Some(text.into_bytes());
+        }
+        f(data.as_ref().unwrap())
+    }
+}
+
+impl Inode for ProcFile {
+    fn name(&self) -> String {
+        String::from(self.name)
+    }
+    fn is_dir(&self) -> bool {
+        false
+    }
+    fn size(&self) -> usize {
+        self.with_data(|data| data.len())
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.with_data(|data| {
+            if offset >= data.len() {
+                return 0;
+            }
+            let len = buf.len().min(data.len() - offset);
+            buf[..len].copy_from_slice(&data[offset..offset + len]);
+            len
+        })
+    }
+    #[allow(unused_variables)]
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+    fn clear(&self) {}
+    fn stat(&self, kstat: &mut Kstat) {
+        kstat.init(self.size() as i64, 512, 0);
+    }
+    #[allow(unused_variables)]
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    #[allow(unused_variables)]
+    fn create(&self, name: &str, dtype: DiskInodeType) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    #[allow(unused_variables)]
+    fn unlink(&self, name: &str) -> Result<(), Errno> {
+        Err(Errno::ENOTDIR)
+    }
+    #[allow(unused_variables)]
+    fn dirent(&self, offset: usize) -> Option<(String, usize)> {
+        None
+    }
+}
+
+/// /proc/<pid>
+struct ProcessDir {
+    pid: usize,
+}
+
+impl Inode for ProcessDir {
+    fn name(&self) -> String {
+        self.pid.to_string()
+    }
+    fn is_dir(&self) -> bool {
+        true
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    #[allow(unused_variables)]
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        0
+    }
+    #[allow(unused_variables)]
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+    fn clear(&self) {}
+    fn stat(&self, kstat: &mut Kstat) {
+        kstat.init(0, 512, 0);
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        let name = PROCESS_FILES.iter().find(|file| **file == name)?;
+        Some(ProcFile::new(*name, process_file(name, self.pid)?))
+    }
+    #[allow(unused_variables)]
+    fn create(&self, name: &str, dtype: DiskInodeType) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    #[allow(unused_variables)]
+    fn unlink(&self, name: &str) -> Result<(), Errno> {
+        Err(Errno::EPERM)
+    }
+    fn dirent(&self, offset: usize) -> Option<(String, usize)> {
+        PROCESS_FILES
+            .get(offset)
+            .map(|name| (String::from(*name), offset + 1))
+    }
+}
+
+/// /proc itself: the system files, then a directory per process
+struct ProcRoot;
+
+impl Inode for ProcRoot {
+    fn name(&self) -> String {
+        String::from("/")
+    }
+    fn is_dir(&self) -> bool {
+        true
+    }
+    fn size(&self) -> usize {
+        0
+    }
+    #[allow(unused_variables)]
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        0
+    }
+    #[allow(unused_variables)]
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+    fn clear(&self) {}
+    fn stat(&self, kstat: &mut Kstat) {
+        kstat.init(0, 512, 0);
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if let Some((name, kind)) = SYSTEM_FILES.iter().find(|(file, _)| *file == name) {
+            return Some(ProcFile::new(*name, *kind));
+        }
+        // 线程也能找到，但不列出，与 Linux 相同
+        let pid: usize = name.parse().ok()?;
+        pid2task(pid)?;
+        Some(Arc::new(ProcessDir { pid }))
+    }
+    #[allow(unused_variables)]
+    fn create(&self, name: &str, dtype: DiskInodeType) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    #[allow(unused_variables)]
+    fn unlink(&self, name: &str) -> Result<(), Errno> {
+        Err(Errno::EPERM)
+    }
+    /// The offset of a process directory is `SYSTEM_FILES.len()` plus its
+    /// pid, so processes exiting meanwhile don't shift the others
+    fn dirent(&self, offset: usize) -> Option<(String, usize)> {
+        if let Some((name, _)) = SYSTEM_FILES.get(offset) {
+            return Some((String::from(*name), offset + 1));
+        }
+        let next_tgid = offset - SYSTEM_FILES.len();
+        processes()
+            .iter()
+            .map(|task| task.getpid())
+            .filter(|tgid| *tgid >= next_tgid)
+            .min()
+            .map(|tgid| (tgid.to_string(), SYSTEM_FILES.len() + tgid + 1))
+    }
+}
+
+pub struct ProcSuperBlock;
+
+impl SuperBlock for ProcSuperBlock {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(ProcRoot)
+    }
+    fn fstype(&self) -> &'static str {
+        "proc"
+    }
+}
+
+pub struct ProcFs;
+
+impl Vfs for ProcFs {
+    fn name(&self) -> &'static str {
+        "proc"
+    }
+    fn mount(&self, _special: &str, _flags: u32) -> Result<Arc<dyn SuperBlock>, Errno> {
+        Ok(Arc::new(ProcSuperBlock))
+    }
+}
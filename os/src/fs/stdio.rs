@@ -3,15 +3,94 @@ use super::dir::DirEntry;
 use super::stat::Kstat;
 
 use super::File;
-use crate::mm::UserBuffer;
+use crate::console::print_bytes;
+use crate::mm::{copy_from_user, copy_to_user, UserBuffer};
 use crate::sbi::console_getchar;
-use crate::task::suspend_current_and_run_next;
+use crate::sync::SpinLock;
+use crate::syscall::{Errno, SysResult};
+use crate::task::{
+    current_blocks_signal, current_has_signal, current_task, process_group, send_signal_to_group, suspend_current_and_run_next, SignalFlags,
+};
+use alloc::collections::VecDeque;
 use alloc::string::String;
+use lazy_static::*;
 ///Standard input
 pub struct Stdin;
 ///Standard output
 pub struct Stdout;
 
+/// Ctrl-C，向前台进程组发送 SIGINT
+const CTRL_C: u8 = 0x03;
+/// Ctrl-Z，向前台进程组发送 SIGTSTP
+const CTRL_Z: u8 = 0x1a;
+/// `ioctl` request: get the foreground process group
+const TIOCGPGRP: usize = 0x540f;
+/// `ioctl` request: set the foreground process group
+const TIOCSPGRP: usize = 0x5410;
+
+/// The console as a terminal shared by stdin and stdout
+struct Tty {
+    /// characters typed but not read by any program yet
+    input: VecDeque<u8>,
+    /// the process group reading the input and receiving Ctrl-C/Ctrl-Z
+    foreground_pgrp: usize,
+}
+
+lazy_static! {
+    static ref TTY: SpinLock<Tty> = SpinLock::new(Tty {
+        input: VecDeque::new(),
+        // initproc 所在的进程组
+        foreground_pgrp: 0,
+    });
+}
+
+/// Take what has been typed on the console, Ctrl-C and Ctrl-Z are turned
+/// into signals to the foreground process group
+pub fn poll_console() {
+    loop {
+        let c = console_getchar();
+        // 没有输入时返回 0 或 -1
+        if c == 0 || c == usize::MAX {
+            return;
+        }
+        let mut tty = TTY.lock();
+        let signal = match c as u8 {
+            CTRL_C => SignalFlags::SIGINT,
+            CTRL_Z => SignalFlags::SIGTSTP,
+            ch => {
+                tty.input.push_back(ch);
+                continue;
+            }
+        };
+        let pgrp = tty.foreground_pgrp;
+        drop(tty);
+        send_signal_to_group(pgrp, signal);
+    }
+}
+
+/// `ioctl` on the console, the caller must not hold its own task
+fn tty_ioctl(request: usize, arg: usize) -> SysResult {
+    match request {
+        TIOCGPGRP => {
+            let pgrp = TTY.lock().foreground_pgrp as i32;
+            copy_to_user(arg as *mut i32, &pgrp)?;
+            Ok(0)
+        }
+        TIOCSPGRP => {
+            let pgrp = copy_from_user(arg as *const i32)?;
+            if pgrp < 0 {
+                return Err(Errno::EINVAL);
+            }
+            if process_group(pgrp as usize).is_empty() {
+                return Err(Errno::ESRCH);
+            }
+            TTY.lock().foreground_pgrp = pgrp as usize;
+            Ok(0)
+        }
+        _ => Err(Errno::ENOTTY),
+    }
+}
+
 impl File for Stdin {
     fn readable(&self) -> bool {
         true
@@ -19,26 +98,40 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
-    fn read(&self, mut user_buf: UserBuffer) -> usize {
-        assert_eq!(user_buf.len(), 1);
+    /// Read one character at most. A background process group is stopped
+    /// by SIGTTIN instead and gets `EINTR`, or `EIO` if it blocks or ignores
+    /// SIGTTIN. A signal interrupts the waiting with `EINTR`.
+    fn read(&self, mut user_buf: UserBuffer) -> Result<usize, Errno> {
+        if user_buf.len() == 0 {
+            return Ok(0);
+        }
+        let pgid = current_task().unwrap().inner_exclusive_access().pgid;
         // busy loop
-        let mut c: usize;
         loop {
-            c = console_getchar();
-            if c == 0 {
-                suspend_current_and_run_next();
-                continue;
-            } else {
-                break;
+            poll_console();
+            let mut tty = TTY.lock();
+            if tty.foreground_pgrp != pgid {
+                drop(tty);
+                if current_blocks_signal(SignalFlags::SIGTTIN) {
+                    return Err(Errno::EIO);
+                }
+                send_signal_to_group(pgid, SignalFlags::SIGTTIN);
+                return Err(Errno::EINTR);
             }
+            if let Some(ch) = tty.input.pop_front() {
+                unsafe {
+                    user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
+                }
+                return Ok(1);
+            }
+            drop(tty);
+            if current_has_signal() {
+                return Err(Errno::EINTR);
+            }
+            suspend_current_and_run_next();
         }
-        let ch = c as u8;
-        unsafe {
-            user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
-        }
-        1
     }
-    fn write(&self, _user_buf: UserBuffer) -> usize {
+    fn write(&self, _user_buf: UserBuffer) -> Result<usize, Errno> {
         panic!("Cannot write to stdin!");
     }
     #[allow(unused_variables)]
@@ -58,6 +151,10 @@ impl File for Stdin {
     fn set_offset(&self, offset: usize) {
         panic!("Stdin not implement set_offset");
     }
+
+    fn ioctl(&self, request: usize, arg: usize) -> SysResult {
+        tty_ioctl(request, arg)
+    }
 }
 
 impl File for Stdout {
@@ -67,14 +164,12 @@ impl File for Stdout {
     fn writable(&self) -> bool {
         true
     }
-    fn read(&self, _user_buf: UserBuffer) -> usize {
+    fn read(&self, _user_buf: UserBuffer) -> Result<usize, Errno> {
         panic!("Cannot read from stdout!");
     }
-    fn write(&self, user_buf: UserBuffer) -> usize {
-        for buffer in user_buf.buffers.iter() {
-            print!("{}", core::str::from_utf8(*buffer).unwrap());
-        }
-        user_buf.len()
+    fn write(&self, user_buf: UserBuffer) -> Result<usize, Errno> {
+        print_bytes(user_buf.buffers.iter().map(|buffer| &**buffer));
+        Ok(user_buf.len())
     }
     
     #[allow(unused_variables)]
@@ -94,4 +189,8 @@ impl File for Stdout {
     fn set_offset(&self, offset: usize) {
         panic!("Stdput not implement set_offset");
     }
+
+    fn ioctl(&self, request: usize, arg: usize) -> SysResult {
+        tty_ioctl(request, arg)
+    }
 }
@@ -0,0 +1,199 @@
+//! tmpfs：内存中的文件系统，文件内容放在 `frame_alloc` 分配的页中，
+//! 卸载或删除后即释放
+//!
+//! Every mount is a new empty filesystem.
+
+use super::stat::Kstat;
+use super::vfs::{DiskInodeType, Inode, SuperBlock, Vfs};
+use crate::config::PAGE_SIZE;
+use crate::mm::{frame_alloc, FrameTracker};
+use crate::sync::SpinLock;
+use crate::syscall::Errno;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+
+enum TmpContent {
+    /// Pages by index, holes left by writing past the end have no page and
+    /// read as zeros. The bytes past `size` in the last page are kept zero.
+    File { size: usize, pages: BTreeMap<usize, FrameTracker> },
+    Dir { entries: BTreeMap<String, Arc<TmpInode>> },
+}
+
+pub struct TmpInode {
+    name: String,
+    content: SpinLock<TmpContent>,
+}
+
+impl TmpInode {
+    fn new(name: &str, dtype: DiskInodeType) -> Arc<Self> {
+        let content = match dtype {
+            DiskInodeType::File => TmpContent::File {
+                size: 0,
+                pages: BTreeMap::new(),
+            },
+            DiskInodeType::Directory => TmpContent::Dir {
+                entries: BTreeMap::new(),
+            },
+        };
+        Arc::new(Self {
+            name: String::from(name),
+            content: SpinLock::new(content),
+        })
+    }
+}
+
+impl Inode for TmpInode {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn is_dir(&self) -> bool {
+        matches!(*self.content.lock(), TmpContent::Dir { .. })
+    }
+    fn size(&self) -> usize {
+        match &*self.content.lock() {
+            TmpContent::File { size, .. } => *size,
+            TmpContent::Dir { .. } => 0,
+        }
+    }
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let content = self.content.lock();
+        let (size, pages) = match &*content {
+            TmpContent::File { size, pages } => (*size, pages),
+            TmpContent::Dir { .. } => return 0,
+        };
+        let end = size.min(offset.saturating_add(buf.len()));
+        let mut pos = offset;
+        while pos < end {
+            let len = (PAGE_SIZE - pos % PAGE_SIZE).min(end - pos);
+            let dst = &mut buf[pos - offset..pos - offset + len];
+            match pages.get(&(pos / PAGE_SIZE)) {
+                Some(frame) => dst.copy_from_slice(&frame.ppn.get_bytes_array()[pos % PAGE_SIZE..][..len]),
+                None => dst.fill(0),
+            }
+            pos += len;
+        }
+        end.saturating_sub(offset)
+    }
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut content = self.content.lock();
+        let (size, pages) = match &mut *content {
+            TmpContent::File { size, pages } => (size, pages),
+            TmpContent::Dir { .. } => return 0,
+        };
+        let end = match offset.checked_add(buf.len()) {
+            Some(end) => end,
+            None => return 0,
+        };
+        // 只为写到的页分配，新页均已清零，内存不足时只写到之前的页
+        let mut pos = offset;
+        while pos < end {
+            let index = pos / PAGE_SIZE;
+            if !pages.contains_key(&index) {
+                match frame_alloc() {
+                    Some(frame) => pages.insert(index, frame),
+                    None => break,
+                };
+            }
+            let frame = &pages[&index];
+            let len = (PAGE_SIZE - pos % PAGE_SIZE).min(end - pos);
+            frame.ppn.get_bytes_array()[pos % PAGE_SIZE..][..len].copy_from_slice(&buf[pos - offset..pos - offset + len]);
+            pos += len;
+        }
+        if pos > *size {
+            *size = pos;
+        }
+        pos - offset
+    }
+    fn clear(&self) {
+        if let TmpContent::File { size, pages } = &mut *self.content.lock() {
+            *size = 0;
+            pages.clear();
+        }
+    }
+    /// Growing leaves a hole, shrinking frees the pages past `len`
+    fn truncate(&self, len: usize) -> Result<(), Errno> {
+        match &mut *self.content.lock() {
+            TmpContent::File { size, pages } => {
+                pages.split_off(&((len + PAGE_SIZE - 1) / PAGE_SIZE));
+                if let Some(frame) = pages.get(&(len / PAGE_SIZE)) {
+                    frame.ppn.get_bytes_array()[len % PAGE_SIZE..].fill(0);
+                }
+                *size = len;
+                Ok(())
+            }
+            TmpContent::Dir { .. } => Err(Errno::EISDIR),
+        }
+    }
+    fn stat(&self, kstat: &mut Kstat) {
+        let (size, blocks) = match &*self.content.lock() {
+            TmpContent::File { size, pages } => (*size, pages.len() * PAGE_SIZE / 512),
+            TmpContent::Dir { .. } => (0, 0),
+        };
+        kstat.init(size as i64, PAGE_SIZE as i64, blocks as u64);
+    }
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        match &*self.content.lock() {
+            TmpContent::Dir { entries } => entries.get(name).map(|inode| inode.clone() as Arc<dyn Inode>),
+            TmpContent::File { .. } => None,
+        }
+    }
+    fn create(&self, name: &str, dtype: DiskInodeType) -> Option<Arc<dyn Inode>> {
+        match &mut *self.content.lock() {
+            TmpContent::Dir { entries } if !entries.contains_key(name) => {
+                let inode = TmpInode::new(name, dtype);
+                entries.insert(String::from(name), inode.clone());
+                Some(inode)
+            }
+            _ => None,
+        }
+    }
+    fn unlink(&self, name: &str) -> Result<(), Errno> {
+        let mut content = self.content.lock();
+        let entries = match &mut *content {
+            TmpContent::Dir { entries } => entries,
+            TmpContent::File { .. } => return Err(Errno::ENOTDIR),
+        };
+        let inode = entries.get(name).ok_or(Errno::ENOENT)?;
+        if let TmpContent::Dir { entries } = &*inode.content.lock() {
+            if !entries.is_empty() {
+                return Err(Errno::ENOTEMPTY);
+            }
+        }
+        // 打开着的文件仍持有 inode，关闭后才释放其页
+        entries.remove(name);
+        Ok(())
+    }
+    fn dirent(&self, offset: usize) -> Option<(String, usize)> {
+        match &*self.content.lock() {
+            TmpContent::Dir { entries } => entries.keys().nth(offset).map(|name| (name.clone(), offset + 1)),
+            TmpContent::File { .. } => None,
+        }
+    }
+}
+
+pub struct TmpSuperBlock {
+    root: Arc<TmpInode>,
+}
+
+impl SuperBlock for TmpSuperBlock {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+    fn fstype(&self) -> &'static str {
+        "tmpfs"
+    }
+}
+
+pub struct TmpFs;
+
+impl Vfs for TmpFs {
+    fn name(&self) -> &'static str {
+        "tmpfs"
+    }
+    fn mount(&self, _special: &str, _flags: u32) -> Result<Arc<dyn SuperBlock>, Errno> {
+        Ok(Arc::new(TmpSuperBlock {
+            root: TmpInode::new("/", DiskInodeType::Directory),
+        }))
+    }
+}
@@ -0,0 +1,131 @@
+//! 虚拟文件系统层：文件系统实现 [`Vfs`]，挂载后得到 [`SuperBlock`]，
+//! 其中的文件与目录都是 [`Inode`]
+//!
+//! 路径总是先规范化为绝对路径，再由挂载表找到它所在的文件系统，
+//! 因此查找会跨过挂载点。
+
+use super::devfs::DevFs;
+use super::fat32::Fat32Fs;
+use super::procfs::ProcFs;
+use super::tmpfs::TmpFs;
+use super::stat::Kstat;
+use super::{File, MNT_TABLE};
+use crate::syscall::Errno;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// 创建文件时的类型
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DiskInodeType {
+    File,
+    Directory,
+}
+
+/// A file or a directory of some filesystem
+pub trait Inode: Send + Sync {
+    /// Name in the parent directory, `/` for the root
+    fn name(&self) -> String;
+    fn is_dir(&self) -> bool;
+    /// Size of the file in bytes
+    fn size(&self) -> usize;
+    /// The largest size a file of the filesystem can have
+    fn max_size(&self) -> usize {
+        usize::MAX
+    }
+    /// Read from `offset`, return the bytes read, 0 at the end of file
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize;
+    /// Write at `offset`, the file grows as needed. Return the bytes
+    /// written, fewer than `buf` holds once the filesystem is full
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize;
+    /// Drop the content of the file
+    fn clear(&self);
+    /// Cut the file to `len` bytes or extend it with zeros, for filesystems
+    /// which can
+    #[allow(unused_variables)]
+    fn truncate(&self, len: usize) -> Result<(), Errno> {
+        Err(Errno::EINVAL)
+    }
+    fn stat(&self, kstat: &mut Kstat);
+    /// The entry `name` of this directory
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>>;
+    /// Create the entry `name` in this directory
+    fn create(&self, name: &str, dtype: DiskInodeType) -> Option<Arc<dyn Inode>>;
+    /// Remove the entry `name` of this directory
+    fn unlink(&self, name: &str) -> Result<(), Errno>;
+    /// The name of the directory entry at `offset` and the offset of the next one
+    fn dirent(&self, offset: usize) -> Option<(String, usize)>;
+    /// The device a device file stands for, opening the file opens it
+    fn device(&self) -> Option<Arc<dyn File + Send + Sync>> {
+        None
+    }
+}
+
+/// A mounted instance of a filesystem
+pub trait SuperBlock: Send + Sync {
+    fn root(&self) -> Arc<dyn Inode>;
+    fn fstype(&self) -> &'static str;
+}
+
+/// A filesystem type, which `sys_mount` finds by name
+pub trait Vfs: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Mount the filesystem on device `special`
+    fn mount(&self, special: &str, flags: u32) -> Result<Arc<dyn SuperBlock>, Errno>;
+}
+
+lazy_static! {
+    /// 内核支持的文件系统
+    static ref FILESYSTEMS: Vec<Arc<dyn Vfs>> = vec![Arc::new(Fat32Fs) as Arc<dyn Vfs>, Arc::new(TmpFs), Arc::new(DevFs), Arc::new(ProcFs)];
+}
+
+/// The filesystem type called `name`
+pub fn find_filesystem(name: &str) -> Option<Arc<dyn Vfs>> {
+    FILESYSTEMS.iter().find(|fs| fs.name() == name).cloned()
+}
+
+/// Turn `path` relative to `work_path` into an absolute path without `.`,
+/// `..` or empty names
+pub fn absolute_path(work_path: &str, path: &str) -> String {
+    let base = if path.starts_with('/') { "" } else { work_path };
+    let mut names: Vec<&str> = Vec::new();
+    for name in base.split('/').chain(path.split('/')) {
+        match name {
+            "" | "." => {}
+            ".." => {
+                names.pop();
+            }
+            _ => names.push(name),
+        }
+    }
+    if names.is_empty() {
+        return String::from("/");
+    }
+    let mut abs = String::new();
+    for name in names {
+        abs.push('/');
+        abs.push_str(name);
+    }
+    abs
+}
+
+/// Split an absolute path into its parent directory and last name, the
+/// name of `/` is empty
+pub fn split_path(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(0) => ("/", &path[1..]),
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => ("/", path),
+    }
+}
+
+/// Find the inode at the absolute `path`, crossing mount points
+pub fn lookup(path: &str) -> Option<Arc<dyn Inode>> {
+    let (mut inode, rest) = MNT_TABLE.lock().resolve(path);
+    for name in rest.split('/').filter(|name| !name.is_empty()) {
+        inode = inode.lookup(name)?;
+    }
+    Some(inode)
+}
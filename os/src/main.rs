@@ -25,6 +25,7 @@
 #![no_main]
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
+#![feature(asm_const)]
 
 extern crate alloc;
 
@@ -41,6 +42,7 @@ mod drivers;
 pub mod fs;
 pub mod lang_items;
 pub mod mm;
+mod random;
 pub mod sbi;
 pub mod sync;
 pub mod syscall;
@@ -48,9 +50,15 @@ pub mod task;
 pub mod timer;
 pub mod trap;
 
+use config::{BOOT_STACK_SIZE, MAX_HARTS};
 use core::arch::global_asm;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-global_asm!(include_str!("entry.asm"));
+global_asm!(
+    include_str!("entry.asm"),
+    boot_stack_size = const BOOT_STACK_SIZE,
+    max_harts = const MAX_HARTS,
+);
 global_asm!(include_str!("link_app.S"));
 /// clear BSS segment
 fn clear_bss() {
@@ -64,9 +72,35 @@ fn clear_bss() {
     }
 }
 
+/// set by the boot hart once the kernel is initialized
+static BOOTED: AtomicBool = AtomicBool::new(false);
+
+/// start the other harts through SBI HSM, they enter `_start` too
+fn start_other_harts(boot_hart_id: usize) {
+    extern "C" {
+        fn _start();
+    }
+    for hart_id in (0..MAX_HARTS).filter(|&hart_id| hart_id != boot_hart_id) {
+        // harts QEMU does not provide fail to start
+        let ret = sbi_rt::hart_start(hart_id, _start as usize, 0);
+        if ret.error == 0 {
+            println!("[kernel] starting hart {}", hart_id);
+        }
+    }
+}
+
 #[no_mangle]
 /// the rust entry-point of os
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize) -> ! {
+    if BOOTED.load(Ordering::Acquire) {
+        // a secondary hart, everything is initialized by the boot hart
+        mm::init_hart();
+        trap::init();
+        trap::enable_timer_interrupt();
+        timer::set_next_trigger();
+        println!("[kernel] hart {} is running", hart_id);
+        task::run_tasks();
+    }
     clear_bss();
     println!("[kernel] Hello, world!");
     mm::init();
@@ -76,7 +110,9 @@ pub fn rust_main() -> ! {
     timer::set_next_trigger();
     fs::list_apps();
     fs::add_initproc_shell();
+    fs::init_mounts();
     task::add_initproc();
+    BOOTED.store(true, Ordering::Release);
+    start_other_harts(hart_id);
     task::run_tasks();
-    panic!("Unreachable in rust_main!");
 }
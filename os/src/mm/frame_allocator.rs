@@ -2,7 +2,8 @@
 //! controls all the frames in the operating system.
 use super::{PhysAddr, PhysPageNum};
 use crate::{config::MEMORY_END, console::print};
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::*;
@@ -23,6 +24,12 @@ impl FrameTracker {
         }
         Self { ppn }
     }
+    /// Create another owner of the same frame without copying it,
+    /// used by copy-on-write fork
+    pub fn share(&self) -> Self {
+        frame_add_ref(self.ppn);
+        Self { ppn: self.ppn }
+    }
 }
 
 impl Debug for FrameTracker {
@@ -41,16 +48,23 @@ trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
+    fn add_ref(&mut self, ppn: PhysPageNum);
+    fn ref_count(&self, ppn: PhysPageNum) -> usize;
 }
 /// an implementation for frame allocator
 pub struct StackFrameAllocator {
+    start: usize,
     current: usize,
     end: usize,
     recycled: Vec<usize>,
+    /// extra owners of frames shared between address spaces,
+    /// a frame absent from it has exactly one owner
+    shared: BTreeMap<usize, usize>,
 }
 
 impl StackFrameAllocator {
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.start = l.0;
         self.current = l.0;
         self.end = r.0;
         println!("last {} Physical Frames.", self.end - self.current);
@@ -59,9 +73,11 @@ impl StackFrameAllocator {
 impl FrameAllocator for StackFrameAllocator {
     fn new() -> Self {
         Self {
+            start: 0,
             current: 0,
             end: 0,
             recycled: Vec::new(),
+            shared: BTreeMap::new(),
         }
     }
     fn alloc(&mut self) -> Option<PhysPageNum> {
@@ -76,6 +92,14 @@ impl FrameAllocator for StackFrameAllocator {
     }
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn = ppn.0;
+        // drop one owner of a shared frame
+        if let Some(count) = self.shared.get_mut(&ppn) {
+            *count -= 1;
+            if *count == 0 {
+                self.shared.remove(&ppn);
+            }
+            return;
+        }
         // validity check
         if ppn >= self.current || self.recycled.iter().any(|&v| v == ppn) {
             panic!("Frame ppn={:#x} has not been allocated!", ppn);
@@ -83,21 +107,34 @@ impl FrameAllocator for StackFrameAllocator {
         // recycle
         self.recycled.push(ppn);
     }
+    fn add_ref(&mut self, ppn: PhysPageNum) {
+        *self.shared.entry(ppn.0).or_insert(0) += 1;
+    }
+    fn ref_count(&self, ppn: PhysPageNum) -> usize {
+        self.shared.get(&ppn.0).map_or(1, |count| count + 1)
+    }
+}
+
+impl StackFrameAllocator {
+    /// Number of frames managed and of those free
+    pub fn stats(&self) -> (usize, usize) {
+        (self.end - self.start, self.end - self.current + self.recycled.len())
+    }
 }
 
 type FrameAllocatorImpl = StackFrameAllocator;
 
 lazy_static! {
     /// frame allocator instance through lazy_static!
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
-        unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinLock<FrameAllocatorImpl> =
+        SpinLock::new(FrameAllocatorImpl::new());
 }
 /// initiate the frame allocator using `ekernel` and `MEMORY_END`
 pub fn init_frame_allocator() {
     extern "C" {
         fn ekernel();
     }
-    FRAME_ALLOCATOR.exclusive_access().init(
+    FRAME_ALLOCATOR.lock().init(
         PhysAddr::from(ekernel as usize).ceil(),
         PhysAddr::from(MEMORY_END).floor(),
     );
@@ -105,13 +142,25 @@ pub fn init_frame_allocator() {
 /// allocate a frame
 pub fn frame_alloc() -> Option<FrameTracker> {
     FRAME_ALLOCATOR
-        .exclusive_access()
+        .lock()
         .alloc()
         .map(FrameTracker::new)
 }
+/// Number of physical frames in all and of the free ones
+pub fn frame_stats() -> (usize, usize) {
+    FRAME_ALLOCATOR.lock().stats()
+}
 /// deallocate a frame
 pub fn frame_dealloc(ppn: PhysPageNum) {
-    FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
+    FRAME_ALLOCATOR.lock().dealloc(ppn);
+}
+/// add an owner to an allocated frame
+pub fn frame_add_ref(ppn: PhysPageNum) {
+    FRAME_ALLOCATOR.lock().add_ref(ppn);
+}
+/// number of owners of an allocated frame
+pub fn frame_ref_count(ppn: PhysPageNum) -> usize {
+    FRAME_ALLOCATOR.lock().ref_count(ppn)
 }
 
 #[allow(unused)]
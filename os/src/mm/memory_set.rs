@@ -1,18 +1,22 @@
 //! Implementation of [`MapArea`] and [`MemorySet`].
-use super::{frame_alloc, FrameTracker};
+use super::{frame_alloc, frame_ref_count, FrameTracker};
 use super::{PTEFlags, PageTable, PageTableEntry, translated_byte_buffer, UserBuffer};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE, USER_HEAP_SIZE};
-use crate::fs::{FileDescriptor, FileType};
-use crate::{fs::File};
-use crate::sync::UPSafeCell;
+use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_HEAP_MAX, SIGRETURN_TRAMPOLINE};
+use crate::config::{ELF_INTERP_BASE, ELF_PIE_BASE};
+use crate::fs::OSInode;
+use crate::sync::SpinLock;
+use crate::syscall::Errno;
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::arch::asm;
 use lazy_static::*;
 use riscv::register::satp;
+use xmas_elf::ElfFile;
 use log::{warn, trace};
 
 extern "C" {
@@ -28,14 +32,35 @@ extern "C" {
     fn strampoline();
 }
 
+/// Code of the page at `SIGRETURN_TRAMPOLINE`: `li a7, 139; ecall`,
+/// i.e. `rt_sigreturn`
+const SIGRETURN_CODE: [u8; 8] = [0x93, 0x08, 0xb0, 0x08, 0x73, 0x00, 0x00, 0x00];
+
+/// 辅助向量的类型，见 Linux `include/uapi/linux/auxvec.h`
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_BASE: usize = 7;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+/// 辅助向量的一项，放在用户栈上 `envp` 之后
+#[derive(Copy, Clone)]
+pub struct AuxHeader {
+    pub aux_type: usize,
+    pub value: usize,
+}
+
 lazy_static! {
     /// a memory set instance through lazy_static! managing kernel space
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinLock<MemorySet>> =
+        Arc::new(SpinLock::new(MemorySet::new_kernel()));
 }
 ///Get kernelspace root ppn
 pub fn kernel_token() -> usize {
-    KERNEL_SPACE.exclusive_access().token()
+    KERNEL_SPACE.lock().token()
 }
 
 /// memory set structure, controls virtual-memory space
@@ -90,37 +115,168 @@ impl MemorySet {
             .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
         {
             area.unmap(&mut self.page_table);
-            self.mmap_areas.remove(idx);
+            self.areas.remove(idx);
         }
     }
-    //移除指定的MMapAreas区域
+    //移除指定的MMapAreas区域，共享的文件映射先写回文件
     pub fn remove_MMapArea_with_start_vpn(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> isize {
         if let Some((idx, area)) = self
             .mmap_areas
             .iter_mut()
             .enumerate()
-            .find(|(_, area)| area.vpn_range.get_start() == start_vpn && area.vpn_range.get_start() == end_vpn)
+            .find(|(_, area)| area.vpn_range.get_start() == start_vpn && area.vpn_range.get_end() == end_vpn)
         {
+            area.sync(&mut self.page_table);
             area.unmap(&mut self.page_table);
-            self.areas.remove(idx);
+            self.mmap_areas.remove(idx);
             0
         } else {
             -1
         }
     }
+    /// Whether every page of `[start_vpn, end_vpn)` belongs to a user area
+    fn user_range_mapped(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        let mut covered = 0;
+        for (range, area_perm) in self
+            .areas
+            .iter()
+            .map(|area| (area.vpn_range, area.map_perm))
+            .chain(self.mmap_areas.iter().map(|area| (area.vpn_range, area.map_perm)))
+        {
+            let lo = range.get_start().0.max(start_vpn.0);
+            let hi = range.get_end().0.min(end_vpn.0);
+            let pages = hi.saturating_sub(lo);
+            if pages > 0 && !area_perm.contains(MapPermission::U) {
+                return false;
+            }
+            covered += pages;
+        }
+        covered == end_vpn.0 - start_vpn.0
+    }
+    /// Change the permission of the user pages in `[start, start + len)` to
+    /// `perm`, splitting the areas crossing the boundaries
+    pub fn mprotect(&mut self, start: usize, len: usize, perm: MapPermission) -> isize {
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        let overlap = |range: VPNRange| {
+            let lo = range.get_start().0.max(start_vpn.0);
+            let hi = range.get_end().0.min(end_vpn.0);
+            hi.saturating_sub(lo)
+        };
+        if !self.user_range_mapped(start_vpn, end_vpn) {
+            return -1;
+        }
+        let mut i = 0;
+        while i < self.areas.len() {
+            let range = self.areas[i].vpn_range;
+            if overlap(range) == 0 {
+                i += 1;
+                continue;
+            }
+            if range.get_start() < start_vpn {
+                let tail = self.areas[i].split_off(start_vpn);
+                self.areas.insert(i + 1, tail);
+                i += 1;
+                continue;
+            }
+            if range.get_end() > end_vpn {
+                let tail = self.areas[i].split_off(end_vpn);
+                self.areas.insert(i + 1, tail);
+            }
+            self.areas[i].protect(&mut self.page_table, perm);
+            i += 1;
+        }
+        let mut i = 0;
+        while i < self.mmap_areas.len() {
+            let range = self.mmap_areas[i].vpn_range;
+            if overlap(range) == 0 {
+                i += 1;
+                continue;
+            }
+            if range.get_start() < start_vpn {
+                let tail = self.mmap_areas[i].split_off(start_vpn);
+                self.mmap_areas.insert(i + 1, tail);
+                i += 1;
+                continue;
+            }
+            if range.get_end() > end_vpn {
+                let tail = self.mmap_areas[i].split_off(end_vpn);
+                self.mmap_areas.insert(i + 1, tail);
+            }
+            self.mmap_areas[i].protect(&mut self.page_table, perm);
+            i += 1;
+        }
+        unsafe {
+            asm!("sfence.vma");
+        }
+        0
+    }
+    /// 将 `[start, start + len)` 中共享文件映射的脏页写回文件，
+    /// 范围内有未映射的页时返回 -1
+    pub fn msync(&mut self, start: usize, len: usize) -> isize {
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        if !self.user_range_mapped(start_vpn, end_vpn) {
+            return -1;
+        }
+        for area in self.mmap_areas.iter_mut() {
+            if area.vpn_range.get_end() <= start_vpn || area.vpn_range.get_start() >= end_vpn {
+                continue;
+            }
+            area.sync(&mut self.page_table);
+        }
+        // the cleared dirty bits may still be cached
+        unsafe {
+            asm!("sfence.vma");
+        }
+        0
+    }
 
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
+    fn push(&mut self, map_area: MapArea, data: Option<&[u8]>) {
+        self.push_with_offset(map_area, 0, data);
+    }
+    /// Like `push`, but `data` starts `offset` bytes into the first page
+    fn push_with_offset(&mut self, mut map_area: MapArea, offset: usize, data: Option<&[u8]>) {
         map_area.map(&mut self.page_table);
         if let Some(data) = data {
-            map_area.copy_data(&mut self.page_table, data);
+            map_area.copy_data(&mut self.page_table, data, offset);
         }
         self.areas.push(map_area);
     }
+    /// Add an area whose frames are allocated on first access by `handle_page_fault`
+    fn push_lazy(&mut self, map_area: MapArea) {
+        self.areas.push(map_area);
+    }
     //ztr_mmap
-    pub fn push_mmap_area(&mut self, mut mmap_area: MMapArea, fd_table: Vec<Option<FileDescriptor>>) -> isize {
-        let tags = mmap_area.map(&mut self.page_table, fd_table);
+    /// mmap 区域的页在第一次访问时才分配
+    pub fn push_mmap_area(&mut self, mmap_area: MMapArea) {
         self.mmap_areas.push(mmap_area);
-        return tags
+    }
+    /// Check that no area overlaps `[start_vpn, end_vpn)`
+    pub fn is_free_range(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        self.areas
+            .iter()
+            .map(|area| area.vpn_range)
+            .chain(self.mmap_areas.iter().map(|area| area.vpn_range))
+            .all(|range| range.get_end() <= start_vpn || range.get_start() >= end_vpn)
+    }
+    /// Map a trap context page for a new thread in the lowest free slot below
+    /// `TRAP_CONTEXT`, return its virtual address
+    pub fn alloc_trap_cx(&mut self) -> usize {
+        let mut trap_cx_va = TRAP_CONTEXT - PAGE_SIZE;
+        while !self.is_free_range(VirtAddr::from(trap_cx_va).floor(), VirtAddr::from(trap_cx_va + PAGE_SIZE).ceil()) {
+            trap_cx_va -= PAGE_SIZE;
+        }
+        self.insert_framed_area(
+            trap_cx_va.into(),
+            (trap_cx_va + PAGE_SIZE).into(),
+            MapPermission::R | MapPermission::W,
+        );
+        trap_cx_va
+    }
+    /// Unmap the trap context page of an exited thread
+    pub fn dealloc_trap_cx(&mut self, trap_cx_va: usize) {
+        self.remove_area_with_start_vpn(VirtAddr::from(trap_cx_va).floor());
     }
     /// Mention that trampoline is not collected by areas.
     fn map_trampoline(&mut self) {
@@ -207,26 +363,31 @@ impl MemorySet {
         }
         memory_set
     }
-    /// Include sections in elf and trampoline and TrapContext and user stack,
-    /// also returns user_sp and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
-        let mut memory_set = Self::new_bare();
-        // map trampoline
-        memory_set.map_trampoline();
-        // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
-        let ph_count = elf_header.pt2.ph_count();
+    /// Map the PT_LOAD segments of `elf` shifted by `bias`, return the end
+    /// of the last segment and where the program headers are mapped.
+    /// A malformed header or a segment overlapping another mapping gives ENOEXEC.
+    fn map_elf(&mut self, elf: &ElfFile, bias: usize) -> Result<(VirtPageNum, usize), Errno> {
+        let ph_count = elf.header.pt2.ph_count();
+        let ph_offset = elf.header.pt2.ph_offset() as usize;
+        // where the program headers are mapped, the user program looks them up through AT_PHDR
+        let mut phdr = 0;
         let mut max_end_vpn = VirtPageNum(0);
-        //ztr_brk
-        let mut program_break = 0;
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+            let ph = elf.program_header(i).map_err(|_| Errno::ENOEXEC)?;
+            let ph_type = ph.get_type().map_err(|_| Errno::ENOEXEC)?;
+            if ph_type == xmas_elf::program::Type::Phdr {
+                phdr = bias + ph.virtual_addr() as usize;
+            }
+            if ph_type == xmas_elf::program::Type::Load {
+                let file_range = ph.offset() as usize..(ph.offset() + ph.file_size()) as usize;
+                if elf.input.get(file_range.clone()).is_none() {
+                    return Err(Errno::ENOEXEC);
+                }
+                if phdr == 0 && file_range.contains(&ph_offset) {
+                    phdr = bias + ph.virtual_addr() as usize + ph_offset - ph.offset() as usize;
+                }
+                let start_va: VirtAddr = (bias + ph.virtual_addr() as usize).into();
+                let end_va: VirtAddr = (bias + (ph.virtual_addr() + ph.mem_size()) as usize).into();
                 let mut map_perm = MapPermission::U;
                 let ph_flags = ph.flags();
                 if ph_flags.is_read() {
@@ -239,74 +400,215 @@ impl MemorySet {
                     map_perm |= MapPermission::X;
                 }
                 let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_range.get_end();
-                //ztr_brk
-                program_break = VirtAddr::from(end_va.ceil()).0;
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                if !self.is_free_range(map_area.vpn_range.get_start(), map_area.vpn_range.get_end()) {
+                    return Err(Errno::ENOEXEC);
+                }
+                max_end_vpn = max_end_vpn.max(map_area.vpn_range.get_end());
+                // segments of compiled programs rarely start at a page boundary
+                self.push_with_offset(map_area, start_va.page_offset(), Some(&elf.input[file_range]));
+            }
+        }
+        Ok((max_end_vpn, phdr))
+    }
+    /// Apply the `R_RISCV_RELATIVE` relocations of an executable mapped at
+    /// `bias`. Others need symbols, which only a dynamic linker resolves.
+    fn relocate(&mut self, elf: &ElfFile, bias: usize) {
+        const DT_NULL: u64 = 0;
+        const DT_RELA: u64 = 7;
+        const DT_RELASZ: u64 = 8;
+        const DT_RELAENT: u64 = 9;
+        const R_RISCV_RELATIVE: u64 = 3;
+        let read_u64 = |offset: usize| {
+            let bytes = elf.input.get(offset..offset.checked_add(8)?)?;
+            Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+        };
+        // file offset of a virtual address in the image
+        let file_offset = |va: u64| {
+            (0..elf.header.pt2.ph_count())
+                .filter_map(|i| elf.program_header(i).ok())
+                .filter(|ph| ph.get_type() == Ok(xmas_elf::program::Type::Load))
+                .find(|ph| ph.virtual_addr() <= va && va < ph.virtual_addr() + ph.file_size())
+                .map(|ph| (ph.offset() + va - ph.virtual_addr()) as usize)
+        };
+        let dynamic = match (0..elf.header.pt2.ph_count())
+            .filter_map(|i| elf.program_header(i).ok())
+            .find(|ph| ph.get_type() == Ok(xmas_elf::program::Type::Dynamic))
+        {
+            Some(dynamic) => dynamic,
+            None => return,
+        };
+        let (mut rela, mut relasz, mut relaent) = (0, 0, 24);
+        let mut entry = dynamic.offset() as usize;
+        while entry + 16 <= (dynamic.offset() + dynamic.file_size()) as usize {
+            let (tag, value) = match (read_u64(entry), read_u64(entry + 8)) {
+                (Some(tag), Some(value)) => (tag, value),
+                _ => return,
+            };
+            match tag {
+                DT_NULL => break,
+                DT_RELA => rela = value,
+                DT_RELASZ => relasz = value,
+                DT_RELAENT => relaent = value,
+                _ => {}
             }
+            entry += 16;
         }
+        let rela_offset = match file_offset(rela) {
+            Some(offset) if relasz != 0 => offset,
+            _ => return,
+        };
+        if relaent < 24 {
+            return;
+        }
+        for i in 0..(relasz / relaent) as usize {
+            let entry = rela_offset + i * relaent as usize;
+            let (offset, info, addend) = match (read_u64(entry), read_u64(entry + 8), read_u64(entry + 16)) {
+                (Some(offset), Some(info), Some(addend)) => (offset, info, addend),
+                _ => return,
+            };
+            if info & 0xffff_ffff == R_RISCV_RELATIVE {
+                let va = VirtAddr::from(bias + offset as usize);
+                if let Some(pa) = self.translate_va(va) {
+                    *pa.get_mut::<u64>() = (bias as u64).wrapping_add(addend);
+                }
+            }
+        }
+    }
+    /// The path of the dynamic linker requested by PT_INTERP, if any
+    pub fn elf_interp(elf_data: &[u8]) -> Option<String> {
+        let elf = xmas_elf::ElfFile::new(elf_data).ok()?;
+        let ph = (0..elf.header.pt2.ph_count())
+            .filter_map(|i| elf.program_header(i).ok())
+            .find(|ph| ph.get_type() == Ok(xmas_elf::program::Type::Interp))?;
+        let path = elf_data.get(ph.offset() as usize..(ph.offset() + ph.file_size()) as usize)?;
+        let path = path.split(|byte| *byte == 0).next()?;
+        core::str::from_utf8(path).ok().map(String::from)
+    }
+    /// Include sections in elf and trampoline and TrapContext and user stack,
+    /// also return the user stack top, the entry point and the auxiliary
+    /// vector. A position-independent executable is loaded at `ELF_PIE_BASE`,
+    /// and `interp_data`, the dynamic linker it asks for, at `ELF_INTERP_BASE`.
+    /// The user stack is `stack_size` bytes, a multiple of `PAGE_SIZE`.
+    /// Either image failing to parse or not fitting in the address space
+    /// gives ENOEXEC.
+    pub fn from_elf(
+        elf_data: &[u8],
+        interp_data: Option<&[u8]>,
+        stack_size: usize,
+    ) -> Result<(Self, usize, usize, Vec<AuxHeader>), Errno> {
+        let parse = |data| match ElfFile::new(data) {
+            Ok(elf) if elf.header.pt1.magic == [0x7f, 0x45, 0x4c, 0x46] => Ok(elf),
+            _ => Err(Errno::ENOEXEC),
+        };
+        let elf = parse(elf_data)?;
+        let interp = interp_data.map(parse).transpose()?;
+        let mut memory_set = Self::new_bare();
+        // map trampoline
+        memory_set.map_trampoline();
+        // map program headers of elf, with U flag
+        let elf_header = elf.header;
+        let bias = match elf_header.pt2.type_().as_type() {
+            xmas_elf::header::Type::SharedObject => ELF_PIE_BASE,
+            _ => 0,
+        };
+        let (max_end_vpn, phdr) = memory_set.map_elf(&elf, bias)?;
+        let program_entry = bias + elf_header.pt2.entry_point() as usize;
+        let mut auxv = vec![
+            AuxHeader { aux_type: AT_PHDR, value: phdr },
+            AuxHeader { aux_type: AT_PHENT, value: elf_header.pt2.ph_entry_size() as usize },
+            AuxHeader { aux_type: AT_PHNUM, value: elf_header.pt2.ph_count() as usize },
+            AuxHeader { aux_type: AT_PAGESZ, value: PAGE_SIZE },
+            AuxHeader { aux_type: AT_ENTRY, value: program_entry },
+        ];
+        // the dynamic linker runs first, then jumps to AT_ENTRY
+        let entry_point = match interp {
+            Some(interp) => {
+                memory_set.map_elf(&interp, ELF_INTERP_BASE)?;
+                auxv.push(AuxHeader { aux_type: AT_BASE, value: ELF_INTERP_BASE });
+                ELF_INTERP_BASE + interp.header.pt2.entry_point() as usize
+            }
+            None => {
+                if bias != 0 {
+                    // nobody else relocates a static PIE
+                    memory_set.relocate(&elf, bias);
+                }
+                program_entry
+            }
+        };
         // map user stack with U flags
         let max_end_va: VirtAddr = max_end_vpn.into();
         let mut user_stack_bottom: usize = max_end_va.into();
         // guard page
         user_stack_bottom += PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        let user_stack_top = user_stack_bottom + stack_size;
+        // the stack and the heap above it must not run into the dynamic linker
+        let user_heap_top = user_stack_top + PAGE_SIZE + USER_HEAP_MAX;
+        if !memory_set.is_free_range(VirtAddr::from(user_stack_bottom).floor(), VirtAddr::from(user_heap_top).ceil()) {
+            return Err(Errno::ENOEXEC);
+        }
+        // 栈的页在第一次访问时分配，`stack_size` 只是它能增长到的大小
+        memory_set.push_lazy(MapArea::new(
+            user_stack_bottom.into(),
+            user_stack_top.into(),
+            MapType::Framed,
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        ));
+        // map TrapContext
         memory_set.push(
             MapArea::new(
-                user_stack_bottom.into(),
-                user_stack_top.into(),
+                TRAP_CONTEXT.into(),
+                SIGRETURN_TRAMPOLINE.into(),
                 MapType::Framed,
-                MapPermission::R | MapPermission::W | MapPermission::U,
+                MapPermission::R | MapPermission::W,
             ),
             None,
         );
-        // map TrapContext
+        // map the return path of signal handlers
         memory_set.push(
             MapArea::new(
-                TRAP_CONTEXT.into(),
+                SIGRETURN_TRAMPOLINE.into(),
                 TRAMPOLINE.into(),
                 MapType::Framed,
-                MapPermission::R | MapPermission::W,
+                MapPermission::R | MapPermission::X | MapPermission::U,
             ),
-            None,
+            Some(&SIGRETURN_CODE),
         );
 
         // 分配用户堆
         let mut user_heap_bottom: usize = user_stack_top;
         //放置一个保护页
         user_heap_bottom += PAGE_SIZE;
-        let user_heap_top: usize = user_heap_bottom + USER_HEAP_SIZE;
+        let user_heap_top: usize = user_heap_bottom + USER_HEAP_MAX;
         
-        memory_set.push(MapArea::new(
+        // 堆的页在 brk 之后第一次访问时分配
+        memory_set.push_lazy(MapArea::new(
             user_heap_bottom.into(),
             user_heap_top.into(),
             MapType::Framed,
             MapPermission::R | MapPermission::W | MapPermission::U,
-        ), None);
+        ));
         //ztr_brk
-        memory_set.heap_pt = user_heap_top;
+        memory_set.heap_pt = user_heap_bottom;
         memory_set.heap_bottom = user_heap_bottom;
-        memory_set.end_MapAreas = VirtPageNum::from(memory_set.heap_pt / PAGE_SIZE);
+        memory_set.end_MapAreas = VirtPageNum::from(user_heap_top / PAGE_SIZE);
         memory_set.end_MMapAreas = memory_set.end_MapAreas;
-        (
-            memory_set,
-            user_stack_top,
-            elf.header.pt2.entry_point() as usize,
-        )
-    }
-    ///Clone a same `MemorySet`
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+        Ok((memory_set, user_stack_top, entry_point, auxv))
+    }
+    ///Clone a same `MemorySet`, user pages are shared copy-on-write
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
-        // copy data sections/trap_context/user_stack
+        // share data sections/user_stack/heap
         for area in user_space.areas.iter() {
+            if area.map_perm.contains(MapPermission::U) {
+                let new_area = area.share(&mut user_space.page_table, &mut memory_set.page_table);
+                memory_set.areas.push(new_area);
+                continue;
+            }
+            // trap_context is written by the kernel, copy it eagerly
             let new_area = MapArea::from_another(area);
             memory_set.push(new_area, None);
-            // copy data from another space
             for vpn in area.vpn_range {
                 let src_ppn = user_space.translate(vpn).unwrap().ppn();
                 let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
@@ -315,8 +617,118 @@ impl MemorySet {
                     .copy_from_slice(src_ppn.get_bytes_array());
             }
         }
+        for mmap_area in user_space.mmap_areas.iter_mut() {
+            let new_area = mmap_area.share(&mut user_space.page_table, &mut memory_set.page_table);
+            memory_set.mmap_areas.push(new_area);
+        }
+        memory_set.heap_bottom = user_space.heap_bottom;
+        memory_set.heap_pt = user_space.heap_pt;
+        memory_set.end_MapAreas = user_space.end_MapAreas;
+        memory_set.end_MMapAreas = user_space.end_MMapAreas;
         memory_set
     }
+    /// Resolve a page fault at `va` caused by a user `access` (one of `R`, `W`
+    /// and `X`), either by allocating a page of a lazily populated area or by
+    /// copying a copy-on-write page. Return false if the access is invalid.
+    pub fn handle_page_fault(&mut self, va: VirtAddr, access: MapPermission) -> bool {
+        let vpn = va.floor();
+        if let Some(pte) = self.page_table.translate(vpn) {
+            if pte.is_valid() {
+                // the page is present, only a store to a shared page can be fixed
+                return access == MapPermission::W && self.cow_page_fault(vpn);
+            }
+        }
+        // the heap is only usable below the program break
+        let heap_end_vpn = VirtAddr::from(self.heap_pt).ceil();
+        let heap_limit_vpn = VirtAddr::from(self.heap_bottom + USER_HEAP_MAX).ceil();
+        if vpn >= heap_end_vpn && vpn < heap_limit_vpn {
+            return false;
+        }
+        if let Some(area) = self.areas.iter_mut().find(|area| area.vpn_range.contains(vpn)) {
+            if !area.map_perm.contains(access | MapPermission::U) {
+                return false;
+            }
+            return area.populate(&mut self.page_table, vpn);
+        }
+        if let Some(area) = self
+            .mmap_areas
+            .iter_mut()
+            .find(|area| area.vpn_range.contains(vpn))
+        {
+            if !area.map_perm.contains(access | MapPermission::U) {
+                return false;
+            }
+            return area.populate(&mut self.page_table, vpn);
+        }
+        false
+    }
+    /// Resolve a store page fault on a page shared by `from_existed_user`.
+    /// Return false if `vpn` is not a writable page of this `MemorySet`.
+    pub fn cow_page_fault(&mut self, vpn: VirtPageNum) -> bool {
+        match self.page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() && !pte.writable() => {}
+            _ => return false,
+        }
+        if let Some(area) = self.areas.iter_mut().find(|area| area.vpn_range.contains(vpn)) {
+            return area.copy_on_write(&mut self.page_table, vpn);
+        }
+        if let Some(area) = self
+            .mmap_areas
+            .iter_mut()
+            .find(|area| area.vpn_range.contains(vpn))
+        {
+            return area.copy_on_write(&mut self.page_table, vpn);
+        }
+        false
+    }
+    /// The kernel accesses user buffers through physical addresses, so the
+    /// pages in `[start, start + len)` must be populated (and copied if
+    /// shared copy-on-write when `access` is `W`) before translating them
+    pub fn fault_in_user_range(&mut self, start: usize, len: usize, access: MapPermission) {
+        if len == 0 {
+            return;
+        }
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            self.handle_page_fault(vpn.into(), access);
+            if access == MapPermission::W {
+                // stores of the kernel do not set the dirty bit
+                if let Some(area) = self
+                    .mmap_areas
+                    .iter()
+                    .find(|area| area.vpn_range.contains(vpn))
+                {
+                    area.mark_dirty(&mut self.page_table, vpn);
+                }
+            }
+        }
+    }
+    /// Populate `[start, start + len)` and check that all of its pages are
+    /// user pages allowing `access`
+    pub fn user_range_accessible(&mut self, start: usize, len: usize, access: MapPermission) -> bool {
+        self.fault_in_user_range(start, len, access);
+        let wanted = PTEFlags::from_bits((access | MapPermission::U).bits).unwrap();
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        VPNRange::new(start_vpn, end_vpn).into_iter().all(|vpn| {
+            match self.page_table.translate(vpn) {
+                Some(pte) => pte.is_valid() && pte.flags().contains(wanted),
+                None => false,
+            }
+        })
+    }
+    /// The frame a user page is backed by, `None` if not populated
+    pub fn user_frame(&self, vpn: VirtPageNum) -> Option<&FrameTracker> {
+        self.areas
+            .iter()
+            .find_map(|area| area.data_frames.get(&vpn))
+            .or_else(|| self.mmap_areas.iter().find_map(|area| area.data_frames.get(&vpn)))
+    }
+    /// Translate a user virtual address to its physical address
+    pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
+        self.page_table.translate_va(va)
+    }
     ///Refresh TLB with `sfence.vma`
     pub fn activate(&self) {
         let satp = self.page_table.token();
@@ -332,14 +744,99 @@ impl MemorySet {
     ///Remove all `MapArea`
     pub fn recycle_data_pages(&mut self) {
         //*self = Self::new_bare();
+        for area in self.mmap_areas.iter() {
+            area.sync(&mut self.page_table);
+        }
         self.areas.clear();
+        self.mmap_areas.clear();
+    }
+    /// The user mappings by address, as /proc/<pid>/maps lists them. The
+    /// heap ends at the program break.
+    pub fn maps(&self) -> Vec<MapInfo> {
+        let heap_start = VirtAddr::from(self.heap_bottom).floor();
+        // 栈与堆之间隔着一个保护页
+        let stack_end = VirtAddr::from(self.heap_bottom.saturating_sub(PAGE_SIZE)).floor();
+        let sigreturn = VirtAddr::from(SIGRETURN_TRAMPOLINE).floor();
+        let mut maps: Vec<MapInfo> = self
+            .areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+            .filter_map(|area| {
+                let start = area.vpn_range.get_start();
+                let mut end = area.vpn_range.get_end();
+                let name = if start == heap_start {
+                    end = VirtAddr::from(self.heap_pt).ceil();
+                    if end == start {
+                        return None;
+                    }
+                    "[heap]"
+                } else if end == stack_end {
+                    "[stack]"
+                } else if start == sigreturn {
+                    "[sigreturn]"
+                } else {
+                    ""
+                };
+                Some(MapInfo {
+                    start: VirtAddr::from(start).into(),
+                    end: VirtAddr::from(end).into(),
+                    perm: area.map_perm,
+                    shared: false,
+                    offset: 0,
+                    name: String::from(name),
+                })
+            })
+            .collect();
+        for area in self.mmap_areas.iter() {
+            maps.push(MapInfo {
+                start: VirtAddr::from(area.vpn_range.get_start()).into(),
+                end: VirtAddr::from(area.vpn_range.get_end()).into(),
+                perm: area.map_perm,
+                shared: area.flags.contains(MMapFlags::SHARED),
+                offset: area.offset,
+                name: area.file.as_ref().map_or(String::new(), |file| file.get_name()),
+            });
+        }
+        maps.sort_by_key(|map| map.start);
+        maps
+    }
+    /// Number of user pages backed by a frame
+    pub fn resident_pages(&self) -> usize {
+        self.areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+            .map(|area| area.data_frames.len())
+            .chain(self.mmap_areas.iter().map(|area| area.data_frames.len()))
+            .sum()
+    }
+    /// Bytes of user memory mapped, which RLIMIT_AS bounds. Of the space
+    /// reserved for the heap only the part below the program break counts.
+    pub fn mapped_size(&self) -> usize {
+        let heap_start = VirtAddr::from(self.heap_bottom).floor();
+        let heap_end = VirtAddr::from(self.heap_bottom + USER_HEAP_MAX).ceil();
+        let pages: usize = self
+            .areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+            .map(|area| area.vpn_range)
+            .filter(|range| range.get_start() < heap_start || range.get_end() > heap_end)
+            .chain(self.mmap_areas.iter().map(|area| area.vpn_range))
+            .map(|range| range.get_end().0 - range.get_start().0)
+            .sum();
+        pages * PAGE_SIZE + self.heap_size()
+    }
+    /// Bytes between the bottom of the heap and the program break
+    pub fn heap_size(&self) -> usize {
+        self.heap_pt - self.heap_bottom
     }
     //ztr_brk
-    pub fn sbrk(&mut self, increment: isize) -> usize {
+    /// Move the program break by `increment`, the heap can't grow beyond
+    /// `limit` bytes. Return the new break, 0 if it can't be moved.
+    pub fn sbrk(&mut self, increment: isize, limit: usize) -> usize {
         let old_pt: usize = self.heap_pt;
         let new_pt: usize = old_pt + increment as usize;
         if increment > 0 {
-            let limit = self.heap_bottom + USER_HEAP_SIZE;
+            let limit = self.heap_bottom + limit.min(USER_HEAP_MAX);
             if new_pt > limit {
                 warn!(
                     "[sbrk] out of the upperbound! upperbound: {:X}, old_pt: {:X}, new_pt: {:X}",
@@ -351,9 +848,7 @@ impl MemorySet {
                 trace!("[sbrk] heap area expanded to {:X}", new_pt);
             }
         } else if increment < 0 {
-            // shrink to `heap_bottom` would cause duplicated insertion of heap area in future
-            // so we simply reject it here
-            if new_pt <= self.heap_bottom {
+            if new_pt < self.heap_bottom {
                 warn!(
                     "[sbrk] out of the lowerbound! lowerbound: {:X}, old_pt: {:X}, new_pt: {:X}",
                     self.heap_bottom, old_pt, new_pt
@@ -363,6 +858,14 @@ impl MemorySet {
             // we only do shrinking when it does have a heap area
             } else {
                 self.heap_pt = new_pt;
+                // give back the pages above the new break, the heap may have
+                // been split by `mprotect`
+                let released = VPNRange::new(VirtAddr::from(new_pt).ceil(), VirtAddr::from(old_pt).ceil());
+                for vpn in released {
+                    if let Some(area) = self.areas.iter_mut().find(|area| area.vpn_range.contains(vpn)) {
+                        area.unmap_one(&mut self.page_table, vpn);
+                    }
+                }
             }
             // we need to adjust `heap_pt` if it's not out of bound
             // in spite of whether the process has a heap area
@@ -388,15 +891,26 @@ impl MemorySet {
             &mut self, start: VirtAddr, 
             end: VirtAddr, 
             map_perm: MapPermission, 
-            fd: usize, 
+            file: Option<Arc<OSInode>>, 
             off: usize, 
-            flags:usize,
-            fd_table: Vec<Option<FileDescriptor>>) -> isize{   
-        let mmap_area = MMapArea::new(start, end, map_perm, MapType::Framed, fd,  off, flags);
-        self.push_mmap_area(mmap_area, fd_table)
+            flags: MMapFlags) {   
+        let mmap_area = MMapArea::new(start, end, map_perm, MapType::Framed, file, off, flags);
+        self.push_mmap_area(mmap_area)
     }
 }
 /// map area structure, controls a contiguous piece of virtual memory
+/// A user mapping as listed in /proc/<pid>/maps
+pub struct MapInfo {
+    pub start: usize,
+    pub end: usize,
+    pub perm: MapPermission,
+    pub shared: bool,
+    /// offset in the file mapped
+    pub offset: usize,
+    /// the file mapped, or what the area is for
+    pub name: String,
+}
+
 pub struct MapArea {
     vpn_range: VPNRange,
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
@@ -428,6 +942,79 @@ impl MapArea {
             map_perm: another.map_perm,
         }
     }
+    /// Map the frames of this area into `child_table` as well, both sides
+    /// lose `W` until a store fault copies the page
+    pub fn share(&self, page_table: &mut PageTable, child_table: &mut PageTable) -> Self {
+        let mut new_area = Self::from_another(self);
+        let pte_flags = PTEFlags::from_bits((self.map_perm - MapPermission::W).bits).unwrap();
+        for (vpn, frame) in self.data_frames.iter() {
+            if self.map_perm.is_present() {
+                page_table.remap(*vpn, frame.ppn, pte_flags);
+                child_table.map(*vpn, frame.ppn, pte_flags);
+            }
+            new_area.data_frames.insert(*vpn, frame.share());
+        }
+        new_area
+    }
+    /// Split the area at `at`, return the part starting from `at`
+    pub fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let tail = Self {
+            vpn_range: VPNRange::new(at, self.vpn_range.get_end()),
+            data_frames: self.data_frames.split_off(&at),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+        };
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        tail
+    }
+    /// Change the permission of the area and rewrite the PTEs of its frames
+    pub fn protect(&mut self, page_table: &mut PageTable, perm: MapPermission) {
+        let was_present = self.map_perm.is_present();
+        self.map_perm = perm;
+        for (vpn, frame) in self.data_frames.iter() {
+            let mut flags = perm;
+            // pages still shared with another address space stay read-only
+            // until a store copies them
+            if frame_ref_count(frame.ppn) > 1 {
+                flags -= MapPermission::W;
+            }
+            let pte_flags = PTEFlags::from_bits(flags.bits).unwrap();
+            match (was_present, perm.is_present()) {
+                (true, true) => page_table.remap(*vpn, frame.ppn, pte_flags),
+                (false, true) => page_table.map(*vpn, frame.ppn, pte_flags),
+                (true, false) => page_table.unmap(*vpn),
+                (false, false) => {}
+            }
+        }
+    }
+    /// Give `vpn` a private writable frame, copying the shared one if
+    /// another address space still owns it
+    pub fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if !self.map_perm.contains(MapPermission::W) {
+            return false;
+        }
+        let src_ppn = match self.data_frames.get(&vpn) {
+            Some(frame) => frame.ppn,
+            None => return false,
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        if frame_ref_count(src_ppn) == 1 {
+            page_table.remap(vpn, src_ppn, pte_flags);
+            return true;
+        }
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        frame
+            .ppn
+            .get_bytes_array()
+            .copy_from_slice(src_ppn.get_bytes_array());
+        page_table.remap(vpn, frame.ppn, pte_flags);
+        // the old frame loses one owner here
+        self.data_frames.insert(vpn, frame);
+        true
+    }
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
         match self.map_type {
@@ -443,11 +1030,28 @@ impl MapArea {
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         page_table.map(vpn, ppn, pte_flags);
     }
+    /// Allocate the frame of `vpn` on its first access
+    pub fn populate(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if self.map_type != MapType::Framed || self.data_frames.contains_key(&vpn) {
+            return false;
+        }
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        page_table.map(vpn, frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, frame);
+        true
+    }
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        if self.map_type == MapType::Framed {
-            self.data_frames.remove(&vpn);
+        if self.map_type == MapType::Framed && self.data_frames.remove(&vpn).is_none() {
+            // never populated
+            return;
+        }
+        if self.map_perm.is_present() {
+            page_table.unmap(vpn);
         }
-        page_table.unmap(vpn);
     }
     pub fn map(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
@@ -460,21 +1064,24 @@ impl MapArea {
         }
     }
     /// data: start-aligned but maybe with shorter length
-    /// assume that all frames were cleared before
-    pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
+    /// assume that all frames were cleared before,
+    /// `offset` is where it begins in the first page
+    pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8], offset: usize) {
         assert_eq!(self.map_type, MapType::Framed);
         let mut start: usize = 0;
+        let mut page_offset = offset;
         let mut current_vpn = self.vpn_range.get_start();
         let len = data.len();
         loop {
-            let src = &data[start..len.min(start + PAGE_SIZE)];
+            let src = &data[start..len.min(start + PAGE_SIZE - page_offset)];
             let dst = &mut page_table
                 .translate(current_vpn)
                 .unwrap()
                 .ppn()
-                .get_bytes_array()[..src.len()];
+                .get_bytes_array()[page_offset..page_offset + src.len()];
             dst.copy_from_slice(src);
-            start += PAGE_SIZE;
+            start += src.len();
+            page_offset = 0;
             if start >= len {
                 break;
             }
@@ -488,9 +1095,10 @@ pub struct MMapArea {
     pub data_frames: BTreeMap<VirtPageNum, FrameTracker>,
     pub map_type: MapType,
     pub map_perm: MapPermission,
-    pub fd: usize,
+    /// 映射的文件，匿名映射为 `None`
+    pub file: Option<Arc<OSInode>>,
     pub offset: usize,
-    pub flags: usize,
+    pub flags: MMapFlags,
     pub length: usize,
 }
 
@@ -500,9 +1108,9 @@ impl MMapArea {
         end_va: VirtAddr,
         map_perm: MapPermission,
         map_type: MapType,
-        fd: usize,
+        file: Option<Arc<OSInode>>,
         offset: usize,
-        flags: usize,
+        flags: MMapFlags,
     ) -> Self {
         let start_vpn: VirtPageNum = start_va.floor();
         let end_vpn: VirtPageNum = end_va.ceil();
@@ -511,88 +1119,201 @@ impl MMapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
-            fd,
+            file,
             offset,
             flags,
             length: end_va.0 - start_va.0,
         }
     }
 
-    /// 取消映射所有页
-    pub fn unmap(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
-            if self.data_frames.contains_key(&vpn) {
-                self.data_frames.remove(&vpn);
-                page_table.unmap(vpn);
+    /// 与子进程共享所有已分配的页，私有映射写时复制。
+    /// 共享映射先分配所有页，之后第一次访问的页也要在父子进程间共享
+    pub fn share(&mut self, page_table: &mut PageTable, child_table: &mut PageTable) -> Self {
+        let mut new_area = Self {
+            vpn_range: VPNRange::new(self.vpn_range.get_start(), self.vpn_range.get_end()),
+            data_frames: BTreeMap::new(),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            file: self.file.clone(),
+            offset: self.offset,
+            flags: self.flags,
+            length: self.length,
+        };
+        if self.flags.contains(MMapFlags::SHARED) {
+            for vpn in self.vpn_range {
+                if self.data_frames.contains_key(&vpn) {
+                    continue;
+                }
+                let frame = match self.alloc_frame(vpn) {
+                    Some(frame) => frame,
+                    None => break,
+                };
+                if self.map_perm.is_present() {
+                    page_table.map(vpn, frame.ppn, PTEFlags::from_bits(self.map_perm.bits()).unwrap());
+                }
+                self.data_frames.insert(vpn, frame);
+            }
+            // 共享映射的写入对父子进程都可见
+            let pte_flags = PTEFlags::from_bits(self.map_perm.bits()).unwrap();
+            for (vpn, frame) in self.data_frames.iter() {
+                if self.map_perm.is_present() {
+                    child_table.map(*vpn, frame.ppn, pte_flags);
+                }
+                new_area.data_frames.insert(*vpn, frame.share());
             }
+            return new_area;
         }
+        let pte_flags = PTEFlags::from_bits((self.map_perm - MapPermission::W).bits()).unwrap();
+        for (vpn, frame) in self.data_frames.iter() {
+            if self.map_perm.is_present() {
+                page_table.remap(*vpn, frame.ppn, pte_flags);
+                child_table.map(*vpn, frame.ppn, pte_flags);
+            }
+            new_area.data_frames.insert(*vpn, frame.share());
+        }
+        new_area
     }
 
-    //分配一次
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        let ppn: PhysPageNum;
-        match self.map_type {
-            MapType::Identical => {
-                ppn = PhysPageNum(vpn.0);
+    /// 在 `at` 处拆分，返回从 `at` 开始的部分
+    pub fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let head_len = (at.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+        let tail = Self {
+            vpn_range: VPNRange::new(at, self.vpn_range.get_end()),
+            data_frames: self.data_frames.split_off(&at),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            file: self.file.clone(),
+            offset: self.offset + head_len,
+            flags: self.flags,
+            length: self.length - head_len,
+        };
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        self.length = head_len;
+        tail
+    }
+
+    /// 修改权限并重写已分配页的页表项
+    pub fn protect(&mut self, page_table: &mut PageTable, perm: MapPermission) {
+        // 重写页表项会清除脏位，先写回
+        self.sync(page_table);
+        let was_present = self.map_perm.is_present();
+        self.map_perm = perm;
+        for (vpn, frame) in self.data_frames.iter() {
+            let mut flags = perm;
+            // 私有映射中仍被共享的页保持只读，写入时再复制
+            if !self.flags.contains(MMapFlags::SHARED) && frame_ref_count(frame.ppn) > 1 {
+                flags -= MapPermission::W;
             }
-            MapType::Framed => {
-                let frame = frame_alloc().unwrap();
-                ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+            let pte_flags = PTEFlags::from_bits(flags.bits()).unwrap();
+            match (was_present, perm.is_present()) {
+                (true, true) => page_table.remap(*vpn, frame.ppn, pte_flags),
+                (false, true) => page_table.map(*vpn, frame.ppn, pte_flags),
+                (true, false) => page_table.unmap(*vpn),
+                (false, false) => {}
             }
         }
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits()).unwrap();
-        
-        page_table.map(vpn, ppn, pte_flags);
     }
-    pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        match self.map_type {
-            MapType::Framed => {
-                self.data_frames.remove(&vpn);
+
+    /// 将共享文件映射中的脏页写回文件，文件末尾之后的部分不写回
+    pub fn sync(&self, page_table: &mut PageTable) {
+        let file = match &self.file {
+            Some(file) if self.flags.contains(MMapFlags::SHARED) => file,
+            _ => return,
+        };
+        let file_size = file.size();
+        for (vpn, frame) in self.data_frames.iter() {
+            let pte = match page_table.translate(*vpn) {
+                Some(pte) if pte.is_valid() && pte.is_dirty() => pte,
+                _ => continue,
+            };
+            let page_offset = (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            let file_offset = self.offset + page_offset;
+            if file_offset < file_size {
+                let len = PAGE_SIZE
+                    .min(self.length - page_offset)
+                    .min(file_size - file_offset);
+                file.write_at(file_offset, &frame.ppn.get_bytes_array()[..len]);
             }
-            _ => {
+            page_table.remap(*vpn, frame.ppn, pte.flags() - PTEFlags::D);
+        }
+    }
+
+    /// 标记共享映射的页为脏页
+    pub fn mark_dirty(&self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        if !self.flags.contains(MMapFlags::SHARED) {
+            return;
+        }
+        if let Some(frame) = self.data_frames.get(&vpn) {
+            match page_table.translate(vpn) {
+                Some(pte) if pte.is_valid() && pte.writable() => {
+                    page_table.remap(vpn, frame.ppn, pte.flags() | PTEFlags::D)
+                }
+                _ => {}
             }
         }
-        page_table.unmap(vpn);
     }
-    /// 分配内存, 同时映射文件
-    /// 一次分配所有页
-    pub fn map(
-        &mut self,
-        page_table: &mut PageTable,
-        fd_table: Vec<Option<FileDescriptor>>
-    ) -> isize {
-        // 对所有使用的虚拟页号进行与物理内存的映射
-        for vpn in self.vpn_range { 
-            self.map_one(page_table, vpn);
+
+    /// 写时复制：为 `vpn` 分配私有的可写页
+    pub fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if !self.map_perm.contains(MapPermission::W) {
+            return false;
         }
-        if self.fd < 0 {
-            return 0;
+        let src_ppn = match self.data_frames.get(&vpn) {
+            Some(frame) => frame.ppn,
+            None => return false,
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits()).unwrap();
+        if frame_ref_count(src_ppn) == 1 {
+            page_table.remap(vpn, src_ppn, pte_flags);
+            return true;
         }
-        //获取分配地址
-        let vaddr: usize = VirtAddr::from(self.vpn_range.get_start()).into();
-        if let Some(file) = &fd_table[self.fd] {
-            let f: Arc<dyn File + Send + Sync> = match &file.ftype {
-                FileType::Abstr(f) => f.clone(),
-                FileType::File(f) => f.clone(),
-                _ => return 0,
-            };
-            if !f.readable() { 
-                return 0; 
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        frame
+            .ppn
+            .get_bytes_array()
+            .copy_from_slice(src_ppn.get_bytes_array());
+        page_table.remap(vpn, frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, frame);
+        true
+    }
+
+    /// 取消映射所有页
+    pub fn unmap(&mut self, page_table: &mut PageTable) {
+        for vpn in self.vpn_range {
+            if self.data_frames.remove(&vpn).is_some() && self.map_perm.is_present() {
+                page_table.unmap(vpn);
             }
-            //设置偏移量
-            f.set_offset(self.offset);
-            // println!{"The va_start is 0x{:X}, offset of file is {}", va_start.0, offset};
-            //将文件读入内存
-            let _read_len = f.read(UserBuffer::new(translated_byte_buffer(
-                page_table.token(),
-            vaddr as *const u8,
-            self.length,)));
-            return vaddr as isize
         }
-        else { 
-            return 0 
+    }
+
+    /// 分配 `vpn` 的页，文件映射读入对应的文件内容
+    fn alloc_frame(&self, vpn: VirtPageNum) -> Option<FrameTracker> {
+        let frame = frame_alloc()?;
+        if let Some(file) = &self.file {
+            let page_offset = (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            // 超出映射长度的部分保持为 0
+            let len = PAGE_SIZE.min(self.length - page_offset);
+            file.read_at(self.offset + page_offset, &mut frame.ppn.get_bytes_array()[..len]);
+        }
+        Some(frame)
+    }
+
+    /// 第一次访问时分配页
+    pub fn populate(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if self.map_type != MapType::Framed || self.data_frames.contains_key(&vpn) {
+            return false;
         }
+        let frame = match self.alloc_frame(vpn) {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits()).unwrap();
+        page_table.map(vpn, frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, frame);
+        true
     }
 }
 
@@ -617,10 +1338,32 @@ bitflags! {
     }
 }
 
+impl MapPermission {
+    /// Pages without any of `R W X` are kept out of the page table, since
+    /// such a PTE would point to the next level
+    pub fn is_present(&self) -> bool {
+        self.intersects(MapPermission::R | MapPermission::W | MapPermission::X)
+    }
+}
+
+bitflags! {
+    /// flags of `mmap`
+    pub struct MMapFlags: u32 {
+        ///Share the mapping, stores are written back to the file
+        const SHARED = 1 << 0;
+        ///Private copy-on-write mapping
+        const PRIVATE = 1 << 1;
+        ///Place the mapping exactly at the given address
+        const FIXED = 1 << 4;
+        ///Not backed by any file, `fd` is ignored
+        const ANONYMOUS = 1 << 5;
+    }
+}
+
 #[allow(unused)]
 ///Check PageTable running correctly
 pub fn remap_test() {
-    let mut kernel_space = KERNEL_SPACE.exclusive_access();
+    let mut kernel_space = KERNEL_SPACE.lock();
     let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
     let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
     let mid_data: VirtAddr = ((sdata as usize + edata as usize) / 2).into();
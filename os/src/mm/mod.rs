@@ -10,22 +10,29 @@ mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
 mod page_table;
+mod uaccess;
 // mod mmap;
 
 use address::VPNRange;
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
-pub use frame_allocator::{frame_alloc, frame_dealloc, FrameTracker};
+pub use frame_allocator::{frame_alloc, frame_dealloc, frame_ref_count, frame_stats, FrameTracker};
+pub use heap_allocator::heap_stats;
 pub use memory_set::remap_test;
-pub use memory_set::{kernel_token, MapPermission, MemorySet, KERNEL_SPACE, MapType, MMapArea};
+pub use memory_set::{kernel_token, MapInfo, MapPermission, MemorySet, KERNEL_SPACE, MapType, MMapArea, MMapFlags};
+pub use memory_set::{AuxHeader, AT_BASE, AT_ENTRY, AT_NULL, AT_PAGESZ, AT_PHDR, AT_PHENT, AT_PHNUM, AT_RANDOM};
 //pub use mmap::*;
 use page_table::PTEFlags;
 pub use page_table::{
-    translated_byte_buffer, translated_ref, translated_refmut, translated_str, PageTable,
-    PageTableEntry, UserBuffer, UserBufferIterator,
+    translated_byte_buffer, PageTable, PageTableEntry, UserBuffer, UserBufferIterator,
 };
+pub use uaccess::{copy_bytes_to_user, copy_from_user, copy_str_from_user, copy_to_user, user_buffer};
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
-    KERNEL_SPACE.exclusive_access().activate();
+    KERNEL_SPACE.lock().activate();
+}
+/// switch a secondary hart to the kernel space set up by the boot hart
+pub fn init_hart() {
+    KERNEL_SPACE.lock().activate();
 }
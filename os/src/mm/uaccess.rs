@@ -0,0 +1,126 @@
+//! Checked access to user memory
+//!
+//! Pointers passed in by user programs are checked against the
+//! [`MemorySet`], including the permission bits, before the kernel touches
+//! them. A bad pointer fails with `EFAULT` instead of bringing the kernel
+//! down.
+
+use super::{translated_byte_buffer, FrameTracker, MapPermission, MemorySet, UserBuffer, VPNRange, VirtAddr};
+use crate::config::{PAGE_SIZE, USER_SPACE_END};
+use crate::sync::SpinLock;
+use crate::syscall::Errno;
+use crate::task::current_task;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::{size_of, MaybeUninit};
+
+/// longest string read from user memory, like `PATH_MAX` with the `\0`
+const USER_STR_MAX: usize = 4096;
+
+impl MemorySet {
+    /// Check that `[start, start + len)` is user memory allowing `access`,
+    /// pages mapped lazily are populated on the way
+    pub fn check_user_range(&mut self, start: usize, len: usize, access: MapPermission) -> Result<(), Errno> {
+        match start.checked_add(len) {
+            Some(end) if end <= USER_SPACE_END => {}
+            _ => return Err(Errno::EFAULT),
+        }
+        if len == 0 || self.user_range_accessible(start, len, access) {
+            Ok(())
+        } else {
+            Err(Errno::EFAULT)
+        }
+    }
+    /// Owners of the frames behind `[start, start + len)`, which has been
+    /// checked, so that they outlive an unmap while the kernel still uses them
+    fn pin_user_range(&self, start: usize, len: usize) -> Vec<FrameTracker> {
+        let range = VPNRange::new(VirtAddr::from(start).floor(), VirtAddr::from(start + len).ceil());
+        range
+            .into_iter()
+            .filter_map(|vpn| self.user_frame(vpn).map(FrameTracker::share))
+            .collect()
+    }
+    /// Copy `src` to user address `dst`
+    pub fn copy_to_user(&mut self, dst: usize, src: &[u8]) -> Result<(), Errno> {
+        self.check_user_range(dst, src.len(), MapPermission::W)?;
+        UserBuffer::new(translated_byte_buffer(self.token(), dst as *const u8, src.len())).write(src);
+        Ok(())
+    }
+    /// Fill `dst` from user address `src`
+    pub fn copy_from_user(&mut self, dst: &mut [u8], src: usize) -> Result<(), Errno> {
+        self.check_user_range(src, dst.len(), MapPermission::R)?;
+        let mut copied = 0;
+        for buffer in translated_byte_buffer(self.token(), src as *const u8, dst.len()) {
+            dst[copied..copied + buffer.len()].copy_from_slice(buffer);
+            copied += buffer.len();
+        }
+        Ok(())
+    }
+    /// Read the `\0`-terminated string at user address `ptr`
+    pub fn read_user_str(&mut self, ptr: usize) -> Result<String, Errno> {
+        let mut bytes = Vec::new();
+        let mut va = ptr;
+        loop {
+            // check a page at a time, the string may end before the next one
+            let len = PAGE_SIZE - VirtAddr::from(va).page_offset();
+            self.check_user_range(va, len, MapPermission::R)?;
+            for buffer in translated_byte_buffer(self.token(), va as *const u8, len) {
+                match buffer.iter().position(|byte| *byte == 0) {
+                    Some(end) => {
+                        bytes.extend_from_slice(&buffer[..end]);
+                        return Ok(String::from_utf8_lossy(&bytes).into_owned());
+                    }
+                    None => bytes.extend_from_slice(buffer),
+                }
+            }
+            if bytes.len() >= USER_STR_MAX {
+                return Err(Errno::ENAMETOOLONG);
+            }
+            va += len;
+        }
+    }
+}
+
+/// The address space of the current task. Its inner must not be held by
+/// the caller.
+fn current_memory_set() -> Arc<SpinLock<MemorySet>> {
+    current_task().unwrap().inner_exclusive_access().memory_set.clone()
+}
+
+/// Copy `value` to user address `ptr` of the current task
+pub fn copy_to_user<T>(ptr: *mut T, value: &T) -> Result<(), Errno> {
+    let bytes = unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    current_memory_set().lock().copy_to_user(ptr as usize, bytes)
+}
+
+/// Copy `bytes` to user address `ptr` of the current task
+pub fn copy_bytes_to_user(ptr: *mut u8, bytes: &[u8]) -> Result<(), Errno> {
+    current_memory_set().lock().copy_to_user(ptr as usize, bytes)
+}
+
+/// Read a `T` at user address `ptr` of the current task, `T` must be
+/// plain data valid for any bytes
+pub fn copy_from_user<T: Copy>(ptr: *const T) -> Result<T, Errno> {
+    let mut value = MaybeUninit::<T>::uninit();
+    let bytes = unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    current_memory_set().lock().copy_from_user(bytes, ptr as usize)?;
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Read the `\0`-terminated string at user address `ptr` of the current task
+pub fn copy_str_from_user(ptr: *const u8) -> Result<String, Errno> {
+    current_memory_set().lock().read_user_str(ptr as usize)
+}
+
+/// Check `[ptr, ptr + len)` of the current task allows `access` and hand it
+/// out as a `UserBuffer`, for files to read into or write from. The lock of
+/// the address space is not held during the I/O, which may block, so the
+/// buffer keeps its frames alive even if another thread unmaps them
+pub fn user_buffer(ptr: *const u8, len: usize, access: MapPermission) -> Result<UserBuffer, Errno> {
+    let memory_set = current_memory_set();
+    let mut memory_set = memory_set.lock();
+    memory_set.check_user_range(ptr as usize, len, access)?;
+    let frames = memory_set.pin_user_range(ptr as usize, len);
+    Ok(UserBuffer::pinned(translated_byte_buffer(memory_set.token(), ptr, len), frames))
+}
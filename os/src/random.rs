@@ -0,0 +1,26 @@
+//! 内核的伪随机数发生器，供 /dev/urandom 与 AT_RANDOM 使用
+//!
+//! xorshift64*, seeded from the clock the first time it is used. There is
+//! no entropy source, the numbers are not for cryptography.
+
+use crate::sync::SpinLock;
+use crate::timer::get_time;
+use lazy_static::*;
+
+lazy_static! {
+    static ref STATE: SpinLock<u64> = SpinLock::new(get_time() as u64 | 1);
+}
+
+/// Fill `buf` with pseudo-random bytes
+pub fn fill_random(buf: &mut [u8]) {
+    let mut state = STATE.lock();
+    for chunk in buf.chunks_mut(8) {
+        let mut x = *state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        *state = x;
+        let value = x.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+    }
+}
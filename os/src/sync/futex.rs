@@ -0,0 +1,101 @@
+//! Fast userspace mutexes, tasks wait on a user word until it is woken up
+use super::{SpinLock, WaitQueue};
+use crate::mm::PhysAddr;
+use crate::task::{block_current_and_run_next, current_has_signal, current_task};
+use crate::timer::{add_timer, get_time_ms, remove_timer};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{fence, AtomicU32, Ordering};
+use lazy_static::*;
+
+lazy_static! {
+    /// Wait queues indexed by the physical address of the futex word, so
+    /// that the tasks sharing a page through different mappings meet in
+    /// the same queue
+    static ref FUTEX_QUEUES: SpinLock<BTreeMap<usize, Arc<WaitQueue>>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+/// Why `futex_wait` returned
+#[derive(Copy, Clone, PartialEq)]
+pub enum FutexWaitResult {
+    /// woken up by `futex_wake`
+    Woken,
+    /// the futex word does not hold the expected value
+    ValueChanged,
+    /// the timeout expired first
+    TimedOut,
+    /// interrupted by a signal
+    Interrupted,
+}
+
+/// Block the current task on the futex at physical address `pa` while it
+/// holds `expected`, until it is woken up, or until `expire_ms` if given.
+/// The caller must not hold the inner of any task.
+pub fn futex_wait(pa: usize, expected: u32, expire_ms: Option<usize>) -> FutexWaitResult {
+    let queue = FUTEX_QUEUES
+        .lock()
+        .entry(pa)
+        .or_insert_with(|| Arc::new(WaitQueue::new()))
+        .clone();
+    let task = current_task().unwrap();
+    // queue up before looking at the word, so that a waker storing to it
+    // on another hart either finds us in the queue or we see its store
+    queue.prepare_to_wait();
+    fence(Ordering::SeqCst);
+    let word = PhysAddr::from(pa).get_ref::<AtomicU32>();
+    let result = if word.load(Ordering::SeqCst) != expected {
+        queue.remove(&task);
+        FutexWaitResult::ValueChanged
+    } else {
+        if let Some(expire_ms) = expire_ms {
+            add_timer(expire_ms, task.clone());
+        }
+        let result = loop {
+            block_current_and_run_next();
+            // whoever woke us up has taken us out of the queue
+            if !queue.remove(&task) {
+                break FutexWaitResult::Woken;
+            } else if current_has_signal() {
+                break FutexWaitResult::Interrupted;
+            } else if expire_ms.map_or(false, |expire_ms| get_time_ms() >= expire_ms) {
+                break FutexWaitResult::TimedOut;
+            }
+            // woken up for nothing, wait again
+            queue.prepare_to_wait();
+        };
+        if expire_ms.is_some() {
+            remove_timer(&task);
+        }
+        result
+    };
+    drop(task);
+    release_queue(pa, &queue);
+    result
+}
+
+/// Wake at most `count` tasks waiting on the futex at physical address `pa`,
+/// return how many of them are woken up
+pub fn futex_wake(pa: usize, count: usize) -> usize {
+    let queue = match FUTEX_QUEUES.lock().get(&pa) {
+        Some(queue) => queue.clone(),
+        None => return 0,
+    };
+    let mut woken = 0;
+    while woken < count && queue.wake_one() {
+        woken += 1;
+    }
+    release_queue(pa, &queue);
+    woken
+}
+
+/// Forget the queue of `pa` once nobody is waiting on it
+fn release_queue(pa: usize, queue: &Arc<WaitQueue>) {
+    if queue.is_empty() {
+        let mut queues = FUTEX_QUEUES.lock();
+        // the other users of the queue still hold it
+        if Arc::strong_count(queue) == 2 {
+            queues.remove(&pa);
+        }
+    }
+}
@@ -1,4 +1,8 @@
 //! Synchronization and interior mutability primitives
-mod up;
+mod futex;
+mod spin;
+mod wait_queue;
 
-pub use up::UPSafeCell;
+pub use futex::{futex_wait, futex_wake, FutexWaitResult};
+pub use self::spin::{SpinLock, SpinLockGuard};
+pub use wait_queue::WaitQueue;
@@ -0,0 +1,66 @@
+//! Queue of tasks blocked until some event happens
+use super::SpinLock;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// Tasks parked by `wait` and woken by `wake_one` or `wake_all`.
+pub struct WaitQueue {
+    queue: SpinLock<VecDeque<Arc<TaskControlBlock>>>,
+}
+
+impl WaitQueue {
+    /// Create an empty wait queue
+    pub fn new() -> Self {
+        Self {
+            queue: SpinLock::new(VecDeque::new()),
+        }
+    }
+    /// Block the current task in this queue. The caller must not hold the
+    /// inner of any task, and should check its condition again on return.
+    pub fn wait(&self) {
+        self.prepare_to_wait();
+        block_current_and_run_next();
+    }
+    /// Put the current task in the queue without blocking it. Calling this
+    /// before checking the condition makes sure that a wakeup from another
+    /// hart in between is not lost. Follow it by `block_current_and_run_next`,
+    /// or by `remove` if the condition already holds.
+    pub fn prepare_to_wait(&self) {
+        self.queue.lock().push_back(current_task().unwrap());
+    }
+    /// Wake the task that has been waiting for the longest time
+    pub fn wake_one(&self) -> bool {
+        let task = self.queue.lock().pop_front();
+        match task {
+            Some(task) => {
+                wakeup_task(task);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Take `task` out of the queue if it is still waiting, used when it
+    /// is woken up by something else, like a timer or a signal
+    pub fn remove(&self, task: &Arc<TaskControlBlock>) -> bool {
+        let mut queue = self.queue.lock();
+        match queue.iter().position(|waiting| Arc::ptr_eq(waiting, task)) {
+            Some(idx) => {
+                queue.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Whether no task is waiting
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().is_empty()
+    }
+    /// Wake all the waiting tasks
+    pub fn wake_all(&self) {
+        let tasks: VecDeque<_> = self.queue.lock().drain(..).collect();
+        for task in tasks {
+            wakeup_task(task);
+        }
+    }
+}
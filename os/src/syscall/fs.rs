@@ -1,71 +1,139 @@
 //! File and filesystem-related syscalls
 use core::mem::size_of;
 use crate::console::print;
-use crate::fs::{open_file, OpenFlags, DiskInodeType, FileDescriptor, FileType, File, OSInode, MNT_TABLE, chdir, DirEntry, Kstat, make_pipe};
-use crate::mm::{translated_byte_buffer, translated_str, translated_refmut, UserBuffer};
-use crate::task::{current_task, current_user_token};
+use crate::fs::{open_file, OpenFlags, DiskInodeType, FileDescriptor, FileType, File, OSInode, MNT_TABLE, chdir, absolute_path, find_filesystem, lookup, split_path, DirEntry, Kstat, make_pipe};
+use crate::mm::{copy_bytes_to_user, copy_str_from_user, copy_to_user, user_buffer, MapPermission};
+use crate::task::{current_task, RLIMIT_NOFILE};
+use super::{Errno, SysResult};
 use alloc::sync::Arc;
 
 const AT_FDCWD: isize = -100;
-pub const FD_LIMIT: usize = 128;
+/// `unlinkat` 删除的是目录
+const AT_REMOVEDIR: u32 = 0x200;
 
 
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> SysResult {
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table[fd] {
+    let fd_table = inner.fd_table.lock();
+    if let Some(Some(file)) = fd_table.get(fd) {
         let file: Arc<dyn File + Send + Sync> = match &file.ftype {
             FileType::Abstr(f) => f.clone(),
             FileType::File(f) => f.clone(),
-            _ => return -1,
         };
         if !file.writable() {
-            return -1;
+            return Err(Errno::EBADF);
         }
-        let file = file.clone();
         // release current task TCB manually to avoid multi-borrow
+        drop(fd_table);
         drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        let buf = user_buffer(buf, len, MapPermission::R)?;
+        Ok(file.write(buf)? as isize)
     } else {
-        -1
+        Err(Errno::EBADF)
     }
 }
 
-pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
+pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> SysResult {
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table[fd] {
+    let fd_table = inner.fd_table.lock();
+    if let Some(Some(file)) = fd_table.get(fd) {
         let file: Arc<dyn File + Send + Sync> = match &file.ftype {
             FileType::Abstr(f) => f.clone(),
             FileType::File(f) => f.clone(),
-            _ => return -1,
         };
         if !file.readable() {
-            return -1;
+            return Err(Errno::EBADF);
         }
         // release current task TCB manually to avoid multi-borrow
+        drop(fd_table);
         drop(inner);
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        let buf = user_buffer(buf, len, MapPermission::W)?;
+        Ok(file.read(buf)? as isize)
     } else {
-        -1
+        Err(Errno::EBADF)
+    }
+}
+/// The file `fd` refers to, for the syscalls which need its offset
+fn get_file(fd: usize) -> Result<FileType, Errno> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = inner.fd_table.lock().get(fd).cloned().flatten();
+    file.map(|filedescriptor| filedescriptor.ftype).ok_or(Errno::EBADF)
+}
+
+/// 移动文件偏移，返回新的偏移
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> SysResult {
+    match get_file(fd)? {
+        FileType::File(f) => f.lseek(offset, whence),
+        FileType::Abstr(f) => f.lseek(offset, whence),
+    }
+}
+
+/// 从 `offset` 处读，不改变文件偏移
+pub fn sys_pread64(fd: usize, buf: *const u8, len: usize, offset: isize) -> SysResult {
+    let file = get_file(fd)?;
+    let readable = match &file {
+        FileType::File(f) => f.readable(),
+        FileType::Abstr(f) => f.readable(),
+    };
+    if !readable {
+        return Err(Errno::EBADF);
+    }
+    if offset < 0 {
+        return Err(Errno::EINVAL);
+    }
+    let buf = user_buffer(buf, len, MapPermission::W)?;
+    match file {
+        FileType::File(f) => Ok(f.pread(offset as usize, buf) as isize),
+        // 管道与终端返回 ESPIPE
+        FileType::Abstr(f) => Ok(f.pread(offset as usize, buf)? as isize),
+    }
+}
+
+/// 在 `offset` 处写，不改变文件偏移
+pub fn sys_pwrite64(fd: usize, buf: *const u8, len: usize, offset: isize) -> SysResult {
+    let file = get_file(fd)?;
+    let writable = match &file {
+        FileType::File(f) => f.writable(),
+        FileType::Abstr(f) => f.writable(),
+    };
+    if !writable {
+        return Err(Errno::EBADF);
+    }
+    if offset < 0 {
+        return Err(Errno::EINVAL);
+    }
+    let buf = user_buffer(buf, len, MapPermission::R)?;
+    match file {
+        FileType::File(f) => Ok(f.pwrite(offset as usize, buf)? as isize),
+        FileType::Abstr(f) => Ok(f.pwrite(offset as usize, buf)? as isize),
     }
 }
+/// 把文件截断或用 0 扩展到 `length` 字节
+pub fn sys_ftruncate(fd: usize, length: isize) -> SysResult {
+    let file = match get_file(fd)? {
+        FileType::File(f) => f,
+        FileType::Abstr(_) => return Err(Errno::EINVAL),
+    };
+    if file.is_dir() {
+        return Err(Errno::EISDIR);
+    }
+    if !file.writable() || length < 0 {
+        return Err(Errno::EINVAL);
+    }
+    file.truncate(length as usize)?;
+    Ok(0)
+}
 //ztr_open
-pub fn sys_openat(fd: isize, path: *const u8, flags: u32, mode: u32) -> isize {
+pub fn sys_openat(fd: isize, path: *const u8, flags: u32, mode: u32) -> SysResult {
     let task = current_task().unwrap();
-    let token = current_user_token();
+    let path = copy_str_from_user(path)?;
     let mut inner = task.inner_exclusive_access();
     
-    let path = translated_str(token, path);
-    let open_flags = OpenFlags::from_bits(flags).unwrap();
+    // 与 Linux 相同，忽略不认识的标志位
+    let open_flags = OpenFlags::from_bits_truncate(flags);
     if fd == AT_FDCWD {
         // 如果是当前工作目录
         
@@ -93,25 +161,23 @@ pub fn sys_openat(fd: isize, path: *const u8, flags: u32, mode: u32) -> isize {
             open_flags, 
             DiskInodeType::File,
         ) {
-            let fd = inner.alloc_fd();
-            inner.fd_table[fd] = Some(FileDescriptor::new(
+            let fd = inner.alloc_fd()?;
+            inner.fd_table.lock()[fd] = Some(FileDescriptor::new(
                 open_flags.contains(OpenFlags::CLOEXEC),
-                FileType::File(inode),
+                inode.file_type(),
             ));
             drop(inner);
-            fd as isize
+            Ok(fd as isize)
         } else {
-            -1
+            Err(Errno::ENOENT)
         }
     } else {
         let dirfd = fd as usize;
-        if dirfd >= inner.fd_table.len() {
-            return -1;
-        }
-        if let Some(filedescriptor) = &inner.fd_table[dirfd]{
+        let filedescriptor = inner.fd_table.lock().get(dirfd).cloned().flatten();
+        if let Some(filedescriptor) = filedescriptor {
             let file:Arc<OSInode> = match &filedescriptor.ftype {
                 FileType::File(file) => file.clone(),
-                _ => return -1,
+                _ => return Err(Errno::ENOTDIR),
             };
             if let Some(f) = open_file(
                 file.get_name().as_str(), 
@@ -119,50 +185,46 @@ pub fn sys_openat(fd: isize, path: *const u8, flags: u32, mode: u32) -> isize {
                 open_flags, 
                 DiskInodeType::Directory,
             ) {
-                let fd = inner.alloc_fd();
-                inner.fd_table[fd] =Some(FileDescriptor::new(
+                let fd = inner.alloc_fd()?;
+                inner.fd_table.lock()[fd] = Some(FileDescriptor::new(
                     open_flags.contains(OpenFlags::CLOEXEC),
-                    FileType::File(f),
+                    f.file_type(),
                 ));
                 drop(inner);
-                fd as isize
+                Ok(fd as isize)
             } else {
-                -1
+                Err(Errno::ENOENT)
             }
         } else {
-            -1
+            Err(Errno::EBADF)
         }
     }
 }
 //ztr_dup
-pub fn sys_dup(fd: usize) -> isize {
+pub fn sys_dup(fd: usize) -> SysResult {
     let task = current_task().unwrap();
     let mut inner = task.inner_exclusive_access();
     // 检查传入 fd 的合法性
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if inner.fd_table[fd].is_none() {
-        return -1;
+    if !matches!(inner.fd_table.lock().get(fd), Some(Some(_))) {
+        return Err(Errno::EBADF);
     }
 
-    let new_fd = inner.alloc_fd();
-    inner.fd_table[new_fd] = inner.fd_table[fd].clone();
-    new_fd as isize
+    let new_fd = inner.alloc_fd()?;
+    let mut fd_table = inner.fd_table.lock();
+    fd_table[new_fd] = fd_table[fd].clone();
+    Ok(new_fd as isize)
 }
-pub fn sys_dup3( old_fd: usize, new_fd: usize )->isize{
+pub fn sys_dup3( old_fd: usize, new_fd: usize )->SysResult{
     let task = current_task().unwrap();
-    let mut inner = task.inner_exclusive_access();
+    let inner = task.inner_exclusive_access();
+    let mut fd_table = inner.fd_table.lock();
 
-    if  old_fd >= inner.fd_table.len() || new_fd > FD_LIMIT {
-        return -1;
-    }
-    if inner.fd_table[old_fd].is_none() {
-        return -1;
+    if !matches!(fd_table.get(old_fd), Some(Some(_))) || new_fd >= inner.rlimits.get(RLIMIT_NOFILE).cur {
+        return Err(Errno::EBADF);
     }
-    if new_fd >= inner.fd_table.len() {
-        for _ in inner.fd_table.len()..(new_fd + 1) {
-            inner.fd_table.push(None);
+    if new_fd >= fd_table.len() {
+        for _ in fd_table.len()..(new_fd + 1) {
+            fd_table.push(None);
         }
     }
 
@@ -171,165 +233,208 @@ pub fn sys_dup3( old_fd: usize, new_fd: usize )->isize{
     //    act_fd = inner.alloc_fd();
     //}
     //let new_fd = inner.alloc_fd();
-    inner.fd_table[new_fd] = inner.fd_table[old_fd].clone();
-    new_fd as isize
+    fd_table[new_fd] = fd_table[old_fd].clone();
+    Ok(new_fd as isize)
 }
 //ztr_mkdir
-pub fn sys_mkdirat(dirfd: isize, path: *const u8, mode: u32) -> isize {
-    let token = current_user_token();
+pub fn sys_mkdirat(dirfd: isize, path: *const u8, mode: u32) -> SysResult {
+    let path = copy_str_from_user(path)?;
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
-    let path = translated_str(token, path);
     _ = mode;
 
     if dirfd == AT_FDCWD {
         if let Some(_) = open_file(inner.get_work_path().as_str(), path.as_str(), OpenFlags::CREATE, DiskInodeType::Directory) {
-            0
+            Ok(0)
         } else {
-            -1
+            Err(Errno::ENOENT)
         }
     } else {
-        let dirfd = dirfd as usize;
-        if dirfd >= inner.fd_table.len() && dirfd > FD_LIMIT {
-            return -1;
-        }
-        if let Some(filedescriptor) = &inner.fd_table[dirfd] {
+        let filedescriptor = inner.fd_table.lock().get(dirfd as usize).cloned().flatten();
+        if let Some(filedescriptor) = filedescriptor {
             let file:Arc<OSInode> = match &filedescriptor.ftype {
                 FileType::File(file) => file.clone(),
-                _ => return -1,
+                _ => return Err(Errno::ENOTDIR),
             };
             if let Some(_) = open_file(file.get_name().as_str(), path.as_str(), OpenFlags::CREATE, DiskInodeType::Directory) {
-                0
+                Ok(0)
             } else {
-                -1
+                Err(Errno::ENOENT)
             }
         } else {
             // dirfd 对应条目为 None
-            -1
+            Err(Errno::EBADF)
         }
     }
 }
 
-/// buf：用于保存当前工作目录的字符串。当 buf 设为 NULL，由系统来分配缓存区
-pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
-    let token = current_user_token();
+/// 删除 `path` 的目录项，带 `AT_REMOVEDIR` 时删除的是空目录。
+/// 已打开的文件在关闭前仍可读写
+pub fn sys_unlinkat(dirfd: isize, path: *const u8, flags: u32) -> SysResult {
+    let path = copy_str_from_user(path)?;
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
+    let work_path = if dirfd == AT_FDCWD {
+        inner.get_work_path()
+    } else {
+        let filedescriptor = inner.fd_table.lock().get(dirfd as usize).cloned().flatten().ok_or(Errno::EBADF)?;
+        match &filedescriptor.ftype {
+            FileType::File(file) => file.get_name(),
+            _ => return Err(Errno::ENOTDIR),
+        }
+    };
+    drop(inner);
+
+    let path = absolute_path(work_path.as_str(), path.as_str());
+    if MNT_TABLE.lock().is_mount_point(path.as_str()) {
+        return Err(Errno::EBUSY);
+    }
+    let (parent_path, name) = split_path(path.as_str());
+    let parent = lookup(parent_path).ok_or(Errno::ENOENT)?;
+    let inode = parent.lookup(name).ok_or(Errno::ENOENT)?;
+    match (inode.is_dir(), flags & AT_REMOVEDIR != 0) {
+        (true, false) => return Err(Errno::EISDIR),
+        (false, true) => return Err(Errno::ENOTDIR),
+        _ => {}
+    }
+    parent.unlink(name)?;
+    Ok(0)
+}
+
+/// buf：用于保存当前工作目录的字符串。当 buf 设为 NULL，由系统来分配缓存区
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let mut cwd = task.inner_exclusive_access().work_path.clone().into_bytes();
+    cwd.push(0);
 
     if buf as usize == 0 {
-        unimplemented!();
+        // 由 C 库分配缓冲区，系统调用本身不支持
+        Err(Errno::EFAULT)
     } else {
-        let buf_vec = translated_byte_buffer(token, buf, len);
-        let mut userbuf = UserBuffer::new(buf_vec);
-        let cwd = inner.work_path.as_bytes();
-        userbuf.write(cwd);
-        return buf as isize;
+        // 还要放下结尾的 '\0'
+        if len < cwd.len() {
+            return Err(Errno::ERANGE);
+        }
+        copy_bytes_to_user(buf, &cwd)?;
+        return Ok(buf as isize);
     }
 }
 
-pub fn sys_chdir(path: *const u8) -> isize {
-    let token = current_user_token();
+pub fn sys_chdir(path: *const u8) -> SysResult {
+    let path = copy_str_from_user(path)?;
     let task = current_task().unwrap();
     let mut inner = task.inner_exclusive_access();
 
-    let path = translated_str(token, path);
     if let Some(new_cwd) = chdir(inner.work_path.as_str(),&path){
         inner.work_path = new_cwd;
-        0
+        Ok(0)
     } else {
-        -1
+        Err(Errno::ENOENT)
     }
     
 }
 //ztr_getdents
-pub fn sys_getdents64(fd: isize, buf: *mut u8, len: usize) -> isize {
-    let token = current_user_token();
+pub fn sys_getdents64(fd: isize, buf: *mut u8, len: usize) -> SysResult {
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
 
-    let dirfd = fd as usize;
-    if dirfd >= inner.fd_table.len() && dirfd > FD_LIMIT {
-        return -1;
-    }
+    let filedescriptor = match inner.fd_table.lock().get(fd as usize).cloned().flatten() {
+        Some(filedescriptor) => filedescriptor,
+        None => return Err(Errno::EBADF),
+    };
+    drop(inner);
+    let file:Arc<OSInode> = match &filedescriptor.ftype {
+        FileType::File(file) => file.clone(),
+        _ => return Err(Errno::ENOTDIR),
+    };
 
-    let buf_vec = translated_byte_buffer(token, buf, len);
-    let mut userbuf = UserBuffer::new(buf_vec);
+    let mut userbuf = user_buffer(buf, len, MapPermission::W)?;
     let mut dirent = DirEntry::empty();
     let dent_len = size_of::<DirEntry>();
     let mut total_len: usize = 0;
-    if let Some(filedescriptor) = &inner.fd_table[dirfd] {
-        let file:Arc<OSInode> = match &filedescriptor.ftype {
-            FileType::File(file) => file.clone(),
-            _ => return -1,
-        };
-        loop {
-            if total_len + dent_len > len {
-                break;
-            }
-            if file.get_dirent(&mut dirent) > 0 {
-                // 写入 userbuf
-                userbuf.write_at(total_len, dirent.as_bytes());
-                // 更新长度
-                total_len += dent_len;
-            } else {
-                break;
-            }
+    loop {
+        if total_len + dent_len > len {
+            break;
+        }
+        if file.get_dirent(&mut dirent) > 0 {
+            // 写入 userbuf
+            userbuf.write_at(total_len, dirent.as_bytes());
+            // 更新长度
+            total_len += dent_len;
+        } else {
+            break;
         }
-        total_len as isize
-    } else {
-        -1
     }
+    Ok(total_len as isize)
 }
 //ztr_fstat
-pub fn sys_fstat(fd: isize, buf: *mut u8) -> isize {
-    let token = current_user_token();
+pub fn sys_fstat(fd: isize, buf: *mut u8) -> SysResult {
     let task = current_task().unwrap();
-    let buf_vec = translated_byte_buffer(token, buf, size_of::<Kstat>());
     let inner = task.inner_exclusive_access();
 
-    let mut userbuf = UserBuffer::new(buf_vec);
+    let filedescriptor = match inner.fd_table.lock().get(fd as usize).cloned().flatten() {
+        Some(filedescriptor) => filedescriptor,
+        None => return Err(Errno::EBADF),
+    };
+    drop(inner);
+    let file:Arc<OSInode> = match &filedescriptor.ftype {
+        FileType::File(file) => file.clone(),
+        _ => return Err(Errno::EBADF),
+    };
     let mut kstat = Kstat::new();
+    file.get_fstat(&mut kstat);
+    copy_bytes_to_user(buf, kstat.as_bytes())?;
+    Ok(0)
+}
 
-    let dirfd = fd as usize;
-    if dirfd >= inner.fd_table.len() && dirfd > FD_LIMIT {
-        return -1;
-    }
-    if let Some(filedescriptor) = &inner.fd_table[dirfd] {
-        let file:Arc<OSInode> = match &filedescriptor.ftype {
-            FileType::File(file) => file.clone(),
-            _ => return -1,
-        };
-        file.get_fstat(&mut kstat);
-        userbuf.write(kstat.as_bytes());
-        0
-    } else {
-        -1
-    }
+/// 设备相关的控制操作，目前只有终端支持
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let filedescriptor = inner.fd_table.lock().get(fd).cloned().flatten().ok_or(Errno::EBADF)?;
+    drop(inner);
+    let file: Arc<dyn File + Send + Sync> = match &filedescriptor.ftype {
+        FileType::Abstr(f) => f.clone(),
+        FileType::File(f) => f.clone(),
+    };
+    file.ioctl(request, arg)
 }
 
 //ztr_pipe
-pub fn sys_pipe(pipe: *mut u32, flag: usize) -> isize {
+pub fn sys_pipe(pipe: *mut u32, flag: usize) -> SysResult {
     let task = current_task().unwrap();
-    let token = current_user_token();
     let mut inner = task.inner_exclusive_access();
 
     // todo 
     _ = flag;
 
     let (pipe_read, pipe_write) = make_pipe();
-    let read_fd = inner.alloc_fd();
-    inner.fd_table[read_fd] = Some(FileDescriptor::new(
+    let read_fd = inner.alloc_fd()?;
+    inner.fd_table.lock()[read_fd] = Some(FileDescriptor::new(
         true,
         FileType::Abstr(pipe_read),
     ));
-    let write_fd = inner.alloc_fd();
-    inner.fd_table[write_fd] = Some(FileDescriptor::new(
+    let write_fd = match inner.alloc_fd() {
+        Ok(fd) => fd,
+        Err(errno) => {
+            inner.fd_table.lock()[read_fd].take();
+            return Err(errno);
+        }
+    };
+    inner.fd_table.lock()[write_fd] = Some(FileDescriptor::new(
         true,
         FileType::Abstr(pipe_write),
     ));
-    *translated_refmut(token, pipe) = read_fd as u32;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd as u32;
-    0
+    drop(inner);
+    if let Err(errno) = copy_to_user(pipe as *mut [u32; 2], &[read_fd as u32, write_fd as u32]) {
+        // 用户程序拿不到这两个描述符
+        let inner = task.inner_exclusive_access();
+        let mut fd_table = inner.fd_table.lock();
+        fd_table[read_fd].take();
+        fd_table[write_fd].take();
+        return Err(errno);
+    }
+    Ok(0)
 }
 // pub fn sys_open(path: *const u8, flags: u32) -> isize {
 //     let task = current_task().unwrap();
@@ -376,35 +481,42 @@ pub fn sys_pipe(pipe: *mut u32, flag: usize) -> isize {
 // }
 
 //ztr_mount
-pub fn sys_mount(special: *const u8, dir: *const u8, fstype: *const u8, flags: usize, data: *const u8) -> isize {
-    let token = current_user_token();
-    let special = translated_str(token, special);
-    let dir = translated_str(token, dir);
-    let fstype = translated_str(token, fstype);
+/// 把类型为 `fstype` 的文件系统挂载到目录 `dir`，此后 `dir` 下的路径都在其中查找
+pub fn sys_mount(special: *const u8, dir: *const u8, fstype: *const u8, flags: usize, data: *const u8) -> SysResult {
+    let special = copy_str_from_user(special)?;
+    let dir = copy_str_from_user(dir)?;
+    let fstype = copy_str_from_user(fstype)?;
 
     _ = data;
 
-    MNT_TABLE.exclusive_access().mount(special, dir, fstype, flags as u32)
+    let dir = absolute_path(current_task().unwrap().inner_exclusive_access().get_work_path().as_str(), dir.as_str());
+    match lookup(dir.as_str()) {
+        Some(inode) if inode.is_dir() => {}
+        Some(_) => return Err(Errno::ENOTDIR),
+        None => return Err(Errno::ENOENT),
+    }
+    let fs = find_filesystem(fstype.as_str()).ok_or(Errno::ENODEV)?;
+    let sb = fs.mount(special.as_str(), flags as u32)?;
+    MNT_TABLE.lock().mount(special, dir, fstype, flags as u32, sb)?;
+    Ok(0)
 }
 
-pub fn sys_umount(p_special: *const u8, flags: usize) -> isize {
-    let token = current_user_token();
-    let special = translated_str(token, p_special);
-    MNT_TABLE.exclusive_access().umount(special, flags as u32)
+pub fn sys_umount(p_special: *const u8, flags: usize) -> SysResult {
+    let special = copy_str_from_user(p_special)?;
+    let target = absolute_path(current_task().unwrap().inner_exclusive_access().get_work_path().as_str(), special.as_str());
+    MNT_TABLE.lock().umount(target.as_str(), flags as u32)?;
+    Ok(0)
 }
 
-pub fn sys_close(fd: usize) -> isize {
+pub fn sys_close(fd: usize) -> SysResult {
     let task = current_task().unwrap();
-    let mut inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        drop(inner);
-        return -1;
-    }
-    if inner.fd_table[fd].is_none() {
-        drop(inner);
-        return -1;
+    let inner = task.inner_exclusive_access();
+    let mut fd_table = inner.fd_table.lock();
+    match fd_table.get_mut(fd) {
+        Some(file @ Some(_)) => {
+            file.take();
+            Ok(0)
+        }
+        _ => Err(Errno::EBADF),
     }
-    inner.fd_table[fd].take();
-    drop(inner);
-    0
 }
@@ -27,44 +27,81 @@
 const SYSCALL_GETCWD:   usize = 17;
 const SYSCALL_DUP:      usize = 23;
 const SYSCALL_DUP3:     usize = 24;
+const SYSCALL_IOCTL:    usize = 29;
 const SYSCALL_MKDIRAT:  usize = 34;
 const SYSCALL_UNLINKAT: usize = 35;
 const SYSCALL_UMOUNT2:  usize = 39;
 const SYSCALL_MOUNT:    usize = 40;
+const SYSCALL_FTRUNCATE: usize = 46;
 const SYSCALL_CHDIR:    usize = 49;
 const SYSCALL_OPENAT:   usize = 56;
 const SYSCALL_CLOSE:    usize = 57;
 const SYSCALL_PIPE:     usize = 59;
 const SYSCALL_GETDENTS64: usize = 61;
+const SYSCALL_LSEEK:    usize = 62;
 const SYSCALL_READ:     usize = 63;
 const SYSCALL_WRITE:    usize = 64;
+const SYSCALL_PREAD64:  usize = 67;
+const SYSCALL_PWRITE64: usize = 68;
 const SYSCALL_FSTAT:    usize = 80;
 const SYSCALL_EXIT:     usize = 93;
+const SYSCALL_EXIT_GROUP: usize = 94;
+const SYSCALL_FUTEX:    usize = 98;
 const SYSCALL_NANOSLEEP:usize = 101;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
+/// sched_yield
 const SYSCALL_YIELD:    usize = 124;
 const SYSCALL_KILL:     usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_SETPRIORITY: usize = 140;
+const SYSCALL_GETPRIORITY: usize = 141;
+const SYSCALL_SETPGID:  usize = 154;
+const SYSCALL_GETPGID:  usize = 155;
+const SYSCALL_GETSID:   usize = 156;
+const SYSCALL_SETSID:   usize = 157;
 const SYSCALL_UNAME:    usize = 160;
-const SYSCALL_GET_TIME: usize = 153;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_TIMES:    usize = 153;
+const SYSCALL_GETRUSAGE: usize = 165;
+/// gettimeofday
+const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID:   usize = 172;
 const SYSCALL_GETPPID:  usize = 173;
+const SYSCALL_GETTID:   usize = 178;
 const SYSCALL_BRK:      usize = 214;
 const SYSCALL_MUNMAP:   usize = 215;
-const SYSCALL_FORK:     usize = 220;
+const SYSCALL_CLONE:    usize = 220;
 const SYSCALL_EXEC:     usize = 221;
 const SYSCALL_MMAP:     usize = 222;
-const SYSCALL_WAITPID:  usize = 260;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_MSYNC:    usize = 227;
+const SYSCALL_WAIT4:    usize = 260;
+const SYSCALL_PRLIMIT64: usize = 261;
 
+mod errno;
 mod fs;
 mod process;
+mod sync;
 
+pub use errno::{Errno, SysResult};
 use fs::*;
 use process::*;
-/// handle syscall exception with `syscall_id` and other arguments
+use sync::*;
+use crate::timer::{tms, Rusage, TimeSpec};
+use crate::task::{RLimit, SigAction};
+use log::warn;
+/// handle syscall exception with `syscall_id` and other arguments,
+/// an error is returned as `-errno`
 pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
-    match syscall_id {
+    let ret: SysResult = match syscall_id {
         //ztr_openat
         SYSCALL_GETCWD =>   sys_getcwd(args[0] as *mut u8, args[1] as usize),
+        SYSCALL_IOCTL =>    sys_ioctl(args[0], args[1], args[2]),
         SYSCALL_MKDIRAT =>  sys_mkdirat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYSCALL_UNLINKAT => sys_unlinkat(args[0] as isize, args[1] as *const u8, args[2] as u32),
         SYSCALL_DUP =>      sys_dup(args[0]),
         SYSCALL_DUP3 =>     sys_dup3(args[0] as usize, args[1] as usize),
         SYSCALL_CHDIR=>     sys_chdir(args[0] as *const u8),
@@ -77,19 +114,42 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         ),
         SYSCALL_UMOUNT2=>   sys_umount(args[0] as *const u8, args[1] as usize),
         SYSCALL_MOUNT=>     sys_mount(args[0] as *const u8, args[1] as *const u8, args[2] as *const u8, args[3] as usize, args[4] as *const u8),
+        SYSCALL_FTRUNCATE => sys_ftruncate(args[0], args[1] as isize),
         SYSCALL_PIPE =>     sys_pipe(args[0] as *mut u32,args[1]),
         SYSCALL_CLOSE => sys_close(args[0]),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_LSEEK =>    sys_lseek(args[0], args[1] as isize, args[2]),
+        SYSCALL_PREAD64 =>  sys_pread64(args[0], args[1] as *const u8, args[2], args[3] as isize),
+        SYSCALL_PWRITE64 => sys_pwrite64(args[0], args[1] as *const u8, args[2], args[3] as isize),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_EXIT_GROUP => sys_exit_group(args[0] as i32),
         SYSCALL_YIELD => sys_yield(),
         SYSCALL_FSTAT=>     sys_fstat(args[0] as isize, args[1] as *mut u8),
         SYSCALL_GETDENTS64 => sys_getdents64(args[0] as isize, args[1] as *mut u8, args[2] as usize),
         SYSCALL_UNAME =>    sys_uname(args[0] as *const u8),
-        SYSCALL_NANOSLEEP=> sys_nanosleep(args[0] as *const u8),
+        SYSCALL_FUTEX => sys_futex(args[0] as *const u32, args[1], args[2], args[3] as *const TimeSpec),
+        SYSCALL_NANOSLEEP=> sys_nanosleep(args[0] as *const TimeSpec, args[1] as *mut TimeSpec),
         SYSCALL_GET_TIME => sys_get_time(args[0] as *const u8),
+        SYSCALL_TIMES =>    sys_times(args[0] as *mut tms),
+        SYSCALL_GETRUSAGE => sys_getrusage(args[0] as isize, args[1] as *mut Rusage),
+        SYSCALL_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1] as *mut TimeSpec),
+        SYSCALL_KILL =>     sys_kill(args[0] as isize, args[1]),
+        SYSCALL_SIGACTION => sys_sigaction(args[0], args[1] as *const SigAction, args[2] as *mut SigAction),
+        SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0], args[1] as *const u64, args[2] as *mut u64),
+        SYSCALL_SIGRETURN => sys_sigreturn(),
+        SYSCALL_SETPRIORITY => sys_setpriority(args[0], args[1], args[2] as isize),
+        SYSCALL_GETPRIORITY => sys_getpriority(args[0], args[1]),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1]),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
+        SYSCALL_GETSID => sys_getsid(args[0]),
+        SYSCALL_SETSID => sys_setsid(),
+        SYSCALL_GETRLIMIT => sys_getrlimit(args[0], args[1] as *mut RLimit),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], args[1] as *const RLimit),
+        SYSCALL_PRLIMIT64 => sys_prlimit64(args[0], args[1], args[2] as *const RLimit, args[3] as *mut RLimit),
         SYSCALL_GETPID => sys_getpid(),
         SYSCALL_GETPPID => sys_getppid(),
+        SYSCALL_GETTID => sys_gettid(),
         //ztr_brk
         SYSCALL_BRK => sys_brk(args[0]),
         //ztr_mmap
@@ -101,9 +161,18 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
                args[4] as usize, 
               args[5] as usize),
         SYSCALL_MUNMAP =>  sys_munmap(args[0] as usize, args[1] as usize),    
-        SYSCALL_FORK => sys_fork(),
-        SYSCALL_EXEC => sys_exec(args[0] as *const u8),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYSCALL_MPROTECT => sys_mprotect(args[0] as usize, args[1] as usize, args[2] as u32),
+        SYSCALL_MSYNC =>   sys_msync(args[0] as usize, args[1] as usize, args[2] as u32),
+        SYSCALL_CLONE => sys_clone(args[0], args[1], args[2] as *mut u32, args[3], args[4] as *mut u32),
+        SYSCALL_EXEC => sys_execve(args[0] as *const u8, args[1] as *const usize, args[2] as *const usize),
+        SYSCALL_WAIT4 =>    sys_wait4(args[0] as isize, args[1] as *mut i32, args[2] as u32, args[3] as *mut Rusage),
+        _ => {
+            warn!("Unsupported syscall_id: {}", syscall_id);
+            Err(Errno::ENOSYS)
+        }
+    };
+    match ret {
+        Ok(ret) => ret,
+        Err(errno) => -(errno as isize),
     }
 }
@@ -1,11 +1,24 @@
+use crate::config::{KERNEL_HEAP_SIZE, PAGE_SIZE, USER_SPACE_END, USER_STACK_SIZE};
 use crate::fs::{open_file, OpenFlags, DiskInodeType};
-use crate::mm::{translated_refmut, translated_str, UserBuffer, translated_byte_buffer,translated_ref};
+use crate::mm::{copy_bytes_to_user, copy_from_user, copy_str_from_user, copy_to_user, MapPermission};
 use crate::task::{
-    add_task, current_task, current_user_token, exit_current_and_run_next,
-    suspend_current_and_run_next, Utsname, UTSNAME,
+    add_task, current_task, exit_current_and_run_next, exit_group_and_run_next,
+    suspend_current_and_run_next, block_current_and_run_next, Utsname, UTSNAME,
+    insert_into_pid2task, pid2task, send_signal, current_has_signal, sigreturn_current,
+    SigAction, SignalFlags, SIG_IGN, CloneFlags, TaskControlBlock, process_cpu_times,
+    probe, Binfmt, BINFMT_MAX_DEPTH, process_group, processes, thread_group, INITPROC, exec_current,
+    RLimit, RLIM_NLIMITS, RLIMIT_AS, RLIMIT_DATA, RLIMIT_NPROC,
 };
-use crate::timer::{TimeVal, tms, get_TimeVal, get_time_ms};
+use super::{Errno, SysResult};
+use core::mem::size_of;
+use crate::timer::{
+    TimeVal, TimeSpec, tms, get_TimeVal, get_time_ms, get_time_ns, add_timer, remove_timer, Rusage,
+    CLOCKS_PER_SEC,
+};
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 //ztr_brk
 use log::{info};
 
@@ -14,159 +27,694 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("Unreachable in sys_exit!");
 }
 
-pub fn sys_yield() -> isize {
+/// 结束进程的所有线程
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    exit_group_and_run_next(exit_code);
+    panic!("Unreachable in sys_exit_group!");
+}
+
+/// 让出 CPU，时间片未用完，MLFQ 调度器不会降低其优先级
+pub fn sys_yield() -> SysResult {
     suspend_current_and_run_next();
-    0
+    Ok(0)
+}
+
+/// `setpriority`/`getpriority` 的 `which`，只支持按进程设置
+const PRIO_PROCESS: usize = 0;
+
+/// `which == PRIO_PROCESS` 时 `who` 指定的任务，0 表示当前任务
+fn priority_target(which: usize, who: usize) -> Result<Arc<TaskControlBlock>, Errno> {
+    if which != PRIO_PROCESS {
+        return Err(Errno::EINVAL);
+    }
+    let task = if who == 0 {
+        current_task()
+    } else {
+        pid2task(who)
+    };
+    task.ok_or(Errno::ESRCH)
 }
 
-pub fn sys_getpid() -> isize {
-    // let re = current_task().unwrap().pid.0;
-    // re as isize
-    2
+/// 设置任务的 nice 值，超出 [-20, 19] 的值取最近的边界
+pub fn sys_setpriority(which: usize, who: usize, prio: isize) -> SysResult {
+    let task = priority_target(which, who)?;
+    task.inner_exclusive_access().sched.nice = prio.clamp(-20, 19);
+    Ok(0)
+}
+
+/// 与 Linux 系统调用相同，返回 `20 - nice`，范围为 [1, 40]
+pub fn sys_getpriority(which: usize, who: usize) -> SysResult {
+    let task = priority_target(which, who)?;
+    let nice = task.inner_exclusive_access().sched.nice;
+    Ok(20 - nice)
+}
+
+pub fn sys_getpid() -> SysResult {
+    Ok(current_task().unwrap().gettgid() as isize)
+}
+
+pub fn sys_gettid() -> SysResult {
+    Ok(current_task().unwrap().getpid() as isize)
 }
 //ztr_getppid
-pub fn sys_getppid() -> isize {
-    unsafe{
-        current_task().unwrap().inner_exclusive_access().parent.clone().unwrap().as_ptr().as_ref().unwrap().pid.0 as isize
+pub fn sys_getppid() -> SysResult {
+    let parent = current_task().unwrap().inner_exclusive_access().parent.clone();
+    // 线程组的父进程，initproc 没有父进程
+    match parent.and_then(|parent| parent.upgrade()) {
+        Some(parent) => Ok(parent.gettgid() as isize),
+        None => Ok(0),
+    }
+}
+
+/// 将进程 `pid` 移入进程组 `pgid`，`pid` 为 0 表示当前进程，`pgid` 为 0 表示
+/// 以 `pid` 为组号新建进程组。只能设置自己或子进程，且不能离开所在的会话
+pub fn sys_setpgid(pid: usize, pgid: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let tgid = task.gettgid();
+    let pid = if pid == 0 { tgid } else { pid };
+    let pgid = if pgid == 0 { pid } else { pgid };
+    let target = if pid == tgid {
+        pid2task(tgid).ok_or(Errno::ESRCH)?
+    } else {
+        let inner = task.inner_exclusive_access();
+        let child = inner.children.iter().find(|child| child.getpid() == pid).cloned();
+        drop(inner);
+        child.ok_or(Errno::ESRCH)?
+    };
+    let sid = task.inner_exclusive_access().sid;
+    let target_sid = target.inner_exclusive_access().sid;
+    // 会话首进程不能换组，也不能把进程移出会话
+    if target_sid == pid || target_sid != sid {
+        return Err(Errno::EPERM);
+    }
+    if pgid != pid
+        && !process_group(pgid)
+            .iter()
+            .any(|process| process.inner_exclusive_access().sid == sid)
+    {
+        return Err(Errno::EPERM);
+    }
+    for thread in thread_group(pid) {
+        thread.inner_exclusive_access().pgid = pgid;
+    }
+    Ok(0)
+}
+
+/// 进程 `pid` 的进程组号，`pid` 为 0 表示当前进程
+pub fn sys_getpgid(pid: usize) -> SysResult {
+    let task = if pid == 0 { current_task().unwrap() } else { pid2task(pid).ok_or(Errno::ESRCH)? };
+    let pgid = task.inner_exclusive_access().pgid;
+    Ok(pgid as isize)
+}
+
+/// 新建会话与进程组，当前进程成为两者的首进程
+pub fn sys_setsid() -> SysResult {
+    let tgid = current_task().unwrap().gettgid();
+    // 已是某个进程组的组长时不能新建会话
+    if !process_group(tgid).is_empty() {
+        return Err(Errno::EPERM);
+    }
+    for thread in thread_group(tgid) {
+        let mut inner = thread.inner_exclusive_access();
+        inner.sid = tgid;
+        inner.pgid = tgid;
     }
+    Ok(tgid as isize)
+}
+
+/// 进程 `pid` 的会话号，`pid` 为 0 表示当前进程
+pub fn sys_getsid(pid: usize) -> SysResult {
+    let task = if pid == 0 { current_task().unwrap() } else { pid2task(pid).ok_or(Errno::ESRCH)? };
+    let sid = task.inner_exclusive_access().sid;
+    Ok(sid as isize)
+}
+
+/// 读取并设置进程 `pid` 的资源限制 `resource`，`pid` 为 0 表示当前进程。
+/// 限制属于整个进程，新的限制对所有线程生效
+pub fn sys_prlimit64(pid: usize, resource: usize, new_limit: *const RLimit, old_limit: *mut RLimit) -> SysResult {
+    if resource >= RLIM_NLIMITS {
+        return Err(Errno::EINVAL);
+    }
+    let task = if pid == 0 { current_task().unwrap() } else { pid2task(pid).ok_or(Errno::ESRCH)? };
+    let new_limit = if new_limit.is_null() { None } else { Some(copy_from_user(new_limit)?) };
+    let old = task.inner_exclusive_access().rlimits.get(resource);
+    if let Some(limit) = new_limit {
+        // 先在本任务上检查，再同步到其他线程
+        task.inner_exclusive_access().rlimits.set(resource, limit)?;
+        for thread in thread_group(task.gettgid()) {
+            let _ = thread.inner_exclusive_access().rlimits.set(resource, limit);
+        }
+    }
+    if !old_limit.is_null() {
+        copy_to_user(old_limit, &old)?;
+    }
+    Ok(0)
+}
+
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> SysResult {
+    sys_prlimit64(0, resource, core::ptr::null(), rlim)
+}
+
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> SysResult {
+    sys_prlimit64(0, resource, rlim, core::ptr::null_mut())
 }
 
 //ztr_brk
-pub fn sys_brk(brk_addr: usize) -> isize{
+pub fn sys_brk(brk_addr: usize) -> SysResult{
     //获取当前任务
     let task = current_task().unwrap();
     //当前任务地址空间
     //???
-    let memory_set = &mut task.inner_exclusive_access().memory_set;
+    let inner = task.inner_exclusive_access();
+    let mut memory_set = inner.memory_set.lock();
+    // 堆的大小受 RLIMIT_DATA 限制，也不能让地址空间超过 RLIMIT_AS
+    let as_room = inner.rlimits.get(RLIMIT_AS).cur.saturating_sub(memory_set.mapped_size() - memory_set.heap_size());
+    let limit = inner.rlimits.get(RLIMIT_DATA).cur.min(as_room);
     let new_ptr;
     if brk_addr == 0 {
-        new_ptr = memory_set.sbrk(0);
+        new_ptr = memory_set.sbrk(0, limit);
     } else {
-        let former_addr = memory_set.sbrk(0);
-        let grow_size: isize = (brk_addr - former_addr) as isize;
-        new_ptr = memory_set.sbrk(grow_size);
+        let former_addr = memory_set.sbrk(0, limit);
+        let grow_size: isize = brk_addr as isize - former_addr as isize;
+        new_ptr = memory_set.sbrk(grow_size, limit);
     }
     drop(memory_set);
+    drop(inner);
     info!(
         "[sys_brk] brk_addr: {:X}; new_addr: {:X}",
         brk_addr, new_ptr
     );
     if new_ptr == 0 {
-        -1
+        Err(Errno::ENOMEM)
     }else{
-        new_ptr as isize
+        Ok(new_ptr as isize)
     }
 }
 //ztr_unname
-pub fn sys_uname(buf: *const u8) -> isize {
-    let token = current_user_token();
-    let uname = UTSNAME.exclusive_access();
-    let buf_vec = translated_byte_buffer(token, buf, core::mem::size_of::<Utsname>());
-    let mut userbuf = UserBuffer::new(buf_vec);
-    userbuf.write(uname.as_bytes());
-    0
+pub fn sys_uname(buf: *const u8) -> SysResult {
+    let uname = UTSNAME.lock();
+    copy_bytes_to_user(buf as *mut u8, uname.as_bytes())?;
+    Ok(0)
+}
+
+/// 填写当前进程及已回收子进程的 CPU 时间，返回开机以来的时钟计数
+pub fn sys_times(buf: *mut tms) -> SysResult {
+    let times = process_cpu_times(current_task().unwrap().gettgid());
+    copy_to_user(buf, &tms::from_times(&times))?;
+    Ok((get_time_ns() / (1_000_000_000 / CLOCKS_PER_SEC)) as isize)
+}
+
+const RUSAGE_SELF: isize = 0;
+const RUSAGE_CHILDREN: isize = -1;
+const RUSAGE_THREAD: isize = 1;
+
+/// 只统计用户态与内核态时间
+pub fn sys_getrusage(who: isize, buf: *mut Rusage) -> SysResult {
+    let task = current_task().unwrap();
+    let usage = match who {
+        RUSAGE_SELF => {
+            let times = process_cpu_times(task.gettgid());
+            Rusage::from_ns(times.utime, times.stime)
+        }
+        RUSAGE_CHILDREN => {
+            let times = task.inner_exclusive_access().cpu_times;
+            Rusage::from_ns(times.cutime, times.cstime)
+        }
+        RUSAGE_THREAD => {
+            let times = task.inner_exclusive_access().cpu_times;
+            Rusage::from_ns(times.utime, times.stime)
+        }
+        _ => return Err(Errno::EINVAL),
+    };
+    copy_to_user(buf, &usage)?;
+    Ok(0)
 }
+
+/// 没有实时时钟，`CLOCK_REALTIME` 与 `CLOCK_MONOTONIC` 相同，均从开机时算起
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+const CLOCK_THREAD_CPUTIME_ID: usize = 3;
+
+/// 纳秒精度的时钟
+pub fn sys_clock_gettime(clockid: usize, tp: *mut TimeSpec) -> SysResult {
+    let task = current_task().unwrap();
+    let ns = match clockid {
+        CLOCK_REALTIME | CLOCK_MONOTONIC => get_time_ns(),
+        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => {
+            // 结算到此刻为止的内核态时间
+            task.inner_exclusive_access().cpu_times.leave_kernel();
+            let times = if clockid == CLOCK_PROCESS_CPUTIME_ID {
+                process_cpu_times(task.gettgid())
+            } else {
+                task.inner_exclusive_access().cpu_times
+            };
+            times.utime + times.stime
+        }
+        _ => return Err(Errno::EINVAL),
+    };
+    copy_to_user(tp, &TimeSpec::from_ns(ns))?;
+    Ok(0)
+}
+
 //ztr_time
-pub fn sys_get_time(buf: *const u8) -> isize {
-    let token = current_user_token();
-    let buffers = translated_byte_buffer(token, buf, core::mem::size_of::<TimeVal>());
-    let mut userbuf = UserBuffer::new(buffers);
-    userbuf.write(get_TimeVal().as_bytes());
-    0
+pub fn sys_get_time(buf: *const u8) -> SysResult {
+    copy_to_user(buf as *mut TimeVal, &get_TimeVal())?;
+    Ok(0)
 }
 //ztr_sleep
-pub fn sys_nanosleep(buf: *const u8) -> isize {
-    let tic = get_time_ms();
-    println!("Sleep");
-    let token = current_user_token();
-    let len_timeval = translated_ref(token, buf as *const TimeVal);
-    let len = len_timeval.sec * 1000 + len_timeval.usec / 1000;
+/// 睡眠 `req` 指定的时长，被信号打断时返回 `EINTR`，并在 `rem` 非空时写回剩余时长
+pub fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> SysResult {
+    let req = copy_from_user(req)?;
+    if !req.is_valid() {
+        return Err(Errno::EINVAL);
+    }
+    let expire_ms = get_time_ms() + req.as_ms();
+    // 挂起当前任务，由定时器到期时唤醒
+    add_timer(expire_ms, current_task().unwrap());
     loop {
-        let toc = get_time_ms();
-        if toc - tic >= len {
+        let now_ms = get_time_ms();
+        if now_ms >= expire_ms {
             break;
         }
-    };
-    0
+        if current_has_signal() {
+            // 被信号打断，未到期的定时器不能再唤醒任务
+            remove_timer(&current_task().unwrap());
+            if !rem.is_null() {
+                copy_to_user(rem, &TimeSpec::from_ns((expire_ms - now_ms) * 1_000_000))?;
+            }
+            return Err(Errno::EINTR);
+        }
+        block_current_and_run_next();
+    }
+    Ok(0)
 }
-pub fn sys_fork() -> isize {
+/// 创建进程或线程，`CLONE_VM` 共享地址空间，`CLONE_FILES` 共享文件描述符表，
+/// `CLONE_THREAD` 加入调用者的线程组（要求同时共享地址空间）
+pub fn sys_clone(flags: usize, stack: usize, ptid: *mut u32, tls: usize, ctid: *mut u32) -> SysResult {
+    let flags = CloneFlags::from_bits_truncate(flags);
+    if flags.contains(CloneFlags::CLONE_THREAD) && !flags.contains(CloneFlags::CLONE_VM) {
+        return Err(Errno::EINVAL);
+    }
     let current_task = current_task().unwrap();
-    let new_task = current_task.fork();
+    // 新进程的数目受 RLIMIT_NPROC 限制，线程不计
+    if !flags.contains(CloneFlags::CLONE_THREAD) {
+        let limit = current_task.inner_exclusive_access().rlimits.get(RLIMIT_NPROC).cur;
+        if processes().len() >= limit {
+            return Err(Errno::EAGAIN);
+        }
+    }
+    // 先检查要写入 tid 的地址，任务创建之后就不再失败。子任务的地址空间复制自
+    // 当前任务，两者的检查结果相同
+    {
+        let inner = current_task.inner_exclusive_access();
+        let mut memory_set = inner.memory_set.lock();
+        if flags.contains(CloneFlags::CLONE_PARENT_SETTID) {
+            memory_set.check_user_range(ptid as usize, size_of::<u32>(), MapPermission::W)?;
+        }
+        if flags.contains(CloneFlags::CLONE_CHILD_SETTID) {
+            memory_set.check_user_range(ctid as usize, size_of::<u32>(), MapPermission::W)?;
+        }
+    }
+    let new_task = current_task.clone_task(flags, stack);
     let new_pid = new_task.pid.0;
+    let tid_bytes = (new_pid as u32).to_ne_bytes();
+    if flags.contains(CloneFlags::CLONE_PARENT_SETTID) {
+        let inner = current_task.inner_exclusive_access();
+        let _ = inner.memory_set.lock().copy_to_user(ptid as usize, &tid_bytes);
+    }
+    let mut new_inner = new_task.inner_exclusive_access();
+    if flags.contains(CloneFlags::CLONE_CHILD_SETTID) {
+        // 写入子任务的地址空间，fork 时两者不同
+        let _ = new_inner.memory_set.lock().copy_to_user(ctid as usize, &tid_bytes);
+    }
+    if flags.contains(CloneFlags::CLONE_CHILD_CLEARTID) {
+        new_inner.clear_child_tid = ctid as usize;
+    }
     // modify trap context of new_task, because it returns immediately after switching
-    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
+    let trap_cx = new_inner.get_trap_cx();
     // we do not have to move to next instruction since we have done it before
     // for child process, fork returns 0
     trap_cx.x[10] = 0;
+    // 线程局部存储的指针
+    if flags.contains(CloneFlags::CLONE_SETTLS) {
+        trap_cx.x[4] = tls;
+    }
+    drop(new_inner);
     // add new task to scheduler
+    insert_into_pid2task(new_pid, new_task.clone());
     add_task(new_task);
-    new_pid as isize
+    Ok(new_pid as isize)
+}
+
+/// `argv` 与 `envp` 的字符串连同指针所占空间的上限，都要放在用户栈上。
+/// 与 Linux 一样取新程序栈大小的 1/4，但不少于默认栈的一半；
+/// 参数先复制到内核堆中，因此也不超过内核堆的 1/8
+fn arg_max() -> usize {
+    let stack_size = current_task().unwrap().user_stack_size();
+    (stack_size / 4).clamp(USER_STACK_SIZE / 2, KERNEL_HEAP_SIZE / 8)
+}
+
+/// 字符串在用户栈上占用的空间，包括结尾的 0 与指向它的指针
+fn arg_size(string: &String) -> usize {
+    string.len() + 1 + size_of::<usize>()
+}
+
+/// 读取以空指针结尾的用户字符串指针数组，`ptr` 为空时视为空数组。
+/// `total` 累计已读取的字节数，超过 `arg_max` 时返回 E2BIG
+fn copy_str_array_from_user(mut ptr: *const usize, total: &mut usize, arg_max: usize) -> Result<Vec<String>, Errno> {
+    let mut strings = Vec::new();
+    if ptr.is_null() {
+        return Ok(strings);
+    }
+    loop {
+        let str_ptr = copy_from_user(ptr)?;
+        if str_ptr == 0 {
+            return Ok(strings);
+        }
+        let string = copy_str_from_user(str_ptr as *const u8)?;
+        *total += arg_size(&string);
+        if *total > arg_max {
+            return Err(Errno::E2BIG);
+        }
+        strings.push(string);
+        ptr = unsafe { ptr.add(1) };
+    }
 }
 
-pub fn sys_exec(path: *const u8) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    //let inner = &mut task.inner_exclusive_access();
-    //ztr_file
-    if let Some(app_inode) = open_file(
+/// 以 `argv`、`envp` 执行 `path`，关闭带 CLOEXEC 标志的文件描述符。
+/// `#!` 脚本交给其解释器执行，无法识别的格式返回 ENOEXEC
+pub fn sys_execve(path: *const u8, argv: *const usize, envp: *const usize) -> SysResult {
+    let mut path = copy_str_from_user(path)?;
+    let arg_max = arg_max();
+    let mut total = 0;
+    let mut args = copy_str_array_from_user(argv, &mut total, arg_max)?;
+    let envs = copy_str_array_from_user(envp, &mut total, arg_max)?;
+    for _ in 0..=BINFMT_MAX_DEPTH {
         //ztr_file
-        "/",
-        path.as_str(),
-        OpenFlags::RDONLY,
-        DiskInodeType::File,) {
+        let app_inode = match open_file("/", path.as_str(), OpenFlags::RDONLY, DiskInodeType::File) {
+            Some(app_inode) => app_inode,
+            None => return Err(Errno::ENOENT),
+        };
         let all_data = app_inode.read_all();
-        let task = current_task().unwrap();
-        task.exec(all_data.as_slice());
-        0
-    }
-    else {
-        -1
+        match probe(all_data.as_slice()) {
+            Some(Binfmt::Elf { interp }) => {
+                // a dynamically linked program is started by its interpreter
+                let interp_data = match interp {
+                    Some(interp) => match open_file("/", interp.as_str(), OpenFlags::RDONLY, DiskInodeType::File) {
+                        Some(interp_inode) => Some(interp_inode.read_all()),
+                        None => return Err(Errno::ENOENT),
+                    },
+                    None => None,
+                };
+                // the interpreter must be a static ELF image itself
+                if let Some(interp_data) = &interp_data {
+                    if !matches!(probe(interp_data), Some(Binfmt::Elf { interp: None })) {
+                        return Err(Errno::ENOEXEC);
+                    }
+                }
+                let task = current_task().unwrap();
+                let image = task.load_image(all_data.as_slice(), interp_data.as_deref(), &args, &envs)?;
+                drop(task);
+                exec_current(image);
+                // a0 is not argc, the program finds its arguments on the stack
+                return Ok(0);
+            }
+            Some(Binfmt::Script { interp, arg }) => {
+                // argv[0] is replaced by `interp [arg] path`
+                let mut script_args = vec![interp.clone()];
+                script_args.extend(arg);
+                script_args.push(path);
+                script_args.extend(args.into_iter().skip(1));
+                args = script_args;
+                path = interp;
+                // the rewritten argv must still fit on the stack
+                if args.iter().chain(envs.iter()).map(arg_size).sum::<usize>() > arg_max {
+                    return Err(Errno::E2BIG);
+                }
+            }
+            None => return Err(Errno::ENOEXEC),
+        }
     }
+    Err(Errno::ELOOP)
 }
 
-/// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// `wait4` 的 `options`：没有已退出的子进程时立即返回 0
+const WNOHANG: u32 = 1;
+/// 也报告被信号停止的子进程
+const WUNTRACED: u32 = 2;
+
+/// 等待子进程退出，将退出状态写入 `wstatus`。`pid` 为 -1 表示任意子进程，
+/// 0 表示同一进程组的子进程，小于 -1 表示进程组 `-pid` 中的子进程
+pub fn sys_wait4(pid: isize, wstatus: *mut i32, options: u32, rusage: *mut Rusage) -> SysResult {
     let task = current_task().unwrap();
-    // find a child process
+    let own_pgid = task.inner_exclusive_access().pgid;
+    let wanted = |child: &Arc<TaskControlBlock>, child_pgid: usize| match pid {
+        -1 => true,
+        0 => child_pgid == own_pgid,
+        pid if pid > 0 => child.getpid() == pid as usize,
+        pid => child_pgid == (-pid) as usize,
+    };
+    loop {
+        // queue up before looking, a child exiting on another hart meanwhile
+        // wakes us up
+        task.child_exit.prepare_to_wait();
+        // find a child process
 
-    // ---- access current PCB exclusively
-    let mut inner = task.inner_exclusive_access();
-    if !inner
-        .children
-        .iter()
-        .any(|p| pid == -1 || pid as usize == p.getpid())
-    {
-        return -1;
+        // ---- access current PCB exclusively
+        let mut inner = task.inner_exclusive_access();
+        if !inner
+            .children
+            .iter()
+            .any(|p| wanted(p, p.inner_exclusive_access().pgid))
+        {
+            drop(inner);
+            task.child_exit.remove(&task);
+            return Err(Errno::ECHILD);
+            // ---- release current PCB
+        }
+        let pair = inner.children.iter().enumerate().find(|(_, p)| {
+            // ++++ temporarily access child PCB exclusively
+            let p_inner = p.inner_exclusive_access();
+            // a process is reaped once all of its threads have exited
+            p_inner.is_zombie() && p_inner.live_threads == 0 && wanted(p, p_inner.pgid)
+            // ++++ release child PCB
+        });
+        if let Some((idx, _)) = pair {
+            let child = inner.children.remove(idx);
+            // the hart which the child exited on may still hold it for a
+            // moment, so it is not always the last reference here
+            let found_pid = child.getpid();
+            // ++++ temporarily access child PCB exclusively
+            let child_inner = child.inner_exclusive_access();
+            let (exit_status, child_times) = (child_inner.exit_status, child_inner.cpu_times);
+            drop(child_inner);
+            // ++++ release child PCB
+            // the times of reaped children are kept by the first thread
+            let group_leader = inner.group_leader.clone();
+            match &group_leader {
+                Some(group_leader) => {
+                    drop(inner);
+                    group_leader.inner_exclusive_access().cpu_times.add_child(&child_times);
+                }
+                None => {
+                    inner.cpu_times.add_child(&child_times);
+                    drop(inner);
+                }
+            }
+            task.child_exit.remove(&task);
+            if !wstatus.is_null() {
+                copy_to_user(wstatus, &exit_status)?;
+            }
+            if !rusage.is_null() {
+                let usage = Rusage::from_ns(
+                    child_times.utime + child_times.cutime,
+                    child_times.stime + child_times.cstime,
+                );
+                copy_to_user(rusage, &usage)?;
+            }
+            return Ok(found_pid as isize);
+        }
+        if options & WUNTRACED != 0 {
+            // a stopped child is reported once, it stays our child
+            let stopped = inner.children.iter().find_map(|p| {
+                let mut p_inner = p.inner_exclusive_access();
+                if !wanted(p, p_inner.pgid) {
+                    return None;
+                }
+                p_inner.stop_status.take().map(|status| (p.getpid(), status))
+            });
+            if let Some((found_pid, status)) = stopped {
+                drop(inner);
+                task.child_exit.remove(&task);
+                if !wstatus.is_null() {
+                    copy_to_user(wstatus, &status)?;
+                }
+                return Ok(found_pid as isize);
+            }
+        }
+        drop(inner);
         // ---- release current PCB
+        if options & WNOHANG != 0 {
+            task.child_exit.remove(&task);
+            return Ok(0);
+        }
+        // interrupted by a signal, the SIGCHLD of an exiting child comes
+        // before the wakeup, so the children are looked at first
+        if current_has_signal() {
+            task.child_exit.remove(&task);
+            return Err(Errno::EINTR);
+        }
+        // sleep until a child exits, then look again
+        block_current_and_run_next();
+        // a signal wakes us up without taking us out of the queue
+        task.child_exit.remove(&task);
     }
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        // ++++ temporarily access child PCB exclusively
-        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
-        // ++++ release child PCB
-    });
-    if let Some((idx, _)) = pair {
-        let child = inner.children.remove(idx);
-        // confirm that child will be deallocated after being removed from children list
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
-        // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
-        // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
-    } else {
-        -2
-    }
-    // ---- release current PCB automatically
 }
 //ztr_mmap
-pub fn sys_mmap(start: usize, len: usize, prot: u32, _flags: u32, fd: usize, off: usize) -> isize{
+pub fn sys_mmap(start: usize, len: usize, prot: u32, _flags: u32, fd: usize, off: usize) -> SysResult{
     let task = current_task().unwrap();
     task.mmap(start, len, prot, _flags, fd, off)
 }
 //ztr_munmap
-pub fn sys_munmap(start: usize, len: usize) -> isize {
+pub fn sys_munmap(start: usize, len: usize) -> SysResult {
+    let task = current_task().unwrap();
+    match task.munmap(start, len) {
+        0 => Ok(0),
+        _ => Err(Errno::EINVAL),
+    }
+}
+/// 修改 `[start, start + len)` 的访问权限
+pub fn sys_mprotect(start: usize, len: usize, prot: u32) -> SysResult {
+    if start % PAGE_SIZE != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let mut map_perm = MapPermission::from_bits_truncate(((prot & 0b111) << 1) as u8) | MapPermission::U;
+    // a writable PTE without R is reserved in RISC-V, PROT_WRITE implies PROT_READ
+    if map_perm.contains(MapPermission::W) {
+        map_perm |= MapPermission::R;
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let mut memory_set = inner.memory_set.lock();
+    match memory_set.mprotect(start, len, map_perm) {
+        0 => Ok(0),
+        // 范围内有未映射的页
+        _ => Err(Errno::ENOMEM),
+    }
+}
+/// 写回共享文件映射，写回总是同步完成，`_flags` 被忽略
+pub fn sys_msync(start: usize, len: usize, _flags: u32) -> SysResult {
+    if start % PAGE_SIZE != 0 {
+        return Err(Errno::EINVAL);
+    }
+    if start.checked_add(len).map_or(true, |end| end > USER_SPACE_END) {
+        return Err(Errno::ENOMEM);
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let mut memory_set = inner.memory_set.lock();
+    match memory_set.msync(start, len) {
+        0 => Ok(0),
+        // 范围内有未映射的页
+        _ => Err(Errno::ENOMEM),
+    }
+}
+
+/// 向进程 `pid` 发送信号，`signum` 为 0 时只检查进程是否存在
+pub fn sys_kill(pid: isize, signum: usize) -> SysResult {
+    let signal = match signum {
+        0 => None,
+        signum => Some(SignalFlags::from_signum(signum).ok_or(Errno::EINVAL)?),
+    };
+    // `pid` 为 0 表示当前进程组，-1 表示除 initproc 与自己外的所有进程，
+    // 小于 -1 表示进程组 `-pid`
+    let targets = match pid {
+        pid if pid > 0 => vec![pid2task(pid as usize).ok_or(Errno::ESRCH)?],
+        0 => process_group(current_task().unwrap().inner_exclusive_access().pgid),
+        -1 => {
+            let tgid = current_task().unwrap().gettgid();
+            processes()
+                .into_iter()
+                .filter(|process| process.getpid() != tgid && !Arc::ptr_eq(process, &INITPROC))
+                .collect()
+        }
+        pid => process_group((-pid) as usize),
+    };
+    if targets.is_empty() {
+        return Err(Errno::ESRCH);
+    }
+    if let Some(signal) = signal {
+        for target in targets.iter() {
+            send_signal(target, signal);
+        }
+    }
+    Ok(0)
+}
+
+/// 设置信号 `signum` 的处理方式，`oldact` 非空时写回原来的处理方式
+pub fn sys_sigaction(signum: usize, act: *const SigAction, oldact: *mut SigAction) -> SysResult {
+    let signal = SignalFlags::from_signum(signum).ok_or(Errno::EINVAL)?;
+    let action = if act.is_null() {
+        None
+    } else {
+        // SIGKILL 与 SIGSTOP 的处理方式不能修改
+        if SignalFlags::unmaskable().contains(signal) {
+            return Err(Errno::EINVAL);
+        }
+        let mut action = copy_from_user(act)?;
+        action.mask = SignalFlags::from_bits_truncate(action.mask.bits()) - SignalFlags::unmaskable();
+        Some(action)
+    };
     let task = current_task().unwrap();
-    task.munmap(start, len)
-}
\ No newline at end of file
+    let mut inner = task.inner_exclusive_access();
+    let old = inner.signal_actions[signum];
+    if let Some(action) = action {
+        inner.signal_actions[signum] = action;
+        if action.handler == SIG_IGN {
+            // 已经挂起的信号也被忽略
+            inner.signals -= signal;
+        }
+    }
+    drop(inner);
+    if !oldact.is_null() {
+        copy_to_user(oldact, &old)?;
+    }
+    Ok(0)
+}
+
+/// `sigprocmask` 的 `how`
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+/// 修改被阻塞的信号集合，`oldset` 非空时写回原来的集合
+pub fn sys_sigprocmask(how: usize, set: *const u64, oldset: *mut u64) -> SysResult {
+    let set = if set.is_null() {
+        None
+    } else {
+        Some(SignalFlags::from_bits_truncate(copy_from_user(set)?) - SignalFlags::unmaskable())
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old = inner.signal_mask;
+    if let Some(set) = set {
+        match how {
+            SIG_BLOCK => inner.signal_mask |= set,
+            SIG_UNBLOCK => inner.signal_mask -= set,
+            SIG_SETMASK => inner.signal_mask = set,
+            _ => return Err(Errno::EINVAL),
+        }
+    }
+    drop(inner);
+    if !oldset.is_null() {
+        copy_to_user(oldset, &old.bits())?;
+    }
+    Ok(0)
+}
+
+/// 从信号处理函数返回，恢复被打断时的上下文
+pub fn sys_sigreturn() -> SysResult {
+    // 返回被打断时的 a0，可能是负数
+    Ok(sigreturn_current())
+}
@@ -0,0 +1,57 @@
+//! Synchronization-related syscalls
+use crate::mm::{copy_from_user, MapPermission, VirtAddr};
+use crate::sync::{futex_wait, futex_wake, FutexWaitResult};
+use crate::task::current_task;
+use super::{Errno, SysResult};
+use crate::timer::{get_time_ms, TimeSpec};
+use core::mem::size_of;
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+/// 只在进程内使用的 futex，按物理地址索引时无需区分
+const FUTEX_PRIVATE_FLAG: usize = 128;
+/// 超时按 CLOCK_REALTIME 计算，这里与单调时钟相同
+const FUTEX_CLOCK_REALTIME: usize = 256;
+
+/// 返回 futex 字 `uaddr` 的物理地址
+fn futex_pa(uaddr: usize) -> Result<usize, Errno> {
+    if uaddr % size_of::<u32>() != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let mut memory_set = inner.memory_set.lock();
+    // 写时复制的私有页先复制，否则 fork 出的进程会共用同一个物理地址
+    if memory_set.check_user_range(uaddr, size_of::<u32>(), MapPermission::W).is_err() {
+        memory_set.check_user_range(uaddr, size_of::<u32>(), MapPermission::R)?;
+    }
+    memory_set.translate_va(VirtAddr::from(uaddr)).map(|pa| pa.0).ok_or(Errno::EFAULT)
+}
+
+/// `FUTEX_WAIT`：`*uaddr == val` 时阻塞，直到被 `FUTEX_WAKE` 唤醒或超时；
+/// `FUTEX_WAKE`：唤醒至多 `val` 个等待的任务，返回唤醒的个数
+pub fn sys_futex(uaddr: *const u32, op: usize, val: usize, timeout: *const TimeSpec) -> SysResult {
+    let pa = futex_pa(uaddr as usize)?;
+    match op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME) {
+        FUTEX_WAIT => {
+            let expire_ms = if timeout.is_null() {
+                None
+            } else {
+                let timeout = copy_from_user(timeout)?;
+                if !timeout.is_valid() {
+                    return Err(Errno::EINVAL);
+                }
+                Some(get_time_ms() + timeout.as_ms())
+            };
+            // futex 字由 futex_wait 在入队之后检查，其他 hart 上的唤醒不会丢失
+            match futex_wait(pa, val as u32, expire_ms) {
+                FutexWaitResult::Woken => Ok(0),
+                FutexWaitResult::ValueChanged => Err(Errno::EAGAIN),
+                FutexWaitResult::TimedOut => Err(Errno::ETIMEDOUT),
+                FutexWaitResult::Interrupted => Err(Errno::EINTR),
+            }
+        }
+        FUTEX_WAKE => Ok(futex_wake(pa, val) as isize),
+        _ => Err(Errno::ENOSYS),
+    }
+}
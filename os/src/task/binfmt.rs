@@ -0,0 +1,103 @@
+//! 可执行文件格式的识别：ELF 映像与 `#!` 脚本
+
+use crate::config::{ELF_INTERP_BASE, PAGE_SIZE, USER_SPACE_END};
+use crate::mm::MemorySet;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::mem::size_of;
+use xmas_elf::header::Machine;
+use xmas_elf::program::{ProgramHeader64, Type};
+use xmas_elf::ElfFile;
+
+/// nested `#!` interpreters followed by `execve` before it gives up
+pub const BINFMT_MAX_DEPTH: usize = 4;
+/// only the first line up to this length is read as the `#!` line
+const BINPRM_BUF_SIZE: usize = 256;
+
+/// How a file is executed
+pub enum Binfmt {
+    /// an ELF image, started through its dynamic linker if it names one
+    Elf { interp: Option<String> },
+    /// a script run by `interp`, with at most one optional argument
+    Script { interp: String, arg: Option<String> },
+}
+
+/// Find out the format of `data`, `None` if it can't be executed
+pub fn probe(data: &[u8]) -> Option<Binfmt> {
+    if data.starts_with(b"#!") {
+        return probe_script(&data[2..]);
+    }
+    let elf = ElfFile::new(data).ok()?;
+    if elf.header.pt1.magic != [0x7f, 0x45, 0x4c, 0x46]
+        || elf.header.pt1.class() != xmas_elf::header::Class::SixtyFour
+        || !elf_loadable(&elf, data)
+    {
+        return None;
+    }
+    match elf.header.pt2.type_().as_type() {
+        xmas_elf::header::Type::Executable | xmas_elf::header::Type::SharedObject => Some(Binfmt::Elf {
+            interp: MemorySet::elf_interp(data),
+        }),
+        _ => None,
+    }
+}
+
+/// Whether `elf` is for RISC-V, its program headers and segments lie
+/// within `data` and no two PT_LOAD segments share a page, so that loading
+/// it can't go wrong
+fn elf_loadable(elf: &ElfFile, data: &[u8]) -> bool {
+    let pt2 = &elf.header.pt2;
+    if pt2.machine().as_machine() != Machine::RISC_V || pt2.ph_entry_size() as usize != size_of::<ProgramHeader64>() {
+        return false;
+    }
+    let within = |offset: u64, len: u64| offset.checked_add(len).map_or(false, |end| end <= data.len() as u64);
+    if !within(pt2.ph_offset(), pt2.ph_count() as u64 * pt2.ph_entry_size() as u64) {
+        return false;
+    }
+    // 可执行文件与动态链接器的段都要落在用户空间中
+    let vaddr_max = (USER_SPACE_END - ELF_INTERP_BASE) as u64;
+    // 各 PT_LOAD 段占用的页
+    let mut pages = Vec::new();
+    let headers_valid = (0..pt2.ph_count()).all(|i| {
+        let ph = match elf.program_header(i) {
+            Ok(ph) => ph,
+            Err(_) => return false,
+        };
+        match ph.get_type() {
+            Ok(Type::Load) => {
+                let valid = within(ph.offset(), ph.file_size())
+                    && ph.file_size() <= ph.mem_size()
+                    && ph.virtual_addr().checked_add(ph.mem_size()).map_or(false, |end| end <= vaddr_max);
+                if valid && ph.mem_size() > 0 {
+                    let page_size = PAGE_SIZE as u64;
+                    let end = ph.virtual_addr() + ph.mem_size();
+                    pages.push((ph.virtual_addr() / page_size, (end + page_size - 1) / page_size));
+                }
+                valid
+            }
+            Ok(Type::Interp) | Ok(Type::Dynamic) => within(ph.offset(), ph.file_size()),
+            Ok(_) => true,
+            Err(_) => false,
+        }
+    });
+    pages.sort_unstable();
+    headers_valid && pages.windows(2).all(|pair| pair[0].1 <= pair[1].0)
+}
+
+/// `#!interp [arg]`, everything after the interpreter is a single argument
+fn probe_script(data: &[u8]) -> Option<Binfmt> {
+    let line = &data[..data.len().min(BINPRM_BUF_SIZE - 2)];
+    let line = line.split(|byte| *byte == b'\n').next()?;
+    let line = core::str::from_utf8(line).ok()?.trim_matches(|c| c == ' ' || c == '\t');
+    let (interp, arg) = match line.find(|c| c == ' ' || c == '\t') {
+        Some(i) => (&line[..i], line[i..].trim_matches(|c| c == ' ' || c == '\t')),
+        None => (line, ""),
+    };
+    if interp.is_empty() {
+        return None;
+    }
+    Some(Binfmt::Script {
+        interp: interp.to_string(),
+        arg: if arg.is_empty() { None } else { Some(arg.to_string()) },
+    })
+}
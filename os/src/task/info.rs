@@ -1,4 +1,4 @@
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 /// # 系统信息模块
 /// `os/src/task/info.rs`
 /// ```
@@ -20,7 +20,7 @@ pub struct Utsname {
 }
 
 lazy_static! {
-    pub static ref UTSNAME: UPSafeCell<Utsname> = unsafe {UPSafeCell::new(Utsname::new())};
+    pub static ref UTSNAME: SpinLock<Utsname> = SpinLock::new(Utsname::new());
 }
 
 impl Utsname {
@@ -54,6 +54,15 @@ impl Utsname {
 bitflags!{
     pub struct CloneFlags: usize{
         const SIGCHLD = 17;
+        /// 共享地址空间
+        const CLONE_VM              = 0x0000_0100;
+        /// 共享文件描述符表
+        const CLONE_FILES           = 0x0000_0400;
+        /// 加入调用者的线程组
+        const CLONE_THREAD          = 0x0001_0000;
+        /// 新任务的 tp 寄存器设为参数 tls
+        const CLONE_SETTLS          = 0x0008_0000;
+        const CLONE_PARENT_SETTID   = 0x0010_0000;
         const CLONE_CHILD_CLEARTID  = 0x0020_0000;
         const CLONE_CHILD_SETTID    = 0x0100_0000;
     }
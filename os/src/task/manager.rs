@@ -1,41 +1,247 @@
 //!Implementation of [`TaskManager`]
 use super::TaskControlBlock;
-use crate::sync::UPSafeCell;
-use alloc::collections::VecDeque;
+use crate::sync::SpinLock;
+use crate::timer::get_time_ms;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
+/// Scheduling accounting of a task, kept in `TaskControlBlockInner`
+#[derive(Copy, Clone)]
+pub struct SchedInfo {
+    /// nice value set by `setpriority`, from -20 (highest priority) to 19
+    pub nice: isize,
+    /// stride: virtual time consumed so far
+    pub pass: u64,
+    /// MLFQ: the queue the task is in, 0 is the highest
+    pub level: usize,
+    /// used up its time slice instead of yielding or blocking, set on timer interrupts
+    pub preempted: bool,
+}
+
+impl SchedInfo {
+    /// Accounting of a new task
+    pub fn new() -> Self {
+        Self {
+            nice: 0,
+            pass: 0,
+            level: 0,
+            preempted: false,
+        }
+    }
+}
+
+/// Scheduling policy of the ready tasks
+pub trait Scheduler {
+    /// A task becomes ready to run
+    fn push(&mut self, task: Arc<TaskControlBlock>);
+    /// Pick the next task to run, or `None` if no task is ready
+    fn pop(&mut self) -> Option<Arc<TaskControlBlock>>;
+}
+
+/// Run the tasks in the order they become ready, ignoring their priority
+pub struct FifoScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl FifoScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for FifoScheduler {
+    fn push(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    fn pop(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+}
+
+/// Stride of a task with nice 0 is `BIG_STRIDE / 20`
+const BIG_STRIDE: u64 = 1 << 20;
+
+/// Run the task with the smallest pass. Every time a task is picked, its
+/// pass grows by a stride inversely proportional to its weight `20 - nice`,
+/// so CPU time is shared in proportion to the weights.
+pub struct StrideScheduler {
+    /// ready tasks by (pass, arrival), the arrival keeps equal passes in FIFO order
+    ready_queue: BTreeMap<(u64, usize), Arc<TaskControlBlock>>,
+    arrival: usize,
+    /// pass of the last task picked
+    min_pass: u64,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: BTreeMap::new(),
+            arrival: 0,
+            min_pass: 0,
+        }
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn push(&mut self, task: Arc<TaskControlBlock>) {
+        let mut inner = task.inner_exclusive_access();
+        // a task back from a long sleep does not get the time it missed
+        inner.sched.pass = inner.sched.pass.max(self.min_pass);
+        let pass = inner.sched.pass;
+        drop(inner);
+        self.ready_queue.insert((pass, self.arrival), task);
+        self.arrival += 1;
+    }
+    fn pop(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let key = *self.ready_queue.keys().next()?;
+        let task = self.ready_queue.remove(&key).unwrap();
+        let mut inner = task.inner_exclusive_access();
+        self.min_pass = inner.sched.pass;
+        let weight = (20 - inner.sched.nice) as u64;
+        inner.sched.pass += BIG_STRIDE / weight;
+        drop(inner);
+        Some(task)
+    }
+}
+
+/// Levels of the MLFQ scheduler
+const MLFQ_LEVELS: usize = 4;
+/// Every task goes back to its highest level this often, so that CPU hogs
+/// in the lowest level are not starved
+const MLFQ_BOOST_MS: usize = 1000;
+
+/// Multilevel feedback queue. A task using up its time slice moves one
+/// level down, a task yielding or blocking before that keeps its level,
+/// so interactive tasks stay ahead of CPU hogs. The nice value decides the
+/// highest level a task may be in.
+pub struct MlfqScheduler {
+    ready_queues: [VecDeque<Arc<TaskControlBlock>>; MLFQ_LEVELS],
+    last_boost_ms: usize,
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queues: Default::default(),
+            last_boost_ms: 0,
+        }
+    }
+    /// Highest level allowed for a nice value
+    fn top_level(nice: isize) -> usize {
+        (nice + 20) as usize * MLFQ_LEVELS / 40
+    }
+    /// Move every ready task to its highest level
+    fn boost(&mut self) {
+        for level in 1..MLFQ_LEVELS {
+            while let Some(task) = self.ready_queues[level].pop_front() {
+                let mut inner = task.inner_exclusive_access();
+                inner.sched.level = Self::top_level(inner.sched.nice);
+                let level = inner.sched.level;
+                drop(inner);
+                self.ready_queues[level].push_back(task);
+            }
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn push(&mut self, task: Arc<TaskControlBlock>) {
+        let mut inner = task.inner_exclusive_access();
+        let sched = &mut inner.sched;
+        if sched.preempted {
+            sched.preempted = false;
+            sched.level = (sched.level + 1).min(MLFQ_LEVELS - 1);
+        }
+        sched.level = sched.level.max(Self::top_level(sched.nice));
+        let level = sched.level;
+        drop(inner);
+        self.ready_queues[level].push_back(task);
+    }
+    fn pop(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let current_ms = get_time_ms();
+        if current_ms - self.last_boost_ms >= MLFQ_BOOST_MS {
+            self.last_boost_ms = current_ms;
+            self.boost();
+        }
+        self.ready_queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+}
+
+/// The scheduler chosen at build time by the `sched_stride` or `sched_mlfq`
+/// feature, FIFO by default
+#[cfg(feature = "sched_stride")]
+type SchedulerImpl = StrideScheduler;
+#[cfg(all(feature = "sched_mlfq", not(feature = "sched_stride")))]
+type SchedulerImpl = MlfqScheduler;
+#[cfg(not(any(feature = "sched_stride", feature = "sched_mlfq")))]
+type SchedulerImpl = FifoScheduler;
+
 ///A array of `TaskControlBlock` that is thread-safe
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    scheduler: SchedulerImpl,
 }
 
-/// A simple FIFO scheduler.
 impl TaskManager {
     ///Creat an empty TaskManager
     pub fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            scheduler: SchedulerImpl::new(),
         }
     }
     ///Add a task to `TaskManager`
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+        self.scheduler.push(task);
     }
-    ///Remove the first task and return it,or `None` if `TaskManager` is empty
+    ///Remove the next task to run and return it,or `None` if `TaskManager` is empty
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
+        self.scheduler.pop()
     }
 }
 
 lazy_static! {
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
+    pub static ref TASK_MANAGER: SpinLock<TaskManager> =
+        SpinLock::new(TaskManager::new());
+    /// Living tasks by pid, used to deliver signals
+    pub static ref PID2TCB: SpinLock<BTreeMap<usize, Arc<TaskControlBlock>>> =
+        SpinLock::new(BTreeMap::new());
 }
-///Interface offered to add task
+///Interface offered to add task, the caller must not hold the inner of `task`
 pub fn add_task(task: Arc<TaskControlBlock>) {
-    TASK_MANAGER.exclusive_access().add(task);
+    TASK_MANAGER.lock().add(task);
+}
+///Find a task which has not exited by pid
+pub fn pid2task(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    PID2TCB.lock().get(&pid).cloned()
+}
+///Register a new task
+pub fn insert_into_pid2task(pid: usize, task: Arc<TaskControlBlock>) {
+    PID2TCB.lock().insert(pid, task);
+}
+///Living tasks of the thread group `tgid`
+pub fn thread_group(tgid: usize) -> Vec<Arc<TaskControlBlock>> {
+    let tasks: Vec<_> = PID2TCB.lock().values().cloned().collect();
+    tasks.into_iter().filter(|task| task.gettgid() == tgid).collect()
+}
+///Living processes, that is the leaders of the thread groups
+pub fn processes() -> Vec<Arc<TaskControlBlock>> {
+    let tasks: Vec<_> = PID2TCB.lock().values().cloned().collect();
+    tasks.into_iter().filter(|task| task.gettgid() == task.getpid()).collect()
+}
+///Living processes of the process group `pgid`
+pub fn process_group(pgid: usize) -> Vec<Arc<TaskControlBlock>> {
+    processes()
+        .into_iter()
+        .filter(|task| task.inner_exclusive_access().pgid == pgid)
+        .collect()
+}
+///Forget a task when it exits
+pub fn remove_from_pid2task(pid: usize) {
+    PID2TCB.lock().remove(&pid);
 }
 ///Interface offered to pop the first task
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
-    TASK_MANAGER.exclusive_access().fetch()
+    TASK_MANAGER.lock().fetch()
 }
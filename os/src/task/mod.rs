@@ -6,20 +6,23 @@
 //! A single global instance of [`TaskManager`] called `TASK_MANAGER` controls
 //! all the tasks in the whole operating system.
 //!
-//! A single global instance of [`Processor`] called `PROCESSOR` monitors running
-//! task(s) for each core.
+//! One instance of [`Processor`] for each hart in `PROCESSORS` monitors the
+//! task running on that hart.
 //!
 //! A single global instance of [`PidAllocator`] called `PID_ALLOCATOR` allocates
 //! pid for user apps.
 //!
 //! Be careful when you see `__switch` ASM function in `switch.S`. Control flow around this function
 //! might not be what you expect.
+mod binfmt;
 mod context;
 mod manager;
 mod pid;
 mod processor;
 mod switch;
 mod info;
+mod signal;
+mod rlimit;
 #[allow(clippy::module_inception)]
 #[allow(rustdoc::private_intra_doc_links)]
 mod task;
@@ -27,19 +30,33 @@ use log::{info, warn};
 
 use crate::console::print;
 use crate::fs::{open_file, OpenFlags};
+use crate::mm::VirtAddr;
 use crate::sbi::shutdown;
+use crate::sync::{futex_wake, SpinLock};
+use crate::timer::{remove_timer, CpuTimes};
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+pub use binfmt::{probe, Binfmt, BINFMT_MAX_DEPTH};
 pub use context::TaskContext;
 use lazy_static::*;
-pub use manager::{fetch_task, TaskManager};
+pub use manager::{
+    fetch_task, FifoScheduler, MlfqScheduler, SchedInfo, Scheduler, StrideScheduler, TaskManager,
+};
 use switch::__switch;
-use task::{TaskControlBlock, TaskStatus};
+pub use task::{ExecImage, TaskControlBlock, TaskStatus};
 pub use info::*;
 
-pub use manager::add_task;
+pub use manager::{
+    add_task, insert_into_pid2task, pid2task, process_group, processes, remove_from_pid2task, thread_group,
+};
+pub use signal::*;
+pub use rlimit::{RLimit, RLimits, RLIM_INFINITY, RLIM_NLIMITS, RLIMIT_AS, RLIMIT_DATA, RLIMIT_NOFILE, RLIMIT_NPROC, RLIMIT_STACK};
 pub use pid::{pid_alloc, KernelStack, PidAllocator, PidHandle};
 pub use processor::{
-    current_task, current_trap_cx, current_user_token, run_tasks, schedule, take_current_task,
+    current_task, current_trap_cx, current_trap_cx_user_va, current_user_token, hart_id, run_tasks, schedule,
+    take_current_task,
     Processor,
 };
 /// Suspend the current 'Running' task and run the next task in task list.
@@ -50,68 +67,245 @@ pub fn suspend_current_and_run_next() {
     // ---- access current TCB exclusively
     let mut task_inner = task.inner_exclusive_access();
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
-    // Change status to Ready
-    task_inner.task_status = TaskStatus::Ready;
     drop(task_inner);
     // ---- release current PCB
 
-    // push back to ready queue.
-    add_task(task);
-    // jump to scheduling cycle
-    schedule(task_cx_ptr);
+    // pushed back to ready queue once it has left its kernel stack
+    schedule(task, TaskStatus::Ready, task_cx_ptr);
 }
 
-/// pid of usertests app in make run TEST=1
-pub const IDLE_PID: usize = 0;
+/// Block the current 'Running' task until `wakeup_task` is called on it,
+/// and run the next task in task list.
+pub fn block_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    drop(task_inner);
+    // the task is kept alive by whoever is going to wake it up
+    schedule(task, TaskStatus::Blocked, task_cx_ptr);
+}
+
+/// Move a 'Blocked' task back to the ready queue.
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    match task_inner.task_status {
+        TaskStatus::Blocked => {
+            task_inner.task_status = TaskStatus::Ready;
+            drop(task_inner);
+            add_task(task);
+        }
+        // it may be on its way to block on another hart, do not let it sleep
+        TaskStatus::Running => task_inner.wakeup_pending = true,
+        // already woken up for another reason
+        _ => {}
+    }
+}
+
+/// Put a task which has just switched to the idle control flow in `status`.
+/// Called by `run_tasks` once no hart runs on the kernel stack of the task.
+fn finish_switch(task: Arc<TaskControlBlock>, status: TaskStatus) {
+    let mut task_inner = task.inner_exclusive_access();
+    match status {
+        TaskStatus::Blocked if !task_inner.wakeup_pending => {
+            task_inner.task_status = TaskStatus::Blocked;
+        }
+        TaskStatus::Ready | TaskStatus::Blocked => {
+            task_inner.wakeup_pending = false;
+            task_inner.task_status = TaskStatus::Ready;
+            drop(task_inner);
+            add_task(task);
+        }
+        TaskStatus::Zombie => {
+            task_inner.task_status = TaskStatus::Zombie;
+            let is_thread = task_inner.tgid != task.getpid();
+            let parent = task_inner.parent.as_ref().and_then(|parent| parent.upgrade());
+            drop(task_inner);
+            // wake up the parent blocked in wait4, nobody waits for a thread
+            if let Some(parent) = parent.filter(|_| !is_thread) {
+                send_signal(&parent, SignalFlags::SIGCHLD);
+                parent.child_exit.wake_all();
+            }
+        }
+        TaskStatus::Running => unreachable!(),
+    }
+}
 
 /// Exit the current 'Running' task and run the next task in task list.
 pub fn exit_current_and_run_next(exit_code: i32) {
+    exit_current_with_status((exit_code & 0xff) << 8);
+}
+
+/// Exit all the threads of the current process and run the next task in task list.
+pub fn exit_group_and_run_next(exit_code: i32) {
+    exit_group_with_status((exit_code & 0xff) << 8);
+}
+
+/// Terminate the current process by signal `signum` and run the next task in
+/// task list, a fatal signal ends all of its threads.
+pub fn kill_current_and_run_next(signum: usize) {
+    exit_group_with_status(signum as i32);
+}
+
+/// Exit the current task after telling the other threads of its process to
+/// exit, `exit_status` is what the parent gets from `wait4`.
+fn exit_group_with_status(exit_status: i32) {
+    let task = current_task().unwrap();
+    for thread in thread_group(task.gettgid()) {
+        if Arc::ptr_eq(&thread, &task) {
+            continue;
+        }
+        let mut inner = thread.inner_exclusive_access();
+        // it exits in handle_signals, SIGKILL interrupts what it waits for
+        inner.group_exit_status = Some(exit_status);
+        inner.signals |= SignalFlags::SIGKILL;
+        drop(inner);
+        wakeup_task(thread);
+    }
+    drop(task);
+    exit_current_with_status(exit_status);
+}
+
+/// Run `image` as the program of the current process once its other threads
+/// are gone. Called by a thread other than the leader, the leader is given
+/// the image to run and the caller exits, the process keeps its pid.
+pub fn exec_current(image: ExecImage) {
+    let task = current_task().unwrap();
+    let tgid = task.gettgid();
+    let leader = pid2task(tgid).filter(|leader| !Arc::ptr_eq(leader, &task));
+    // the new program must not run alongside the old one, wait for the
+    // threads to exit, including those they are just creating
+    loop {
+        let others: Vec<_> = thread_group(tgid)
+            .into_iter()
+            .filter(|thread| !Arc::ptr_eq(thread, &task))
+            .filter(|thread| leader.as_ref().map_or(true, |leader| !Arc::ptr_eq(thread, leader)))
+            .collect();
+        if others.is_empty() {
+            break;
+        }
+        if task.inner_exclusive_access().group_exit_status.is_some() {
+            // killed by another thread meanwhile, exits on the way to user mode
+            return;
+        }
+        for thread in others {
+            let mut inner = thread.inner_exclusive_access();
+            inner.group_exit_status = Some(0);
+            inner.signals |= SignalFlags::SIGKILL;
+            drop(inner);
+            wakeup_task(thread);
+        }
+        suspend_current_and_run_next();
+    }
+    match leader {
+        Some(leader) => {
+            // it installs the image in handle_signals
+            let mut inner = leader.inner_exclusive_access();
+            inner.exec_image = Some(image);
+            inner.signals |= SignalFlags::SIGKILL;
+            drop(inner);
+            wakeup_task(leader);
+            drop(task);
+            exit_current_with_status(0);
+        }
+        None => task.install_image(image),
+    }
+}
+
+/// pid of initproc, which is usertests in make run TEST=1
+pub const IDLE_PID: usize = 0;
+
+/// Exit the current task, `exit_status` is what its parent gets from `wait4`.
+fn exit_current_with_status(exit_status: i32) {
     // take from Processor
     let task = take_current_task().unwrap();
 
     let pid = task.getpid();
     if pid == IDLE_PID {
-        println!(
-            "[kernel] Idle process exit with exit_code {} ...",
-            exit_code
-        );
-        if exit_code != 0 {
-            //crate::sbi::shutdown(255); //255 == -1 for err hint
-            shutdown(true)
-        } else {
-            //crate::sbi::shutdown(0); //0 for success hint
-            shutdown(false)
-        }
+        // usertests 作为 initproc 运行时，退出即测试结束
+        println!("[kernel] Idle process exit with exit_status {:#x} ...", exit_status);
+        shutdown(exit_status != 0);
     }
+    remove_from_pid2task(pid);
+    // a task killed while sleeping leaves its timer behind
+    remove_timer(&task);
 
     // **** access current TCB exclusively
     let mut inner = task.inner_exclusive_access();
-    // Change status to Zombie
-    inner.task_status = TaskStatus::Zombie;
-    // Record exit code
-    inner.exit_code = exit_code;
-    // do not move to its parent but under initproc
-
-    // ++++++ access initproc TCB exclusively
-    {
-        let mut initproc_inner = INITPROC.inner_exclusive_access();
-        for child in inner.children.iter() {
-            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-            initproc_inner.children.push(child.clone());
+    // Record exit status
+    inner.exit_status = exit_status;
+    let (cpu_times, group_leader) = (inner.cpu_times, inner.group_leader.take());
+    let children = core::mem::take(&mut inner.children);
+    // tell the threads joining this one that it is gone
+    let mut clear_child_tid_pa = None;
+    if inner.clear_child_tid != 0 {
+        let clear_child_tid = inner.clear_child_tid;
+        let mut memory_set = inner.memory_set.lock();
+        if memory_set.copy_to_user(clear_child_tid, &0u32.to_ne_bytes()).is_ok() {
+            clear_child_tid_pa = memory_set.translate_va(VirtAddr::from(clear_child_tid));
         }
     }
-    // ++++++ release parent PCB
-
-    inner.children.clear();
-    // deallocate user space
-    inner.memory_set.recycle_data_pages();
+    if Arc::strong_count(&inner.memory_set) == 1 {
+        // deallocate user space
+        inner.memory_set.lock().recycle_data_pages();
+    } else {
+        // the other threads are still running in it
+        let trap_cx_va = inner.trap_cx_va;
+        inner.memory_set.lock().dealloc_trap_cx(trap_cx_va);
+    }
+    // close the files now rather than when the zombie is reaped, a pipe
+    // reader is waiting for its writers to go away. Threads sharing the
+    // table keep it open.
+    let fd_table = core::mem::replace(&mut inner.fd_table, Arc::new(SpinLock::new(Vec::new())));
     drop(inner);
     // **** release current PCB
-    // drop task manually to maintain rc correctly
-    drop(task);
-    // we do not have to save task context
+    drop(fd_table);
+
+    // do not move to its parent but under initproc, a child is locked
+    // without holding its parent so that the lock order stays parent first
+    if !children.is_empty() {
+        for child in children.iter() {
+            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
+        }
+        INITPROC.inner_exclusive_access().children.extend(children);
+        // some of them may be zombies already
+        INITPROC.child_exit.wake_all();
+    }
+    if let Some(pa) = clear_child_tid_pa {
+        futex_wake(pa.0, 1);
+    }
+    if let Some(leader) = group_leader {
+        // the time of an exited thread still counts for its process
+        let mut leader_inner = leader.inner_exclusive_access();
+        leader_inner.cpu_times.add_thread(&cpu_times);
+        leader_inner.live_threads -= 1;
+        // the parent waits for the last thread before it reaps the process
+        if leader_inner.live_threads == 0 && leader_inner.is_zombie() {
+            let parent = leader_inner.parent.as_ref().and_then(|parent| parent.upgrade());
+            drop(leader_inner);
+            if let Some(parent) = parent {
+                parent.child_exit.wake_all();
+            }
+        }
+    }
+    // we do not have to save task context, the task becomes a zombie and
+    // its parent is woken up once it has left its kernel stack
     let mut _unused = TaskContext::zero_init();
-    schedule(&mut _unused as *mut _);
+    schedule(task, TaskStatus::Zombie, &mut _unused as *mut _);
+}
+
+/// CPU times of the process `tgid`, summed over its living threads
+pub fn process_cpu_times(tgid: usize) -> CpuTimes {
+    let mut times = CpuTimes::new();
+    for thread in thread_group(tgid) {
+        let inner = thread.inner_exclusive_access();
+        times.add_thread(&inner.cpu_times);
+        if thread.getpid() == tgid {
+            // reaped children belong to the process, kept by its first thread
+            times.cutime = inner.cpu_times.cutime;
+            times.cstime = inner.cpu_times.cstime;
+        }
+    }
+    times
 }
 
 lazy_static! {
@@ -124,11 +318,14 @@ lazy_static! {
         OpenFlags::RDONLY,
         crate::fs::DiskInodeType::File,).unwrap();
         let v = inode.read_all();
-        TaskControlBlock::new(v.as_slice())
+        let task = TaskControlBlock::new(v.as_slice());
+        task.inner_exclusive_access().cmdline = vec![String::from("initproc")];
+        task
     });
 }
 ///Add init process to the manager
 pub fn add_initproc() {
+    insert_into_pid2task(INITPROC.getpid(), INITPROC.clone());
     add_task(INITPROC.clone());
 }
 
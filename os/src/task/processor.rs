@@ -1,18 +1,23 @@
 //!Implementation of [`Processor`] and Intersection of control flow
 use super::__switch;
-use super::{fetch_task, TaskStatus};
+use super::{fetch_task, finish_switch, TaskStatus};
 use super::{TaskContext, TaskControlBlock};
-use crate::sync::UPSafeCell;
+use crate::config::MAX_HARTS;
+use crate::sync::{SpinLock, SpinLockGuard};
+use crate::timer::check_timer;
 use crate::trap::TrapContext;
 use alloc::sync::Arc;
+use core::arch::asm;
 use lazy_static::*;
-use log::info;
 ///Processor management structure
 pub struct Processor {
     ///The task currently executing on the current processor
     current: Option<Arc<TaskControlBlock>>,
     ///The basic control flow of each core, helping to select and switch process
     idle_task_cx: TaskContext,
+    ///The task which has just switched to the idle control flow and the status
+    ///it leaves with, see `finish_switch`
+    switched_out: Option<(Arc<TaskControlBlock>, TaskStatus)>,
 }
 
 impl Processor {
@@ -21,6 +26,7 @@ impl Processor {
         Self {
             current: None,
             idle_task_cx: TaskContext::zero_init(),
+            switched_out: None,
         }
     }
     ///Get mutable reference to `idle_task_cx`
@@ -38,38 +44,69 @@ impl Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    ///One processor for each hart, indexed by `hart_id`
+    pub static ref PROCESSORS: [SpinLock<Processor>; MAX_HARTS] =
+        core::array::from_fn(|_| SpinLock::new(Processor::new()));
 }
+
+///Id of the hart running this code, kept in `tp` while in the kernel
+pub fn hart_id() -> usize {
+    let hart_id;
+    unsafe {
+        asm!("mv {}, tp", out(reg) hart_id);
+    }
+    hart_id
+}
+
+///Processor of the current hart
+fn processor() -> SpinLockGuard<'static, Processor> {
+    PROCESSORS[hart_id()].lock()
+}
+
 ///The main part of process execution and scheduling
 ///Loop `fetch_task` to get the process that needs to run, and switcnh the process through `__switch`
-pub fn run_tasks() {
-    let mut count = 0;
+pub fn run_tasks() -> ! {
     loop {
-        let mut processor = PROCESSOR.exclusive_access();     
+        let mut processor = processor();
         if let Some(task) = fetch_task() {
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // access coming task TCB exclusively
             let mut task_inner = task.inner_exclusive_access();
             let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
             task_inner.task_status = TaskStatus::Running;
+            task_inner.cpu_times.scheduled();
             drop(task_inner);
             // release coming task TCB manually
             processor.current = Some(task);
             // release processor manually
             drop(processor);
             unsafe {
+                // the kernel stack of the task may have been mapped again
+                // since this hart last used that address
+                asm!("sfence.vma");
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            // nobody runs on the kernel stack of the switched out task now,
+            // so other harts may pick it up
+            let switched_out = self::processor().switched_out.take();
+            if let Some((task, status)) = switched_out {
+                finish_switch(task, status);
+            }
+        } else {
+            // timer interrupts are not taken in the kernel, so poll the
+            // sleeping tasks while nothing else can run
+            drop(processor);
+            check_timer();
         }
     }
 }
 ///Take the current task,leaving a None in its place
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    processor().take_current()
 }
 ///Get running task
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    processor().current()
 }
 ///Get token of the address space of current task
 pub fn current_user_token() -> usize {
@@ -77,6 +114,10 @@ pub fn current_user_token() -> usize {
     let token = task.inner_exclusive_access().get_user_token();
     token
 }
+///Get the user virtual address of trap context of current task
+pub fn current_trap_cx_user_va() -> usize {
+    current_task().unwrap().inner_exclusive_access().trap_cx_va
+}
 ///Get the mutable reference to trap context of current task
 pub fn current_trap_cx() -> &'static mut TrapContext {
     current_task()
@@ -84,9 +125,17 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
         .inner_exclusive_access()
         .get_trap_cx()
 }
-///Return to idle control flow for new scheduling
-pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
-    let mut processor = PROCESSOR.exclusive_access();
+///Return to idle control flow for new scheduling, which then puts `task`
+///in `status` once it has left its kernel stack
+pub fn schedule(
+    task: Arc<TaskControlBlock>,
+    status: TaskStatus,
+    switched_task_cx_ptr: *mut TaskContext,
+) {
+    // the time waiting for a CPU is not system time
+    task.inner_exclusive_access().cpu_times.leave_kernel();
+    let mut processor = processor();
+    processor.switched_out = Some((task, status));
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     drop(processor);
     unsafe {
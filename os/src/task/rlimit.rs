@@ -0,0 +1,65 @@
+//! 进程的资源限制，由 `prlimit64`/`getrlimit`/`setrlimit` 读写，fork 时继承
+
+use crate::config::{USER_STACK_MAX, USER_STACK_SIZE};
+use crate::syscall::Errno;
+
+/// size of the heap (the program break), in bytes
+pub const RLIMIT_DATA: usize = 2;
+/// size of the user stack mapped by `execve`, in bytes
+pub const RLIMIT_STACK: usize = 3;
+/// number of processes
+pub const RLIMIT_NPROC: usize = 6;
+/// one more than the largest file descriptor
+pub const RLIMIT_NOFILE: usize = 7;
+/// size of the address space, in bytes
+pub const RLIMIT_AS: usize = 9;
+/// number of the resources Linux knows, the others are not enforced
+pub const RLIM_NLIMITS: usize = 16;
+/// no limit
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+/// `struct rlimit`: the soft limit is enforced, it can be raised up to the
+/// hard limit
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+impl RLimit {
+    const fn new(cur: usize, max: usize) -> Self {
+        Self { cur, max }
+    }
+}
+
+/// The limits of a process, indexed by resource
+#[derive(Clone, Copy)]
+pub struct RLimits([RLimit; RLIM_NLIMITS]);
+
+impl RLimits {
+    /// Limits of the first process
+    pub fn new() -> Self {
+        let mut limits = [RLimit::new(RLIM_INFINITY, RLIM_INFINITY); RLIM_NLIMITS];
+        // the stack is allocated when the program starts, not on demand
+        limits[RLIMIT_STACK] = RLimit::new(USER_STACK_SIZE, USER_STACK_MAX);
+        limits[RLIMIT_NOFILE] = RLimit::new(1024, 4096);
+        Self(limits)
+    }
+    /// The limit of `resource`, which must be below `RLIM_NLIMITS`
+    pub fn get(&self, resource: usize) -> RLimit {
+        self.0[resource]
+    }
+    /// Change the limit of `resource`. No process is privileged, so the hard
+    /// limit can only be lowered.
+    pub fn set(&mut self, resource: usize, limit: RLimit) -> Result<(), Errno> {
+        if limit.cur > limit.max {
+            return Err(Errno::EINVAL);
+        }
+        if limit.max > self.0[resource].max {
+            return Err(Errno::EPERM);
+        }
+        self.0[resource] = limit;
+        Ok(())
+    }
+}
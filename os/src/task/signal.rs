@@ -0,0 +1,324 @@
+//! POSIX signals: actions, masks and delivery to user mode
+use super::{
+    block_current_and_run_next, current_task, exit_current_with_status, kill_current_and_run_next, process_group,
+    wakeup_task,
+    TaskControlBlock, INITPROC,
+};
+use crate::config::SIGRETURN_TRAMPOLINE;
+use alloc::sync::Arc;
+use core::mem::size_of;
+
+/// Largest supported signal number, real-time signals are not supported
+pub const MAX_SIG: usize = 31;
+
+bitflags! {
+    /// Set of signals, signal `n` is bit `n - 1` as in Linux `sigset_t`
+    pub struct SignalFlags: u64 {
+        const SIGHUP    = 1 << 0;
+        const SIGINT    = 1 << 1;
+        const SIGQUIT   = 1 << 2;
+        const SIGILL    = 1 << 3;
+        const SIGTRAP   = 1 << 4;
+        const SIGABRT   = 1 << 5;
+        const SIGBUS    = 1 << 6;
+        const SIGFPE    = 1 << 7;
+        const SIGKILL   = 1 << 8;
+        const SIGUSR1   = 1 << 9;
+        const SIGSEGV   = 1 << 10;
+        const SIGUSR2   = 1 << 11;
+        const SIGPIPE   = 1 << 12;
+        const SIGALRM   = 1 << 13;
+        const SIGTERM   = 1 << 14;
+        const SIGSTKFLT = 1 << 15;
+        const SIGCHLD   = 1 << 16;
+        const SIGCONT   = 1 << 17;
+        const SIGSTOP   = 1 << 18;
+        const SIGTSTP   = 1 << 19;
+        const SIGTTIN   = 1 << 20;
+        const SIGTTOU   = 1 << 21;
+        const SIGURG    = 1 << 22;
+        const SIGXCPU   = 1 << 23;
+        const SIGXFSZ   = 1 << 24;
+        const SIGVTALRM = 1 << 25;
+        const SIGPROF   = 1 << 26;
+        const SIGWINCH  = 1 << 27;
+        const SIGIO     = 1 << 28;
+        const SIGPWR    = 1 << 29;
+        const SIGSYS    = 1 << 30;
+    }
+}
+
+impl SignalFlags {
+    /// The set containing only signal `signum`
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if signum == 0 || signum > MAX_SIG {
+            return None;
+        }
+        Self::from_bits(1 << (signum - 1))
+    }
+    /// The smallest signal number in the set
+    pub fn lowest_signum(&self) -> Option<usize> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.bits.trailing_zeros() as usize + 1)
+        }
+    }
+    /// Signals that can be neither caught, blocked nor ignored
+    pub fn unmaskable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+    /// Signals whose default action stops the process
+    pub fn stopping() -> Self {
+        Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU
+    }
+}
+
+/// Default action
+pub const SIG_DFL: usize = 0;
+/// Ignore the signal
+pub const SIG_IGN: usize = 1;
+/// Do not block the signal while its handler runs
+pub const SA_NODEFER: usize = 0x4000_0000;
+/// Restore the default action once the handler is entered
+pub const SA_RESETHAND: usize = 0x8000_0000;
+
+/// `struct sigaction` as seen by `rt_sigaction` on riscv64
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SigAction {
+    pub handler: usize,
+    pub flags: usize,
+    pub mask: SignalFlags,
+}
+
+impl SigAction {
+    pub fn new() -> Self {
+        Self {
+            handler: SIG_DFL,
+            flags: 0,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// What happens to a process receiving a signal whose action is `SIG_DFL`
+fn default_ignored(signal: SignalFlags) -> bool {
+    // SIGCONT has done its work when it was sent
+    (SignalFlags::SIGCHLD | SignalFlags::SIGURG | SignalFlags::SIGWINCH | SignalFlags::SIGCONT)
+        .contains(signal)
+}
+
+/// Saved on the user stack while a handler runs, restored by `rt_sigreturn`
+#[repr(C)]
+struct SignalFrame {
+    x: [usize; 32],
+    sepc: usize,
+    mask: SignalFlags,
+}
+
+/// Mark `signal` pending for `task`, waking it up if it is blocked
+pub fn send_signal(task: &Arc<TaskControlBlock>, signal: SignalFlags) {
+    let mut inner = task.inner_exclusive_access();
+    let signum = signal.lowest_signum().unwrap();
+    // init only receives the signals it has handlers for
+    if Arc::ptr_eq(task, &INITPROC) && inner.signal_actions[signum].handler == SIG_DFL {
+        return;
+    }
+    let mut resumed = false;
+    if signal == SignalFlags::SIGCONT {
+        // a stopped process continues when SIGCONT is sent, even if it is
+        // blocked or ignored
+        inner.signals -= SignalFlags::stopping();
+        resumed = inner.stopped;
+        inner.stopped = false;
+        inner.stop_status = None;
+    } else if SignalFlags::stopping().contains(signal) {
+        inner.signals -= SignalFlags::SIGCONT;
+    }
+    // an ignored signal is discarded right away unless it is blocked, it
+    // does not interrupt a sleeping task
+    let handler = inner.signal_actions[signum].handler;
+    let ignored = (handler == SIG_IGN || (handler == SIG_DFL && default_ignored(signal)))
+        && !inner.signal_mask.contains(signal);
+    if !ignored {
+        inner.signals |= signal;
+    }
+    drop(inner);
+    if ignored && !resumed {
+        return;
+    }
+    // interrupt sleep and wait, they check for pending signals. The task may
+    // be about to block on another hart, so it is woken up even if running.
+    wakeup_task(task.clone());
+}
+
+/// Send `signal` to every process of the group `pgid`, return false if
+/// there is none
+pub fn send_signal_to_group(pgid: usize, signal: SignalFlags) -> bool {
+    let group = process_group(pgid);
+    for task in group.iter() {
+        send_signal(task, signal);
+    }
+    !group.is_empty()
+}
+
+/// Stop the current task until SIGCONT or SIGKILL arrives, the parent
+/// learns about it from `wait4` with `WUNTRACED`
+fn stop_current(signum: usize) {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.stopped = true;
+    inner.stop_status = Some(((signum as i32) << 8) | 0x7f);
+    let parent = inner.parent.as_ref().and_then(|parent| parent.upgrade());
+    drop(inner);
+    if let Some(parent) = parent {
+        parent.child_exit.wake_all();
+    }
+    loop {
+        let inner = task.inner_exclusive_access();
+        if !inner.stopped || inner.signals.contains(SignalFlags::SIGKILL) {
+            return;
+        }
+        drop(inner);
+        // send_signal wakes us up
+        block_current_and_run_next();
+    }
+}
+
+/// Raise a signal caused by the current instruction. It cannot be blocked
+/// nor ignored, otherwise returning to the instruction would fault forever.
+pub fn force_signal_current(signal: SignalFlags) {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let signum = signal.lowest_signum().unwrap();
+    if inner.signal_mask.contains(signal) || inner.signal_actions[signum].handler == SIG_IGN {
+        inner.signal_mask -= signal;
+        inner.signal_actions[signum] = SigAction::new();
+    }
+    inner.signals |= signal;
+}
+
+/// Whether the current task blocks or ignores `signal`, so that it would not
+/// act on it
+pub fn current_blocks_signal(signal: SignalFlags) -> bool {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let signum = signal.lowest_signum().unwrap();
+    inner.signal_mask.contains(signal) || inner.signal_actions[signum].handler == SIG_IGN
+}
+
+/// Whether the current task has a pending signal which is not blocked
+pub fn current_has_signal() -> bool {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    !(inner.signals - inner.signal_mask).is_empty()
+}
+
+/// Deliver the pending signals of the current task before it returns to
+/// user mode. At most one handler is entered each time.
+pub fn handle_signals() {
+    loop {
+        let task = current_task().unwrap();
+        let mut inner = task.inner_exclusive_access();
+        // another thread has run execve, the SIGKILL was only to wake us up
+        if let Some(image) = inner.exec_image.take() {
+            inner.signals -= SignalFlags::SIGKILL;
+            drop(inner);
+            task.install_image(image);
+            continue;
+        }
+        // another thread has ended the process
+        if let Some(exit_status) = inner.group_exit_status {
+            drop(inner);
+            drop(task);
+            exit_current_with_status(exit_status);
+            return;
+        }
+        let signum = match (inner.signals - inner.signal_mask).lowest_signum() {
+            Some(signum) => signum,
+            None => return,
+        };
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        inner.signals -= signal;
+        let action = inner.signal_actions[signum];
+        match action.handler {
+            SIG_IGN => continue,
+            SIG_DFL if default_ignored(signal) => continue,
+            SIG_DFL if SignalFlags::stopping().contains(signal) => {
+                drop(inner);
+                drop(task);
+                stop_current(signum);
+                // SIGKILL may be what woke us up
+                continue;
+            }
+            SIG_DFL => {
+                drop(inner);
+                drop(task);
+                kill_current_and_run_next(signum);
+                return;
+            }
+            handler => {
+                // push the signal frame onto the user stack
+                let trap_cx = inner.get_trap_cx();
+                let frame = SignalFrame {
+                    x: trap_cx.x,
+                    sepc: trap_cx.sepc,
+                    mask: inner.signal_mask,
+                };
+                let sp = (trap_cx.x[2] - size_of::<SignalFrame>()) & !0xf;
+                let frame_bytes = unsafe {
+                    core::slice::from_raw_parts(&frame as *const _ as *const u8, size_of::<SignalFrame>())
+                };
+                if inner.memory_set.lock().copy_to_user(sp, frame_bytes).is_err() {
+                    // the stack is broken, nothing can handle it
+                    drop(inner);
+                    drop(task);
+                    kill_current_and_run_next(SignalFlags::SIGSEGV.lowest_signum().unwrap());
+                    return;
+                }
+                // enter the handler, returning from it runs `rt_sigreturn`
+                trap_cx.x[2] = sp;
+                trap_cx.x[1] = SIGRETURN_TRAMPOLINE;
+                trap_cx.x[10] = signum;
+                trap_cx.x[11] = 0;
+                trap_cx.x[12] = 0;
+                trap_cx.sepc = handler;
+                inner.signal_mask |= action.mask - SignalFlags::unmaskable();
+                if action.flags & SA_NODEFER == 0 {
+                    inner.signal_mask |= signal;
+                }
+                if action.flags & SA_RESETHAND != 0 {
+                    inner.signal_actions[signum] = SigAction::new();
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Restore the context saved by `handle_signals`, return the restored `a0`
+pub fn sigreturn_current() -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let trap_cx = inner.get_trap_cx();
+    let sp = trap_cx.x[2];
+    let mut frame = SignalFrame {
+        x: [0; 32],
+        sepc: 0,
+        mask: SignalFlags::empty(),
+    };
+    let frame_bytes = unsafe {
+        core::slice::from_raw_parts_mut(&mut frame as *mut _ as *mut u8, size_of::<SignalFrame>())
+    };
+    if inner.memory_set.lock().copy_from_user(frame_bytes, sp).is_err() {
+        drop(inner);
+        drop(task);
+        kill_current_and_run_next(SignalFlags::SIGSEGV.lowest_signum().unwrap());
+        return -1;
+    }
+    trap_cx.x = frame.x;
+    trap_cx.sepc = frame.sepc;
+    inner.signal_mask = frame.mask - SignalFlags::unmaskable();
+    trap_cx.x[10] as isize
+}
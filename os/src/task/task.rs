@@ -1,18 +1,24 @@
 //!Implementation of [`TaskControlBlock`]
 use super::TaskContext;
-use super::{pid_alloc, KernelStack, PidHandle};
-use crate::config::{TRAP_CONTEXT, PAGE_SIZE, USER_HEAP_SIZE};
+use super::{pid_alloc, CloneFlags, KernelStack, PidHandle, SchedInfo};
+use super::signal::{SigAction, SignalFlags, MAX_SIG, SIG_IGN};
+use super::rlimit::{RLimits, RLIMIT_AS, RLIMIT_NOFILE, RLIMIT_STACK};
+use crate::config::{TRAP_CONTEXT, PAGE_SIZE, USER_SPACE_END, USER_STACK_MAX, USER_STACK_SIZE};
 use crate::fs::{File, Stdin, Stdout, FileDescriptor, FileType};
-use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE, MapPermission, MMapArea, MapType, VirtPageNum};
-use crate::sync::UPSafeCell;
+use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE, MapPermission, MMapArea, MMapFlags, MapType, VirtPageNum};
+use crate::mm::{translated_byte_buffer, AuxHeader, UserBuffer, AT_NULL, AT_RANDOM};
+use crate::sync::{SpinLock, SpinLockGuard, WaitQueue};
+use crate::random::fill_random;
+use crate::timer::CpuTimes;
+use core::mem::size_of;
 use crate::trap::{trap_handler, TrapContext};
+use crate::syscall::{Errno, SysResult};
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
 use alloc::vec::Vec;
 //ztr_file
 use alloc::string::String;
 use riscv::register::fcsr::{Flags, Flag};
-use core::cell::RefMut;
 use core::iter::Map;
 use core::panic;
 
@@ -20,20 +26,67 @@ pub struct TaskControlBlock {
     // immutable
     pub pid: PidHandle,
     pub kernel_stack: KernelStack,
+    /// the task blocked in `wait4` until one of its children exits
+    pub child_exit: WaitQueue,
     // mutable
-    inner: UPSafeCell<TaskControlBlockInner>,
+    inner: SpinLock<TaskControlBlockInner>,
 }
 
 pub struct TaskControlBlockInner {
     pub trap_cx_ppn: PhysPageNum,
+    /// user virtual address of the trap context, every thread has its own
+    pub trap_cx_va: usize,
     pub base_size: usize,
     pub task_cx: TaskContext,
     pub task_status: TaskStatus,
-    pub memory_set: MemorySet,
+    /// shared by the threads of a process
+    pub memory_set: Arc<SpinLock<MemorySet>>,
+    /// thread group id, the pid of the first thread of the process
+    pub tgid: usize,
+    /// process group, the unit of job control
+    pub pgid: usize,
+    /// session, the process groups sharing a controlling terminal
+    pub sid: usize,
+    /// stopped by SIGSTOP or a job control signal until SIGCONT arrives
+    pub stopped: bool,
+    /// `wait4` status of a stop the parent has not been told about yet
+    pub stop_status: Option<i32>,
+    /// set when another thread ends the process, the status to exit with
+    pub group_exit_status: Option<i32>,
+    /// the program another thread has loaded by `execve` for this one to run
+    pub exec_image: Option<ExecImage>,
+    /// written with 0 when the thread exits, set by `CLONE_CHILD_CLEARTID`
+    pub clear_child_tid: usize,
+    /// woken up while running, possibly on its way to block on another hart,
+    /// so the next block returns at once
+    pub wakeup_pending: bool,
+    /// priority and the accounting of the scheduler
+    pub sched: SchedInfo,
+    /// user and system time of this task
+    pub cpu_times: CpuTimes,
+    /// the first thread of the process, `None` in the first thread itself.
+    /// Exited threads and reaped children add their times to it
+    pub group_leader: Option<Arc<TaskControlBlock>>,
+    /// threads of the process besides the first one which have not exited,
+    /// counted in the first thread, which is not reaped before they are gone
+    pub live_threads: usize,
     pub parent: Option<Weak<TaskControlBlock>>,
     pub children: Vec<Arc<TaskControlBlock>>,
-    pub exit_code: i32,
-    pub fd_table: Vec<Option<FileDescriptor>>,
+    /// status reported by `wait4`: `exit_code << 8`, or the signal number
+    /// if killed by a signal
+    pub exit_status: i32,
+    /// pending signals
+    pub signals: SignalFlags,
+    /// blocked signals
+    pub signal_mask: SignalFlags,
+    /// actions indexed by signal number
+    pub signal_actions: [SigAction; MAX_SIG + 1],
+    /// shared by the tasks created with `CLONE_FILES`
+    pub fd_table: Arc<SpinLock<Vec<Option<FileDescriptor>>>>,
+    /// resource limits, the same for all the threads of a process
+    pub rlimits: RLimits,
+    /// arguments of the program, for /proc/<pid>/cmdline
+    pub cmdline: Vec<String>,
     //ztr_file
     pub work_path: String,
 }
@@ -43,7 +96,7 @@ impl TaskControlBlockInner {
         self.trap_cx_ppn.get_mut()
     }
     pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
+        self.memory_set.lock().token()
     }
     fn get_status(&self) -> TaskStatus {
         self.task_status
@@ -51,13 +104,20 @@ impl TaskControlBlockInner {
     pub fn is_zombie(&self) -> bool {
         self.get_status() == TaskStatus::Zombie
     }
-    pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            fd
-        } else {
-            self.fd_table.push(None);
-            self.fd_table.len() - 1
+    /// The lowest free fd, EMFILE if it would reach RLIMIT_NOFILE
+    pub fn alloc_fd(&mut self) -> Result<usize, Errno> {
+        let limit = self.rlimits.get(RLIMIT_NOFILE).cur;
+        let mut fd_table = self.fd_table.lock();
+        let fd = (0..fd_table.len())
+            .find(|fd| fd_table[*fd].is_none())
+            .unwrap_or(fd_table.len());
+        if fd >= limit {
+            return Err(Errno::EMFILE);
+        }
+        if fd == fd_table.len() {
+            fd_table.push(None);
         }
+        Ok(fd)
     }
     //ztr_open
     pub fn get_work_path(&self) -> String {
@@ -65,13 +125,75 @@ impl TaskControlBlockInner {
     }
 }
 
+/// A program loaded by `execve`, not yet running
+pub struct ExecImage {
+    memory_set: MemorySet,
+    user_sp: usize,
+    entry_point: usize,
+    cmdline: Vec<String>,
+}
+
+/// Lay out the strings, `argc`, `argv`, `envp` and the auxiliary vector below
+/// `user_sp` in `memory_set` as a Linux RISC-V process expects, return the
+/// new stack pointer, which points to `argc`
+fn init_user_stack(
+    memory_set: &mut MemorySet,
+    user_sp: usize,
+    args: &[String],
+    envs: &[String],
+    mut auxv: Vec<AuxHeader>,
+) -> usize {
+    // the stack is populated lazily, allocate the pages written here: the
+    // strings, AT_RANDOM, the pointers and the auxiliary vector, and padding
+    let strings: usize = args.iter().chain(envs.iter()).map(|s| s.len() + 1).sum();
+    let words = args.len() + envs.len() + 3 + 2 * (auxv.len() + 2);
+    let size = strings + 16 + words * size_of::<usize>() + 32;
+    memory_set.fault_in_user_range(user_sp - size, size, MapPermission::W);
+    let token = memory_set.token();
+    let write = |va: usize, bytes: &[u8]| {
+        UserBuffer::new(translated_byte_buffer(token, va as *const u8, bytes.len())).write(bytes);
+    };
+    let mut sp = user_sp;
+    let mut push_str = |s: &String| {
+        sp -= s.len() + 1;
+        write(sp, s.as_bytes());
+        write(sp + s.len(), &[0]);
+        sp
+    };
+    let argv: Vec<usize> = args.iter().map(&mut push_str).collect();
+    let envp: Vec<usize> = envs.iter().map(&mut push_str).collect();
+    // 16 bytes for AT_RANDOM
+    let mut random = [0u8; 16];
+    fill_random(&mut random);
+    sp = (sp - random.len()) & !0xf;
+    write(sp, &random);
+    auxv.push(AuxHeader { aux_type: AT_RANDOM, value: sp });
+    auxv.push(AuxHeader { aux_type: AT_NULL, value: 0 });
+    // argc, argv, NULL, envp, NULL, auxv
+    let mut words = vec![argv.len()];
+    words.extend(argv);
+    words.push(0);
+    words.extend(envp);
+    words.push(0);
+    for aux in auxv {
+        words.push(aux.aux_type);
+        words.push(aux.value);
+    }
+    sp = (sp - words.len() * size_of::<usize>()) & !0xf;
+    for (i, word) in words.iter().enumerate() {
+        write(sp + i * size_of::<usize>(), &word.to_ne_bytes());
+    }
+    sp
+}
+
 impl TaskControlBlock {
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
-        self.inner.exclusive_access()
+    pub fn inner_exclusive_access(&self) -> SpinLockGuard<'_, TaskControlBlockInner> {
+        self.inner.lock()
     }
     pub fn new(elf_data: &[u8]) -> Self {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (mut memory_set, user_sp, entry_point, auxv) = MemorySet::from_elf(elf_data, None, USER_STACK_SIZE).unwrap();
+        let user_sp = init_user_stack(&mut memory_set, user_sp, &[], &[], auxv);
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
@@ -80,119 +202,248 @@ impl TaskControlBlock {
         let pid_handle = pid_alloc();
         let kernel_stack = KernelStack::new(&pid_handle);
         let kernel_stack_top = kernel_stack.get_top();
+        let tgid = pid_handle.0;
         let task_control_block = Self {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx_ppn,
-                    base_size: user_sp,
-                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: vec![
-                        // 0 -> stdin
-                        Some(FileDescriptor::new(false, FileType::Abstr(Arc::new(Stdin)))),
-                        // 1 -> stdout
-                        Some(FileDescriptor::new(
-                            false,
-                            FileType::Abstr(Arc::new(Stdout)),
-                        )),
-                        // 2 -> stderr
-                        Some(FileDescriptor::new(
-                            false,
-                            FileType::Abstr(Arc::new(Stdout)),
-                        )),
-                    ],
-                    work_path: String::from("/"),
-                })
-            }
+            child_exit: WaitQueue::new(),
+            inner: SpinLock::new(TaskControlBlockInner {
+                trap_cx_ppn,
+                trap_cx_va: TRAP_CONTEXT,
+                base_size: user_sp,
+                task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                task_status: TaskStatus::Ready,
+                memory_set: Arc::new(SpinLock::new(memory_set)),
+                tgid,
+                // the first process leads its own group and session
+                pgid: tgid,
+                sid: tgid,
+                stopped: false,
+                stop_status: None,
+                group_exit_status: None,
+                exec_image: None,
+                clear_child_tid: 0,
+                wakeup_pending: false,
+                sched: SchedInfo::new(),
+                cpu_times: CpuTimes::new(),
+                group_leader: None,
+                live_threads: 0,
+                parent: None,
+                children: Vec::new(),
+                exit_status: 0,
+                signals: SignalFlags::empty(),
+                signal_mask: SignalFlags::empty(),
+                signal_actions: [SigAction::new(); MAX_SIG + 1],
+                fd_table: Arc::new(SpinLock::new(vec![
+                    // 0 -> stdin
+                    Some(FileDescriptor::new(false, FileType::Abstr(Arc::new(Stdin)))),
+                    // 1 -> stdout
+                    Some(FileDescriptor::new(
+                        false,
+                        FileType::Abstr(Arc::new(Stdout)),
+                    )),
+                    // 2 -> stderr
+                    Some(FileDescriptor::new(
+                        false,
+                        FileType::Abstr(Arc::new(Stdout)),
+                    )),
+                ])),
+                rlimits: RLimits::new(),
+                cmdline: Vec::new(),
+                work_path: String::from("/"),
+            }),
         };
         // prepare TrapContext in user space
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
         *trap_cx = TrapContext::app_init_context(
             entry_point,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             kernel_stack_top,
             trap_handler as usize,
         );
         task_control_block
     }
-    pub fn exec(&self, elf_data: &[u8]) {
+    /// Load the program `elf_data` for the process of the task, which
+    /// receives `args` and `envs` on its stack. `interp_data` is its dynamic
+    /// linker. Nothing changes if either image can't be loaded.
+    pub fn load_image(
+        &self,
+        elf_data: &[u8],
+        interp_data: Option<&[u8]>,
+        args: &[String],
+        envs: &[String],
+    ) -> Result<ExecImage, Errno> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (mut memory_set, user_sp, entry_point, auxv) = MemorySet::from_elf(elf_data, interp_data, self.user_stack_size())?;
+        let user_sp = init_user_stack(&mut memory_set, user_sp, args, envs, auxv);
+        Ok(ExecImage {
+            memory_set,
+            user_sp,
+            entry_point,
+            cmdline: args.to_vec(),
+        })
+    }
+    /// Size of the user stack `execve` gives the new program, RLIMIT_STACK.
+    /// It is never smaller than the default, the arguments go there
+    pub fn user_stack_size(&self) -> usize {
+        let stack_limit = self.inner_exclusive_access().rlimits.get(RLIMIT_STACK).cur;
+        stack_limit.clamp(USER_STACK_SIZE, USER_STACK_MAX) & !(PAGE_SIZE - 1)
+    }
+    /// Replace the program of the task with `image`, the other threads of
+    /// the process must be gone
+    pub fn install_image(&self, image: ExecImage) {
+        let ExecImage { memory_set, user_sp, entry_point, cmdline } = image;
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();      
         // **** access current TCB exclusively
         let mut inner = self.inner_exclusive_access();
-        // substitute memory_set
-        inner.memory_set = memory_set;
+        // write back shared mappings, then substitute memory_set. A thread
+        // which has just exited may still hold the old one
+        if Arc::strong_count(&inner.memory_set) == 1 {
+            inner.memory_set.lock().recycle_data_pages();
+        } else {
+            inner.memory_set.lock().dealloc_trap_cx(inner.trap_cx_va);
+        }
+        inner.memory_set = Arc::new(SpinLock::new(memory_set));
         // update trap_cx ppn
         inner.trap_cx_ppn = trap_cx_ppn;
-        // initialize trap_cx  
-        let f_user_sp  = user_sp - 8;       
+        inner.trap_cx_va = TRAP_CONTEXT;
+        // initialize trap_cx
         let trap_cx = TrapContext::app_init_context(
             entry_point,
-            f_user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            user_sp,
+            KERNEL_SPACE.lock().token(),
             self.kernel_stack.get_top(),
             trap_handler as usize,
         );
         *inner.get_trap_cx() = trap_cx;
+        inner.cmdline = cmdline;
+        // the handlers are gone with the old image, ignored signals stay ignored
+        for action in inner.signal_actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SigAction::new();
+            }
+        }
+        // the fd table is not shared with the other threads any more
+        if Arc::strong_count(&inner.fd_table) > 1 {
+            let fd_table = inner.fd_table.lock().clone();
+            inner.fd_table = Arc::new(SpinLock::new(fd_table));
+        }
+        for fd in inner.fd_table.lock().iter_mut() {
+            if fd.as_ref().map_or(false, |fd| fd.get_cloexec()) {
+                *fd = None;
+            }
+        }
         // **** release current PCB
     }
     pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
+        self.clone_task(CloneFlags::SIGCHLD, 0)
+    }
+    /// Create a task as `clone` does. With `CLONE_VM` the address space is
+    /// shared and the new task gets a trap context page of its own, with
+    /// `CLONE_FILES` the fd table is shared, otherwise they are copied.
+    /// A `CLONE_THREAD` task joins the thread group of `self` instead of
+    /// becoming its child. A non-zero `stack` is the new user stack pointer.
+    pub fn clone_task(self: &Arc<TaskControlBlock>, flags: CloneFlags, stack: usize) -> Arc<TaskControlBlock> {
         // ---- hold parent PCB lock
         let mut parent_inner = self.inner_exclusive_access();
-        // copy user space(include trap context)
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        let (memory_set, trap_cx_va) = if flags.contains(CloneFlags::CLONE_VM) {
+            let memory_set = parent_inner.memory_set.clone();
+            let trap_cx_va = memory_set.lock().alloc_trap_cx();
+            // the new thread returns from clone with the registers of the caller
+            memory_set
+                .lock()
+                .translate(VirtAddr::from(trap_cx_va).into())
+                .unwrap()
+                .ppn()
+                .get_bytes_array()
+                .copy_from_slice(parent_inner.trap_cx_ppn.get_bytes_array());
+            (memory_set, trap_cx_va)
+        } else {
+            // copy user space(include trap context)
+            let memory_set = MemorySet::from_existed_user(&mut parent_inner.memory_set.lock());
+            (Arc::new(SpinLock::new(memory_set)), parent_inner.trap_cx_va)
+        };
         let trap_cx_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .lock()
+            .translate(VirtAddr::from(trap_cx_va).into())
             .unwrap()
             .ppn();
         // alloc a pid and a kernel stack in kernel space
         let pid_handle = pid_alloc();
         let kernel_stack = KernelStack::new(&pid_handle);
         let kernel_stack_top = kernel_stack.get_top();
-        // copy fd table
-        let mut new_fd_table: Vec<Option<FileDescriptor>> = Vec::new();
-        for fd in parent_inner.fd_table.iter() {
-            if let Some(file) = fd {
-                new_fd_table.push(Some(file.clone()));
-            } else {
-                new_fd_table.push(None);
-            }
-        }
+        let fd_table = if flags.contains(CloneFlags::CLONE_FILES) {
+            parent_inner.fd_table.clone()
+        } else {
+            // copy fd table
+            let new_fd_table: Vec<Option<FileDescriptor>> = parent_inner.fd_table.lock().clone();
+            Arc::new(SpinLock::new(new_fd_table))
+        };
+        let (tgid, parent, group_leader) = if flags.contains(CloneFlags::CLONE_THREAD) {
+            // a thread shares the parent of the thread group
+            let group_leader = parent_inner.group_leader.clone().unwrap_or_else(|| self.clone());
+            (parent_inner.tgid, parent_inner.parent.clone(), Some(group_leader))
+        } else {
+            (pid_handle.0, Some(Arc::downgrade(self)), None)
+        };
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx_ppn,
-                    base_size: parent_inner.base_size,
-                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: new_fd_table,
-                    work_path: parent_inner.work_path.clone(),
-                })
-            },
+            child_exit: WaitQueue::new(),
+            inner: SpinLock::new(TaskControlBlockInner {
+                trap_cx_ppn,
+                trap_cx_va,
+                base_size: parent_inner.base_size,
+                task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                task_status: TaskStatus::Ready,
+                memory_set,
+                tgid,
+                pgid: parent_inner.pgid,
+                sid: parent_inner.sid,
+                stopped: false,
+                stop_status: None,
+                group_exit_status: None,
+                exec_image: None,
+                clear_child_tid: 0,
+                wakeup_pending: false,
+                // inherit the priority and the accounting of the parent
+                sched: SchedInfo {
+                    preempted: false,
+                    ..parent_inner.sched
+                },
+                cpu_times: CpuTimes::new(),
+                group_leader: group_leader.clone(),
+                live_threads: 0,
+                parent,
+                children: Vec::new(),
+                exit_status: 0,
+                signals: SignalFlags::empty(),
+                signal_mask: parent_inner.signal_mask,
+                signal_actions: parent_inner.signal_actions,
+                fd_table,
+                rlimits: parent_inner.rlimits,
+                cmdline: parent_inner.cmdline.clone(),
+                work_path: parent_inner.work_path.clone(),
+            }),
         });
-        // add child
-        parent_inner.children.push(task_control_block.clone());
+        match group_leader {
+            Some(group_leader) if Arc::ptr_eq(&group_leader, self) => parent_inner.live_threads += 1,
+            // a thread is locked before the first thread of its process
+            Some(group_leader) => group_leader.inner_exclusive_access().live_threads += 1,
+            // add child
+            None => parent_inner.children.push(task_control_block.clone()),
+        }
         // modify kernel_sp in trap_cx
         // **** access child PCB exclusively
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
         trap_cx.kernel_sp = kernel_stack_top;
+        if stack != 0 {
+            trap_cx.x[2] = stack;
+        }
         // return
         task_control_block
         // **** release child PCB
@@ -201,56 +452,74 @@ impl TaskControlBlock {
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
+    /// The process id seen by user programs, shared by all the threads
+    pub fn gettgid(&self) -> usize {
+        self.inner_exclusive_access().tgid
+    }
     //ztr_mmap
-    pub fn mmap(&self, start: usize, len: usize, prot: u32, _flags: u32, fd: usize, off: usize) -> isize {
-        let mut inner = self.inner_exclusive_access();
-        //将usize转换为虚拟地址
-        let start_addr = VirtAddr::from(start);
-        //获取当前地址所在虚拟地址页号
-        let mut start_vpn = start_addr.floor();
-        //获取文件描述符表
-        let fd_table = inner.fd_table.clone();
+    pub fn mmap(&self, start: usize, len: usize, prot: u32, flags: u32, fd: usize, off: usize) -> SysResult {
+        let inner = self.inner_exclusive_access();
         //确定权限控制
         let map_perm = (((prot & 0b111)<<1) + (1<<4))  as u8;
+        let flags = MMapFlags::from_bits_truncate(flags);
+        //SHARED 与 PRIVATE 必须且只能指定一个
+        if flags.contains(MMapFlags::SHARED) == flags.contains(MMapFlags::PRIVATE) || len == 0 {
+            return Err(Errno::EINVAL);
+        }
+        //地址空间不能超过 RLIMIT_AS
+        let map_size = len.checked_add(PAGE_SIZE - 1).ok_or(Errno::ENOMEM)? & !(PAGE_SIZE - 1);
+        if inner.memory_set.lock().mapped_size().saturating_add(map_size) > inner.rlimits.get(RLIMIT_AS).cur {
+            return Err(Errno::ENOMEM);
+        }
+        //获取映射的文件，匿名映射忽略 fd
+        let file = if flags.contains(MMapFlags::ANONYMOUS) {
+            None
+        } else {
+            match inner.fd_table.lock().get(fd) {
+                Some(Some(FileDescriptor { ftype: FileType::File(f), .. })) if f.readable() => Some(f.clone()),
+                Some(Some(FileDescriptor { ftype: FileType::File(_), .. })) => return Err(Errno::EACCES),
+                _ => return Err(Errno::EBADF),
+            }
+        };
+        //共享的可写映射会写回文件
+        if let Some(f) = &file {
+            if flags.contains(MMapFlags::SHARED) && map_perm & MapPermission::W.bits() != 0 && !f.writable() {
+                return Err(Errno::EACCES);
+            }
+        }
         
         //当start有指定值时，需判断当前虚拟地址是否已经被分配
         if start != 0 {
             if start % PAGE_SIZE!= 0 {
-                panic!("mmap: The address :{} is illegal!", start);
+                return Err(Errno::EINVAL);
             }
-            //检查当前地址到分配结束是否被占用
-            while start_vpn.0 < (start + len) % PAGE_SIZE {
-                if !inner.memory_set.translate(start_vpn).unwrap().is_valid() {
-                    return -1;
-                }
-                start_vpn.0 += 1;
+            //不能越过用户空间的末尾，VirtAddr 只保留低 39 位
+            if start.checked_add(len).map_or(true, |end| end > USER_SPACE_END) {
+                return Err(Errno::ENOMEM);
             }
-            //如果没有被占用，则插入mmap区域，需要确定是否插入成功
-            let mmap_areas = MMapArea::new(VirtAddr::from(start), VirtAddr::from(start+ len), MapPermission::from_bits(map_perm).unwrap(), MapType::Framed, fd, _flags as usize, off);
-            let tags = inner.memory_set.push_mmap_area(mmap_areas, fd_table);
-            drop(inner);
-            if tags == 0 {
-                return -1;
+            //检查当前地址到分配结束是否被占用
+            if !inner.memory_set.lock().is_free_range(VirtAddr::from(start).floor(), VirtAddr::from(start + len).ceil()) {
+                return Err(Errno::ENOMEM);
             }
-            return start as isize;
+            //页在第一次访问时才分配
+            inner.memory_set.lock().insert_mmap_area(VirtAddr::from(start), VirtAddr::from(start + len), MapPermission::from_bits(map_perm).unwrap(), file, off, flags);
+            return Ok(start as isize);
         }
         //如果为NULL，自主找到空闲区域进行分配
         else {
-            let re_addr = VirtAddr::from(inner.memory_set.get_max_vpn()).0;
-            let mmap_areas = MMapArea::new(VirtAddr::from(re_addr), VirtAddr::from(re_addr + len), MapPermission::from_bits(map_perm).unwrap(), MapType::Framed, fd, _flags as usize, off);
-            let tags = inner.memory_set.push_mmap_area(mmap_areas, fd_table);
-            inner.memory_set.set_max_vpn(re_addr + len);
-            drop(inner);
-            if tags == 0 {
-                return -1;
+            let re_addr = VirtAddr::from(inner.memory_set.lock().get_max_vpn()).0;
+            if re_addr.checked_add(len).map_or(true, |end| end > USER_SPACE_END) {
+                return Err(Errno::ENOMEM);
             }
-            return re_addr as isize;
+            inner.memory_set.lock().insert_mmap_area(VirtAddr::from(re_addr), VirtAddr::from(re_addr + len), MapPermission::from_bits(map_perm).unwrap(), file, off, flags);
+            inner.memory_set.lock().set_max_vpn(re_addr + len);
+            return Ok(re_addr as isize);
         }
     }
 
     pub fn munmap(&self, start: usize, len: usize) -> isize {
-        let mut inner = self.inner_exclusive_access();
-        let tags = inner.memory_set.remove_MMapArea_with_start_vpn(VirtPageNum::from(start), VirtPageNum::from(start + len));
+        let inner = self.inner_exclusive_access();
+        let tags = inner.memory_set.lock().remove_MMapArea_with_start_vpn(VirtAddr::from(start).floor(), VirtAddr::from(start + len).ceil());
         drop(inner);
         tags
     }
@@ -260,5 +529,7 @@ impl TaskControlBlock {
 pub enum TaskStatus {
     Ready,
     Running,
+    /// waiting for an event, not in the ready queue
+    Blocked,
     Zombie,
 }
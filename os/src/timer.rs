@@ -23,24 +23,42 @@
 /// ## 实现功能
 /// ```
 /// pub struct  TimeVal
+/// pub struct  TimeSpec
 /// pub fn get_time() -> usize
 /// pub fn get_time_ms() -> usize
+/// pub fn get_time_ns() -> usize
 /// pub fn get_TimeVal() -> TimeVal
+/// pub struct CpuTimes
 /// pub fn set_next_trigger()
+/// pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>)
+/// pub fn remove_timer(task: &Arc<TaskControlBlock>)
+/// pub fn check_timer()
 /// ```
 //
 
 use crate::config::CLOCK_FREQ;
 use crate::sbi::set_timer;
+use crate::sync::SpinLock;
+use crate::task::{wakeup_task, TaskControlBlock};
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use core::cmp::Ordering;
+use lazy_static::*;
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
 const MSEC_PER_SEC: usize = 1000;
+const USEC_PER_SEC: usize = 1_000_000;
+const NSEC_PER_SEC: usize = 1_000_000_000;
+/// `times` 的计时单位，即 Linux 的 USER_HZ
+pub const CLOCKS_PER_SEC: usize = 100;
 
 /// ### Linux 时间格式
 /// - `sec`：秒
 /// - `usec`：微秒
 /// - 两个值相加的结果是结构体表示的时间
+#[repr(C)]
+#[derive(Clone, Copy)]
 pub struct  TimeVal {
     /// 单位：秒
     pub sec:usize,  /// 单位：微秒
@@ -48,14 +66,50 @@ pub struct  TimeVal {
 }
 
 impl TimeVal {
+    /// 由纳秒数换算，不足 1 微秒的部分舍去
+    pub fn from_ns(ns: usize) -> Self {
+        Self {
+            sec: ns / NSEC_PER_SEC,
+            usec: ns % NSEC_PER_SEC / (NSEC_PER_SEC / USEC_PER_SEC),
+        }
+    }
     pub fn as_bytes(&self) -> &[u8] {
         let size = core::mem::size_of::<Self>();
         unsafe { core::slice::from_raw_parts(self as *const _ as usize as *const u8, size) }
     }
 }
 
+/// ### Linux `timespec`
+/// - `sec`：秒
+/// - `nsec`：纳秒
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    /// 由纳秒数换算
+    pub fn from_ns(ns: usize) -> Self {
+        Self {
+            sec: ns / NSEC_PER_SEC,
+            nsec: ns % NSEC_PER_SEC,
+        }
+    }
+    /// 秒数非负且纳秒数小于 1 秒
+    pub fn is_valid(&self) -> bool {
+        (self.sec as isize) >= 0 && self.nsec < NSEC_PER_SEC
+    }
+    /// 换算为毫秒，不足 1 毫秒的部分向上取整
+    pub fn as_ms(&self) -> usize {
+        self.sec * MSEC_PER_SEC + (self.nsec + 999_999) / 1_000_000
+    }
+}
+
 #[allow(non_camel_case_types)]
-/// ### Linux 间隔计数
+#[repr(C)]
+/// ### Linux 间隔计数，单位为 `1 / CLOCKS_PER_SEC` 秒
 /// - `tms_utime`：用户态时间
 /// - `tms_stime`：内核态时间
 /// - `tms_cutime`：已回收子进程的用户态时间
@@ -68,12 +122,131 @@ pub struct tms {    /// 用户态时间
 }
 
 impl tms {
+    /// 由 CPU 时间换算
+    pub fn from_times(times: &CpuTimes) -> Self {
+        let ticks = |ns: usize| (ns / (NSEC_PER_SEC / CLOCKS_PER_SEC)) as isize;
+        Self {
+            tms_utime: ticks(times.utime),
+            tms_stime: ticks(times.stime),
+            tms_cutime: ticks(times.cutime),
+            tms_cstime: ticks(times.cstime),
+        }
+    }
     pub fn as_bytes(&self) -> &[u8] {
         let size = core::mem::size_of::<Self>();
         unsafe { core::slice::from_raw_parts(self as *const _ as usize as *const u8, size) }
     }
 }
 
+/// ### Linux 资源使用统计
+/// - `ru_utime`：用户态时间
+/// - `ru_stime`：内核态时间
+/// - 其余字段暂不统计，始终为 0
+#[repr(C)]
+pub struct Rusage {
+    pub ru_utime: TimeVal,
+    pub ru_stime: TimeVal,
+    pub ru_maxrss: isize,
+    pub ru_ixrss: isize,
+    pub ru_idrss: isize,
+    pub ru_isrss: isize,
+    pub ru_minflt: isize,
+    pub ru_majflt: isize,
+    pub ru_nswap: isize,
+    pub ru_inblock: isize,
+    pub ru_oublock: isize,
+    pub ru_msgsnd: isize,
+    pub ru_msgrcv: isize,
+    pub ru_nsignals: isize,
+    pub ru_nvcsw: isize,
+    pub ru_nivcsw: isize,
+}
+
+impl Rusage {
+    pub fn new() -> Self {
+        Self {
+            ru_utime: TimeVal { sec: 0, usec: 0 },
+            ru_stime: TimeVal { sec: 0, usec: 0 },
+            ru_maxrss: 0,
+            ru_ixrss: 0,
+            ru_idrss: 0,
+            ru_isrss: 0,
+            ru_minflt: 0,
+            ru_majflt: 0,
+            ru_nswap: 0,
+            ru_inblock: 0,
+            ru_oublock: 0,
+            ru_msgsnd: 0,
+            ru_msgrcv: 0,
+            ru_nsignals: 0,
+            ru_nvcsw: 0,
+            ru_nivcsw: 0,
+        }
+    }
+    /// 用户态与内核态时间分别为 `utime`、`stime`（单位：ns）
+    pub fn from_ns(utime: usize, stime: usize) -> Self {
+        Self {
+            ru_utime: TimeVal::from_ns(utime),
+            ru_stime: TimeVal::from_ns(stime),
+            ..Self::new()
+        }
+    }
+}
+
+/// ### 任务的 CPU 时间（单位：ns）
+/// 在 `trap_handler` 进入内核、`trap_return` 返回用户态以及调度时结算
+#[derive(Copy, Clone)]
+pub struct CpuTimes {
+    /// 用户态时间
+    pub utime: usize,
+    /// 内核态时间
+    pub stime: usize,
+    /// 已回收子进程的用户态时间
+    pub cutime: usize,
+    /// 已回收子进程的内核态时间
+    pub cstime: usize,
+    /// 上次结算的时刻
+    mark: usize,
+}
+
+impl CpuTimes {
+    pub fn new() -> Self {
+        Self {
+            utime: 0,
+            stime: 0,
+            cutime: 0,
+            cstime: 0,
+            mark: get_time_ns(),
+        }
+    }
+    /// 从用户态进入内核，结算用户态时间
+    pub fn enter_kernel(&mut self) {
+        let now = get_time_ns();
+        self.utime += now - self.mark;
+        self.mark = now;
+    }
+    /// 返回用户态或让出 CPU，结算内核态时间
+    pub fn leave_kernel(&mut self) {
+        let now = get_time_ns();
+        self.stime += now - self.mark;
+        self.mark = now;
+    }
+    /// 被调度到 CPU 上，让出 CPU 期间的时间不计入
+    pub fn scheduled(&mut self) {
+        self.mark = get_time_ns();
+    }
+    /// 计入一个已回收的子进程及其子进程的时间
+    pub fn add_child(&mut self, child: &CpuTimes) {
+        self.cutime += child.utime + child.cutime;
+        self.cstime += child.stime + child.cstime;
+    }
+    /// 计入同一线程组中另一个线程的时间
+    pub fn add_thread(&mut self, thread: &CpuTimes) {
+        self.utime += thread.utime;
+        self.stime += thread.stime;
+    }
+}
+
 /// ### 取得当前 `mtime` 计数器的值
 /// - `mtime`: 统计处理器自上电以来经过了多少个内置时钟的时钟周期,64bit
 pub fn get_time() -> usize {
@@ -85,17 +258,81 @@ pub fn get_time_ms() -> usize {
     time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
 }
 
+/// 获取CPU上电时间（单位：ns）
+pub fn get_time_ns() -> usize {
+    (time::read() as u128 * NSEC_PER_SEC as u128 / CLOCK_FREQ as u128) as usize
+}
+
 /// 获取 `TimeVal` 格式的时间信息
 #[allow(non_snake_case)]
 pub fn get_TimeVal() -> TimeVal{
-    let time_ms = get_time_ms();
-    TimeVal {
-        sec: time_ms / 1000,
-        usec: (time_ms % 1000) * 1000,
-    }
+    TimeVal::from_ns(get_time_ns())
 }
 
 /// ### 设置下次触发时钟中断的时间
 pub fn set_next_trigger() {
     set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
 }
+
+/// ### 定时器
+/// - `expire_ms`：到期时间（单位：ms）
+/// - `task`：到期时唤醒的任务
+pub struct TimerCondVar {
+    pub expire_ms: usize,
+    pub task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for TimerCondVar {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_ms == other.expire_ms
+    }
+}
+
+impl Eq for TimerCondVar {}
+
+impl PartialOrd for TimerCondVar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerCondVar {
+    /// `BinaryHeap` 是大根堆，反转比较使最早到期的定时器位于堆顶
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire_ms.cmp(&self.expire_ms)
+    }
+}
+
+lazy_static! {
+    /// 按到期时间排序的定时器队列
+    static ref TIMERS: SpinLock<BinaryHeap<TimerCondVar>> =
+        SpinLock::new(BinaryHeap::new());
+}
+
+/// ### 添加定时器，在 `expire_ms` 时唤醒 `task`
+pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
+    TIMERS.lock().push(TimerCondVar { expire_ms, task });
+}
+
+/// ### 移除 `task` 的所有定时器，任务退出时调用
+pub fn remove_timer(task: &Arc<TaskControlBlock>) {
+    let mut timers = TIMERS.lock();
+    let remaining: BinaryHeap<TimerCondVar> = timers
+        .drain()
+        .filter(|timer| !Arc::ptr_eq(&timer.task, task))
+        .collect();
+    *timers = remaining;
+}
+
+/// ### 唤醒所有已到期定时器对应的任务
+pub fn check_timer() {
+    let current_ms = get_time_ms();
+    let mut timers = TIMERS.lock();
+    while let Some(timer) = timers.peek() {
+        if timer.expire_ms > current_ms {
+            break;
+        }
+        let timer = timers.pop().unwrap();
+        wakeup_task(timer.task);
+    }
+}
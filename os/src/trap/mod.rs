@@ -13,12 +13,15 @@
 //! to [`syscall()`].
 mod context;
 
-use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::config::TRAMPOLINE;
+use crate::fs::poll_console;
+use crate::mm::{MapPermission, VirtAddr};
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    current_task, current_trap_cx, current_trap_cx_user_va, current_user_token, suspend_current_and_run_next,
+    force_signal_current, handle_signals, SignalFlags,
 };
-use crate::timer::set_next_trigger;
+use crate::timer::{check_timer, set_next_trigger};
 use core::arch::{asm, global_asm};
 use riscv::register::{
     mtvec::TrapMode,
@@ -27,6 +30,8 @@ use riscv::register::{
 };
 
 global_asm!(include_str!("trap.S"));
+/// `scause` of a misaligned load, which `Exception` of the riscv crate lacks
+const EXCEPTION_LOAD_MISALIGNED: usize = 4;
 /// initialize CSR `stvec` as the entry of `__alltraps`
 pub fn init() {
     set_kernel_trap_entry();
@@ -55,6 +60,7 @@ pub fn enable_timer_interrupt() {
 /// handle an interrupt, exception, or system call from user space
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    current_task().unwrap().inner_exclusive_access().cpu_times.enter_kernel();
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
@@ -68,6 +74,13 @@ pub fn trap_handler() -> ! {
             cx = current_trap_cx();
             cx.x[10] = result as usize;
         }
+        Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::InstructionPageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+            if user_page_fault(scause.cause(), stval) =>
+        {
+            // the page has been allocated or copied, retry the instruction
+        }
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::InstructionFault)
@@ -75,21 +88,35 @@ pub fn trap_handler() -> ! {
         | Trap::Exception(Exception::LoadFault)
         | Trap::Exception(Exception::LoadPageFault) => {
             println!(
-                "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
+                "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, send SIGSEGV.",
                 scause.cause(),
                 stval,
                 current_trap_cx().sepc,
             );
-            // page fault exit code
-            exit_current_and_run_next(-2);
+            force_signal_current(SignalFlags::SIGSEGV);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            println!("[kernel] IllegalInstruction in application, kernel killed it.");
-            // illegal instruction exit code
-            exit_current_and_run_next(-3);
+            println!("[kernel] IllegalInstruction in application, send SIGILL.");
+            force_signal_current(SignalFlags::SIGILL);
+        }
+        Trap::Exception(Exception::Breakpoint) => {
+            force_signal_current(SignalFlags::SIGTRAP);
+        }
+        Trap::Exception(Exception::InstructionMisaligned) | Trap::Exception(Exception::StoreMisaligned) => {
+            println!("[kernel] {:?} in application, bad addr = {:#x}, send SIGBUS.", scause.cause(), stval);
+            force_signal_current(SignalFlags::SIGBUS);
+        }
+        Trap::Exception(Exception::Unknown) if scause.code() == EXCEPTION_LOAD_MISALIGNED => {
+            println!("[kernel] LoadMisaligned in application, bad addr = {:#x}, send SIGBUS.", stval);
+            force_signal_current(SignalFlags::SIGBUS);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
+            check_timer();
+            // Ctrl-C 等需要在程序不读输入时也能送达
+            poll_console();
+            // the time slice is used up
+            current_task().unwrap().inner_exclusive_access().sched.preempted = true;
             suspend_current_and_run_next();
         }
         _ => {
@@ -100,17 +127,42 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+    // deliver signals before going back to user mode
+    handle_signals();
     //println!("before trap_return");
     trap_return();
 }
 
+/// Try to resolve a page fault of the current task, on demand or copy-on-write
+fn user_page_fault(cause: Trap, stval: usize) -> bool {
+    let access = match cause {
+        Trap::Exception(Exception::StorePageFault) => MapPermission::W,
+        Trap::Exception(Exception::InstructionPageFault) => MapPermission::X,
+        _ => MapPermission::R,
+    };
+    let resolved = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .memory_set
+        .lock()
+        .handle_page_fault(VirtAddr::from(stval), access);
+    if resolved {
+        // drop the stale translation before retrying
+        unsafe {
+            asm!("sfence.vma");
+        }
+    }
+    resolved
+}
+
 #[no_mangle]
 /// set the new addr of __restore asm function in TRAMPOLINE page,
 /// set the reg a0 = trap_cx_ptr, reg a1 = phy addr of usr page table,
 /// finally, jump to new addr of __restore asm function
 pub fn trap_return() -> ! {
     set_user_trap_entry();
-    let trap_cx_ptr = TRAP_CONTEXT;
+    current_task().unwrap().inner_exclusive_access().cpu_times.leave_kernel();
+    let trap_cx_ptr = current_trap_cx_user_va();
     let user_satp = current_user_token();
     extern "C" {
         fn __alltraps();
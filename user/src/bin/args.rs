@@ -0,0 +1,103 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use core::ptr::null;
+use user_lib::errno::{E2BIG, EBADF};
+use user_lib::{
+    close, execve, exit, fork, getauxval, getenv, getrlimit, open, setrlimit, wait, OpenFlags,
+    RLimit, AT_BASE, AT_ENTRY, AT_PAGESZ, AT_PHDR, AT_PHNUM, AT_RANDOM, RLIMIT_STACK,
+};
+
+/// bytes of the value of `LONG`
+const LONG_LEN: usize = 5000;
+
+/// Run by `main` with arguments and an environment
+fn child(argv: &[&str]) -> i32 {
+    assert_eq!(argv, ["args", "hello", "world", "3", "4"]);
+    assert_eq!(getenv("GREETING"), Some("hi there"));
+    assert_eq!(getenv("EMPTY"), Some(""));
+    assert_eq!(getenv("GREET"), None);
+    // where the program headers are mapped
+    let phdr = getauxval(AT_PHDR).unwrap();
+    assert_ne!(phdr, 0);
+    assert!(getauxval(AT_PHNUM).unwrap() > 0);
+    assert_eq!(getauxval(AT_PAGESZ), Some(4096));
+    assert_eq!(getauxval(AT_ENTRY), Some(0x10000));
+    // statically linked, no interpreter was loaded
+    assert_eq!(getauxval(AT_BASE), None);
+    let random = getauxval(AT_RANDOM).unwrap();
+    let bytes = unsafe { core::slice::from_raw_parts(random as *const u8, 16) };
+    assert!(bytes.iter().any(|byte| *byte != 0));
+    // descriptors opened with CLOEXEC are gone, the others are kept
+    let cloexec_fd: usize = argv[3].parse().unwrap();
+    let kept_fd: usize = argv[4].parse().unwrap();
+    assert_eq!(close(cloexec_fd), -EBADF);
+    assert_eq!(close(kept_fd), 0);
+    println!("args passed!");
+    0
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    assert_eq!(argc, argv.len());
+    if argc > 1 && argv[1] == "long" {
+        assert_eq!(getenv("LONG").map(|value| value.len()), Some(LONG_LEN));
+        return 0;
+    }
+    if argc > 1 {
+        return child(argv);
+    }
+    assert_eq!(argv, ["args"]);
+    // argv and envp must fit on the stack of the new program
+    let mut long_env = b"LONG=".to_vec();
+    long_env.extend(vec![b'x'; LONG_LEN]);
+    long_env.push(0);
+    let long_argv = ["args\0".as_ptr(), "long\0".as_ptr(), null()];
+    assert_eq!(execve("args\0", &long_argv, &[long_env.as_ptr(), null()]), -E2BIG);
+    // a larger RLIMIT_STACK leaves room for more
+    let pid = fork();
+    if pid == 0 {
+        let mut stack = RLimit::default();
+        getrlimit(RLIMIT_STACK, &mut stack);
+        stack.cur = 4096 * 16;
+        assert_eq!(setrlimit(RLIMIT_STACK, &stack), 0);
+        execve("args\0", &long_argv, &[long_env.as_ptr(), null()]);
+        panic!("unreachable!");
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    let cloexec_fd = open("args\0", OpenFlags::RDONLY | OpenFlags::CLOEXEC);
+    let kept_fd = open("args\0", OpenFlags::RDONLY);
+    assert!(cloexec_fd > 0 && kept_fd > 0);
+    // fds are small, one digit each
+    assert!(cloexec_fd < 10 && kept_fd < 10);
+    let cloexec_arg = [b'0' + cloexec_fd as u8, 0];
+    let kept_arg = [b'0' + kept_fd as u8, 0];
+    let pid = fork();
+    if pid == 0 {
+        execve(
+            "args\0",
+            &[
+                "args\0".as_ptr(),
+                "hello\0".as_ptr(),
+                "world\0".as_ptr(),
+                cloexec_arg.as_ptr(),
+                kept_arg.as_ptr(),
+                null(),
+            ],
+            &["GREETING=hi there\0".as_ptr(), "EMPTY=\0".as_ptr(), null()],
+        );
+        panic!("unreachable!");
+    }
+    assert_eq!(wait(&mut exit_code), pid);
+    if exit_code != 0 {
+        exit(exit_code);
+    }
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{brk, exit, fork, wait, SIGSEGV};
+
+const PAGE_SIZE: usize = 4096;
+const LEN: usize = PAGE_SIZE * 16;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let bottom = brk(0) as usize;
+    assert_eq!(brk(bottom + LEN), (bottom + LEN) as isize);
+    // every page of the new heap is allocated on its first access
+    let heap = unsafe { core::slice::from_raw_parts_mut(bottom as *mut u8, LEN) };
+    for (i, byte) in heap.iter_mut().enumerate() {
+        assert_eq!(*byte, 0);
+        *byte = i as u8;
+    }
+    for (i, byte) in heap.iter().enumerate() {
+        assert_eq!(*byte, i as u8);
+    }
+    // give back half of the heap, touching it again must kill the process
+    assert_eq!(brk(bottom + LEN / 2), (bottom + LEN / 2) as isize);
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            ((bottom + LEN / 2) as *mut u8).write_volatile(1);
+        }
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(pid, wait(&mut exit_code));
+    assert_eq!(exit_code, -(SIGSEGV as i32));
+    println!("brk_lazy passed!");
+    0
+}
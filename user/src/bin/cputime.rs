@@ -0,0 +1,119 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINVAL;
+use user_lib::{
+    clock_gettime, exit, exit_thread, fork, get_time, getrusage, sleep, thread_create,
+    thread_join, times, wait, Rusage, TimeSpec, Tms, CLOCK_MONOTONIC, CLOCK_PROCESS_CPUTIME_ID,
+    CLOCK_REALTIME, RUSAGE_CHILDREN, RUSAGE_SELF,
+};
+
+const STACK_SIZE: usize = 4096 * 2;
+
+static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+
+/// Stay in user mode for about `ms` milliseconds
+fn busy(ms: isize) {
+    let start = get_time();
+    let mut sum = 0usize;
+    while get_time() - start < ms {
+        for i in 0..10000usize {
+            sum = sum.wrapping_add(i);
+        }
+    }
+    assert_ne!(sum, 1);
+}
+
+fn busy_thread(ms: usize) -> i32 {
+    busy(ms as isize);
+    0
+}
+
+/// Reap a child busy for about 100ms, run by a thread
+fn wait_busy_child(_: usize) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        busy(100);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    0
+}
+
+fn now(clockid: usize) -> usize {
+    let mut tp = TimeSpec::default();
+    assert_eq!(clock_gettime(clockid, &mut tp), 0);
+    assert!(tp.nsec < 1_000_000_000);
+    tp.as_ns()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // monotonic clocks never go back
+    let mut last = now(CLOCK_MONOTONIC);
+    for _ in 0..100 {
+        let current = now(CLOCK_MONOTONIC);
+        assert!(current >= last);
+        last = current;
+    }
+    assert!(now(CLOCK_REALTIME) > 0);
+    assert_eq!(clock_gettime(100, &mut TimeSpec::default()), -EINVAL);
+
+    // CPU time grows while running, not while sleeping
+    let cpu_start = now(CLOCK_PROCESS_CPUTIME_ID);
+    busy(100);
+    let cpu_busy = now(CLOCK_PROCESS_CPUTIME_ID);
+    assert!(cpu_busy - cpu_start >= 50_000_000);
+    sleep(200);
+    let cpu_slept = now(CLOCK_PROCESS_CPUTIME_ID);
+    assert!(cpu_slept - cpu_busy < 100_000_000);
+
+    let mut tms = Tms::default();
+    let ticks = times(&mut tms);
+    assert!(ticks > 0);
+    assert!(tms.tms_utime >= 5);
+    assert_eq!(tms.tms_cutime, 0);
+    let mut usage = Rusage::default();
+    assert_eq!(getrusage(RUSAGE_SELF, &mut usage), 0);
+    assert!(usage.ru_utime[0] * 1_000_000 + usage.ru_utime[1] >= 50_000);
+
+    // the times of a reaped child go to its parent
+    let pid = fork();
+    if pid == 0 {
+        busy(100);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    times(&mut tms);
+    assert!(tms.tms_cutime >= 5);
+    assert_eq!(getrusage(RUSAGE_CHILDREN, &mut usage), 0);
+    assert!(usage.ru_utime[0] * 1_000_000 + usage.ru_utime[1] >= 50_000);
+    assert!(times(&mut tms) >= ticks);
+
+    // a child reaped by another thread counts for the whole process
+    let cutime = tms.tms_cutime;
+    let mut tid = 0;
+    assert!(thread_create(wait_busy_child, 0, unsafe { &mut STACK }, &mut tid) > 0);
+    thread_join(&tid);
+    times(&mut tms);
+    assert!(tms.tms_cutime - cutime >= 5);
+
+    // the first thread exits before the other one, the process is reaped
+    // after both with the time of both
+    let cutime = tms.tms_cutime;
+    let pid = fork();
+    if pid == 0 {
+        assert!(thread_create(busy_thread, 100, unsafe { &mut STACK }, &mut tid) > 0);
+        exit_thread(0);
+    }
+    assert_eq!(wait(&mut exit_code), pid);
+    times(&mut tms);
+    assert!(tms.tms_cutime - cutime >= 5);
+    println!("cputime passed!");
+    0
+}
@@ -0,0 +1,71 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{ENOENT, ENOSPC, ENOTTY, EPERM, ESPIPE};
+use user_lib::{close, open, pread, pwrite, read, tcgetpgrp, unlink, write, OpenFlags};
+
+fn open_dev(path: &str) -> usize {
+    let fd = open(path, OpenFlags::RDWR);
+    assert!(fd >= 0);
+    fd as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buf = [0xffu8; 32];
+
+    let null = open_dev("/dev/null\0");
+    assert_eq!(write(null, b"gone"), 4);
+    assert_eq!(read(null, &mut buf), 0);
+    // positioned I/O on devices other than the terminal ignores the offset
+    assert_eq!(pwrite(null, b"gone", 100), 4);
+    assert_eq!(pread(null, &mut buf, 100), 0);
+    assert_eq!(tcgetpgrp(null), -ENOTTY);
+    close(null);
+    // opening with O_CREAT, as redirections do, keeps the device
+    let null = open("/dev/null\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(null >= 0);
+    assert_eq!(write(null as usize, b"gone"), 4);
+    close(null as usize);
+
+    let zero = open_dev("/dev/zero\0");
+    assert_eq!(read(zero, &mut buf), buf.len() as isize);
+    assert!(buf.iter().all(|byte| *byte == 0));
+    assert_eq!(write(zero, b"gone"), 4);
+    buf.fill(0xff);
+    assert_eq!(pread(zero, &mut buf, 4096), buf.len() as isize);
+    assert!(buf.iter().all(|byte| *byte == 0));
+    close(zero);
+
+    let full = open_dev("/dev/full\0");
+    buf.fill(0xff);
+    assert_eq!(read(full, &mut buf), buf.len() as isize);
+    assert!(buf.iter().all(|byte| *byte == 0));
+    assert_eq!(write(full, b"no space"), -ENOSPC);
+    close(full);
+
+    let urandom = open_dev("/dev/urandom\0");
+    let mut other = [0u8; 32];
+    assert_eq!(read(urandom, &mut buf), buf.len() as isize);
+    assert_eq!(read(urandom, &mut other), other.len() as isize);
+    assert_ne!(buf, other);
+    assert_eq!(pread(urandom, &mut buf, 0), buf.len() as isize);
+    close(urandom);
+
+    // the same terminal as stdin
+    let tty = open_dev("/dev/tty\0");
+    assert_eq!(tcgetpgrp(tty), tcgetpgrp(0));
+    assert_eq!(write(tty, b"written to /dev/tty\n"), 20);
+    // not UTF-8, the console takes the bytes as they are
+    assert_eq!(write(tty, b"\xc3\n"), 2);
+    assert_eq!(pwrite(tty, b"x", 0), -ESPIPE);
+    close(tty);
+
+    assert_eq!(open("/dev/nothing\0", OpenFlags::RDONLY), -ENOENT);
+    assert_eq!(unlink("/dev/null\0"), -EPERM);
+    println!("devfs passed!");
+    0
+}
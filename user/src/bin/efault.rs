@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::slice;
+use user_lib::errno::EFAULT;
+use user_lib::{
+    close, mmap, munmap, open, pipe, read, sleep, thread_create, thread_join, write, MapFlags,
+    OpenFlags, ProtFlags,
+};
+
+/// nothing is mapped at the first pages of the address space
+const UNMAPPED: usize = 0x1000;
+const STACK_SIZE: usize = 4096 * 2;
+
+static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+/// the page main is reading into, and the page mapped after it is gone
+static mut BUFFER: usize = 0;
+static mut FRESH: usize = 0;
+
+fn map_page() -> usize {
+    let page = mmap(
+        0,
+        4096,
+        ProtFlags::READ | ProtFlags::WRITE,
+        MapFlags::PRIVATE | MapFlags::ANONYMOUS,
+        usize::MAX,
+        0,
+    );
+    assert!(page > 0);
+    page as usize
+}
+
+/// Unmap the buffer of a blocked read, map a new page and wake up the read
+fn unmap_buffer(write_fd: usize) -> i32 {
+    sleep(50);
+    assert_eq!(munmap(unsafe { BUFFER }, 4096), 0);
+    let fresh = map_page();
+    // populated now, with the frame just freed if the read let it go
+    unsafe {
+        *(fresh as *mut u8) = 0;
+        FRESH = fresh;
+    }
+    assert_eq!(write(write_fd, b"stale"), 5);
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a bad buffer fails the call instead of bringing the kernel down
+    let unmapped = unsafe { slice::from_raw_parts_mut(UNMAPPED as *mut u8, 16) };
+    assert_eq!(write(1, unmapped), -EFAULT);
+    let path = unsafe { core::str::from_utf8_unchecked(slice::from_raw_parts(UNMAPPED as *const u8, 1)) };
+    assert_eq!(open(path, OpenFlags::RDONLY), -EFAULT);
+    // a kernel address is not user memory either
+    let kernel = unsafe { slice::from_raw_parts(0xffff_ffff_ffff_f000usize as *const u8, 16) };
+    assert_eq!(write(1, kernel), -EFAULT);
+    // the code is readable but not writable
+    let fd = open("efault\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let code = unsafe { slice::from_raw_parts_mut(main as usize as *mut u8, 16) };
+    assert_eq!(read(fd as usize, code), -EFAULT);
+    assert_eq!(read(fd as usize, unmapped), -EFAULT);
+    close(fd as usize);
+
+    // a read blocked on a buffer which another thread unmaps does not write
+    // into memory handed out again
+    let mut fds = [0u32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    unsafe {
+        BUFFER = map_page();
+        *(BUFFER as *mut u8) = 1;
+    }
+    let mut tid = 0;
+    assert!(thread_create(unmap_buffer, fds[1] as usize, unsafe { &mut STACK }, &mut tid) > 0);
+    let buffer = unsafe { slice::from_raw_parts_mut(BUFFER as *mut u8, 5) };
+    assert_eq!(read(fds[0] as usize, buffer), 5);
+    thread_join(&tid);
+    let fresh = unsafe { slice::from_raw_parts(FRESH as *const u8, 5) };
+    assert_eq!(fresh, [0; 5]);
+    close(fds[0] as usize);
+    close(fds[1] as usize);
+    println!("efault passed!");
+    0
+}
@@ -0,0 +1,171 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr::null;
+use user_lib::errno::ENOEXEC;
+use user_lib::{close, exec, fork, open, waitpid, write, OpenFlags};
+
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+const PF_R: u32 = 4;
+const PF_X: u32 = 1;
+/// where the kernel loads the dynamic linker
+const ELF_INTERP_BASE: u64 = 0x20_0000_0000;
+
+/// `exit(a0)`
+const EXIT: [u32; 2] = [
+    0x05d00893, // li a7, 93
+    0x00000073, // ecall
+];
+
+/// Copy `bytes` into `image` at `offset`
+fn put(image: &mut [u8], offset: usize, bytes: &[u8]) {
+    image[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+/// A RISC-V ELF image of `size` bytes with `phnum` program headers
+fn elf_image(size: usize, type_: u16, entry: u64, phnum: u16) -> Vec<u8> {
+    let mut image = vec![0u8; size];
+    put(&mut image, 0, &[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    put(&mut image, 16, &type_.to_le_bytes());
+    put(&mut image, 18, &0xf3u16.to_le_bytes());
+    put(&mut image, 20, &1u32.to_le_bytes());
+    put(&mut image, 24, &entry.to_le_bytes());
+    put(&mut image, 32, &64u64.to_le_bytes());
+    put(&mut image, 52, &64u16.to_le_bytes());
+    put(&mut image, 54, &56u16.to_le_bytes());
+    put(&mut image, 56, &phnum.to_le_bytes());
+    image
+}
+
+/// Fill in program header `index`, a segment of `size` bytes in the file and in memory
+fn program_header(image: &mut [u8], index: usize, type_: u32, flags: u32, offset: u64, vaddr: u64, size: u64) {
+    let base = 64 + 56 * index;
+    put(image, base, &type_.to_le_bytes());
+    put(image, base + 4, &flags.to_le_bytes());
+    for (field, value) in [offset, vaddr, vaddr, size, size, 0x1000].iter().enumerate() {
+        put(image, base + 8 + field * 8, &value.to_le_bytes());
+    }
+}
+
+/// Place the instructions `insns` at `offset`
+fn code(image: &mut [u8], offset: usize, insns: &[u32]) {
+    for (i, insn) in insns.iter().enumerate() {
+        put(image, offset + i * 4, &insn.to_le_bytes());
+    }
+}
+
+/// A position-independent executable exiting with 0 once it finds that the
+/// word at 0x130 was relocated to its own runtime address
+fn pie() -> Vec<u8> {
+    let mut image = elf_image(0x138, ET_DYN, 0x110, 2);
+    program_header(&mut image, 0, PT_LOAD, PF_R | PF_X, 0, 0, 0x138);
+    program_header(&mut image, 1, PT_DYNAMIC, PF_R, 0xb0, 0xb0, 0x40);
+    // DT_RELA, DT_RELASZ, DT_RELAENT, DT_NULL
+    for (i, (tag, value)) in [(7u64, 0xf0u64), (8, 24), (9, 24), (0, 0)].iter().enumerate() {
+        put(&mut image, 0xb0 + i * 16, &tag.to_le_bytes());
+        put(&mut image, 0xb8 + i * 16, &value.to_le_bytes());
+    }
+    // R_RISCV_RELATIVE of the word at 0x130, addend 0x130
+    put(&mut image, 0xf0, &0x130u64.to_le_bytes());
+    put(&mut image, 0xf8, &3u64.to_le_bytes());
+    put(&mut image, 0x100, &0x130u64.to_le_bytes());
+    code(&mut image, 0x110, &[
+        0x00000517, // auipc a0, 0
+        0x02050513, // addi a0, a0, 0x20
+        0x00053583, // ld a1, 0(a0)
+        0x40b50533, // sub a0, a0, a1
+        0x00a03533, // snez a0, a0
+    ]);
+    code(&mut image, 0x124, &EXIT);
+    image
+}
+
+/// A program at 0x10000 asking for `interp` as its dynamic linker
+fn dynamic(interp: &str) -> Vec<u8> {
+    dynamic_at(interp, 0x10000)
+}
+
+/// A program at `base` asking for `interp` as its dynamic linker
+fn dynamic_at(interp: &str, base: u64) -> Vec<u8> {
+    let size = 0xb0 + interp.len();
+    let mut image = elf_image(size, ET_EXEC, base, 2);
+    program_header(&mut image, 0, PT_INTERP, PF_R, 0xb0, base + 0xb0, interp.len() as u64);
+    program_header(&mut image, 1, PT_LOAD, PF_R, 0, base, size as u64);
+    put(&mut image, 0xb0, interp.as_bytes());
+    image
+}
+
+/// A program whose two segments share the page at 0x10000
+fn overlapping() -> Vec<u8> {
+    let mut image = elf_image(0x200, ET_EXEC, 0x10000, 2);
+    program_header(&mut image, 0, PT_LOAD, PF_R | PF_X, 0, 0x10000, 0x100);
+    program_header(&mut image, 1, PT_LOAD, PF_R, 0x100, 0x10800, 0x100);
+    code(&mut image, 0x78, &EXIT);
+    image
+}
+
+/// A dynamic linker exiting with the second byte of the program at 0x10000,
+/// the 'E' of its ELF magic
+fn interpreter() -> Vec<u8> {
+    let mut image = elf_image(0x88, ET_DYN, 0x78, 1);
+    program_header(&mut image, 0, PT_LOAD, PF_R | PF_X, 0, 0, 0x88);
+    code(&mut image, 0x78, &[
+        0x000105b7, // lui a1, 0x10
+        0x0015c503, // lbu a0, 1(a1)
+    ]);
+    code(&mut image, 0x80, &EXIT);
+    image
+}
+
+/// Write `content` to a new file at `path`
+fn create(path: &str, content: &[u8]) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, content), content.len() as isize);
+    close(fd as usize);
+}
+
+/// Exit code of `path` run in a child
+fn run(path: &str) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        exec(path, &[path.as_ptr(), null()]);
+        panic!("unreachable!");
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    create("elf_load_pie\0", &pie());
+    assert_eq!(run("elf_load_pie\0"), 0);
+    create("elf_load_interp\0", &interpreter());
+    create("elf_load_dynamic\0", &dynamic("/elf_load_interp\0"));
+    assert_eq!(run("elf_load_dynamic\0"), b'E' as i32);
+    // the interpreter must be an ELF image itself
+    create("elf_load_bad_interp\0", b"#!/elf_load_pie\n");
+    create("elf_load_dynamic_bad\0", &dynamic("/elf_load_bad_interp\0"));
+    let path = "elf_load_dynamic_bad\0";
+    assert_eq!(exec(path, &[path.as_ptr(), null()]), -ENOEXEC);
+    // segments in the same page, and a stack running into the dynamic linker
+    create("elf_load_overlapping\0", &overlapping());
+    let path = "elf_load_overlapping\0";
+    assert_eq!(exec(path, &[path.as_ptr(), null()]), -ENOEXEC);
+    create("elf_load_dynamic_high\0", &dynamic_at("/elf_load_interp\0", ELF_INTERP_BASE - 0x1000));
+    let path = "elf_load_dynamic_high\0";
+    assert_eq!(exec(path, &[path.as_ptr(), null()]), -ENOEXEC);
+    println!("elf_load passed!");
+    0
+}
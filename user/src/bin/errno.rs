@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use user_lib::errno::{EBADF, ECHILD, EINVAL, ENOENT, ENOMEM, ENOSYS, ESRCH};
+use user_lib::{close, kill, mmap, open, read, wait, write, MapFlags, OpenFlags, ProtFlags};
+
+/// a system call number the kernel does not know
+const SYSCALL_UNKNOWN: usize = 4095;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut ret: isize;
+    unsafe {
+        asm!("ecall", lateout("x10") ret, in("x17") SYSCALL_UNKNOWN);
+    }
+    assert_eq!(ret, -ENOSYS);
+    assert_eq!(open("errno_no_such_file\0", OpenFlags::RDONLY), -ENOENT);
+    assert_eq!(close(100), -EBADF);
+    assert_eq!(read(100, &mut [0u8; 4]), -EBADF);
+    assert_eq!(write(100, b"lost"), -EBADF);
+    assert_eq!(wait(&mut 0), -ECHILD);
+    assert_eq!(kill(100000, 0), -ESRCH);
+    // an unaligned address used to bring the kernel down
+    let start = mmap(
+        4097,
+        4096,
+        ProtFlags::READ | ProtFlags::WRITE,
+        MapFlags::PRIVATE | MapFlags::ANONYMOUS,
+        usize::MAX,
+        0,
+    );
+    assert_eq!(start, -EINVAL);
+    // ranges wrapping around or reaching the trampolines at the top
+    for (start, len) in [(usize::MAX & !4095, 8192), (1 << 38, 4096), (0x3f_ffff_f000, 8192)] {
+        let ret = mmap(
+            start,
+            len,
+            ProtFlags::READ | ProtFlags::WRITE,
+            MapFlags::PRIVATE | MapFlags::ANONYMOUS | MapFlags::FIXED,
+            usize::MAX,
+            0,
+        );
+        assert_eq!(ret, -ENOMEM);
+    }
+    // what is written to the console need not be UTF-8
+    let bytes = b"bytes \xff\xfe\xe4\xbd are no UTF-8\n";
+    assert_eq!(write(1, bytes), bytes.len() as isize);
+    println!("errno passed!");
+    0
+}
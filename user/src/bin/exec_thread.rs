@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use core::ptr::null;
+use user_lib::{
+    close, exec, fork, getpid, pipe, read, sleep, thread_create, waitpid, waitpid_with, WNOHANG,
+};
+
+const STACK_SIZE: usize = 4096 * 2;
+
+static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+/// write end of the pipe, the parent sees EOF once every copy is closed
+static mut WRITE_FD: usize = 0;
+
+/// Hold the fd table of the old program until killed
+fn idle(_: usize) -> i32 {
+    loop {
+        sleep(10);
+    }
+}
+
+/// Run this program again as the new image of the process
+fn exec_image(_: usize) -> i32 {
+    let fd = format!("{}\0", unsafe { WRITE_FD });
+    let pid = format!("{}\0", getpid());
+    let path = "exec_thread\0";
+    exec(path, &[path.as_ptr(), "image\0".as_ptr(), fd.as_ptr(), pid.as_ptr(), null()]);
+    panic!("unreachable!");
+}
+
+/// `execve` from the leader if `leader`, otherwise from the other thread
+fn run(leader: bool) {
+    let mut fds = [0u32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0] as usize);
+        unsafe {
+            WRITE_FD = fds[1] as usize;
+        }
+        let mut tid = 0;
+        if leader {
+            assert!(thread_create(idle, 0, unsafe { &mut STACK }, &mut tid) > 0);
+            exec_image(0);
+        } else {
+            assert!(thread_create(exec_image, 0, unsafe { &mut STACK }, &mut tid) > 0);
+            idle(0);
+        }
+    }
+    close(fds[1] as usize);
+    // the new image closes its copy, the old threads must be gone with theirs
+    let mut buf = [0u8; 1];
+    assert_eq!(read(fds[0] as usize, &mut buf), 0);
+    close(fds[0] as usize);
+    let mut exit_code: i32 = 0;
+    // while the new image is still running
+    assert_eq!(waitpid_with(pid, &mut exit_code, WNOHANG), 0);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 {
+        // the new image, in the same process
+        assert_eq!(argv[1], "image");
+        assert_eq!(argv[3].parse::<isize>().unwrap(), getpid());
+        close(argv[2].parse().unwrap());
+        sleep(100);
+        return 7;
+    }
+    run(true);
+    run(false);
+    println!("exec_thread passed!");
+    0
+}
@@ -0,0 +1,125 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::EINTR;
+use user_lib::{
+    close, exit, fork, futex_wait, kill, pipe, read, sigaction, sleep, thread_create, waitpid, write,
+    SignalAction, SIGCHLD, SIGKILL, SIGUSR1,
+};
+
+const STACK_SIZE: usize = 4096 * 2;
+
+static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+/// nobody wakes up the waiters of this futex
+static NEVER: u32 = 1;
+static mut GOT_SIGCHLD: bool = false;
+
+extern "C" fn on_sigchld(_signum: usize) {
+    unsafe {
+        GOT_SIGCHLD = true;
+    }
+}
+
+fn exit_process(code: usize) -> i32 {
+    exit(code as i32);
+}
+
+/// Give the main thread time to block first
+fn exit_process_later(code: usize) -> i32 {
+    sleep(50);
+    exit(code as i32);
+}
+
+extern "C" fn on_sigusr1(_signum: usize) {}
+
+/// Fork a child which blocks reading a pipe nobody writes to after running
+/// `before`, the parent goes on once it is about to read
+fn child_reading_pipe(before: fn()) -> isize {
+    let mut fds = [0u32; 2];
+    let mut ready = [0u32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(pipe(&mut ready), 0);
+    let pid = fork();
+    if pid == 0 {
+        before();
+        assert_eq!(write(ready[1] as usize, b"r"), 1);
+        let mut buf = [0u8; 1];
+        let len = read(fds[0] as usize, &mut buf);
+        exit(if len == -EINTR { 0 } else { 1 });
+    }
+    // the child keeps the write end open
+    let mut buf = [0u8; 1];
+    assert_eq!(read(ready[0] as usize, &mut buf), 1);
+    for fd in fds.iter().chain(ready.iter()) {
+        close(*fd as usize);
+    }
+    // let it block in read
+    sleep(20);
+    pid
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a thread ends the whole process, the main thread waiting forever too
+    let pid = fork();
+    if pid == 0 {
+        let mut tid = 0u32;
+        assert!(thread_create(exit_process, 7, unsafe { &mut STACK }, &mut tid) > 0);
+        loop {
+            futex_wait(&NEVER, 1, None);
+        }
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+
+    // a thread blocked reading a pipe is ended by exit_group and SIGKILL,
+    // and a caught signal interrupts the read with EINTR
+    let pid = child_reading_pipe(|| {
+        let mut tid = 0u32;
+        assert!(thread_create(exit_process_later, 8, unsafe { &mut STACK }, &mut tid) > 0);
+    });
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 8);
+    let pid = child_reading_pipe(|| {});
+    assert_eq!(kill(pid, SIGKILL), 0);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -(SIGKILL as i32));
+    let pid = child_reading_pipe(|| {
+        let action = SignalAction {
+            handler: on_sigusr1 as usize,
+            ..Default::default()
+        };
+        assert_eq!(sigaction(SIGUSR1, Some(&action), None), 0);
+    });
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // the files of a child are closed when it exits, before it is reaped
+    let action = SignalAction {
+        handler: on_sigchld as usize,
+        ..Default::default()
+    };
+    assert_eq!(sigaction(SIGCHLD, Some(&action), None), 0);
+    let mut fds = [0u32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        // leave the write end open
+        close(fds[0] as usize);
+        exit(0);
+    }
+    close(fds[1] as usize);
+    let mut buf = [0u8; 1];
+    assert_eq!(read(fds[0] as usize, &mut buf), 0);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // the parent was told with SIGCHLD
+    assert!(unsafe { core::ptr::read_volatile(&GOT_SIGCHLD) });
+    println!("exit_group passed!");
+    0
+}
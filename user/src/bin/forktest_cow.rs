@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, wait};
+
+const LEN: usize = 4096 * 4;
+
+static mut DATA: [u8; LEN] = [1; LEN];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut stack_value: usize = 7;
+    let pid = fork();
+    if pid == 0 {
+        // child process: every store lands on a page shared with the parent
+        unsafe {
+            for i in 0..LEN {
+                DATA[i] = 2;
+            }
+        }
+        stack_value = 8;
+        assert_eq!(stack_value, 8);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(pid, wait(&mut exit_code));
+    assert_eq!(exit_code, 0);
+    // the parent must not observe the writes of its child
+    unsafe {
+        for i in 0..LEN {
+            assert_eq!(DATA[i], 1);
+        }
+    }
+    assert_eq!(stack_value, 7);
+    println!("forktest_cow passed!");
+    0
+}
@@ -0,0 +1,108 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use user_lib::errno::{EAGAIN, EINVAL, ETIMEDOUT};
+use user_lib::{
+    exit, fork, futex_wait, futex_wait_timespec, futex_wake, get_time, mmap, munmap, sleep,
+    thread_create, thread_join, wait, MapFlags, ProtFlags, TimeSpec,
+};
+
+const THREAD_NUM: usize = 4;
+const STACK_SIZE: usize = 4096 * 2;
+const ROUNDS: usize = 500;
+
+static mut STACKS: [[u8; STACK_SIZE]; THREAD_NUM] = [[0; STACK_SIZE]; THREAD_NUM];
+/// 0: unlocked, 1: locked, 2: locked with waiters
+static LOCK: AtomicU32 = AtomicU32::new(0);
+static mut COUNTER: usize = 0;
+
+/// The futex word behind an atomic
+fn word(atomic: &AtomicU32) -> &u32 {
+    unsafe { &*(atomic as *const AtomicU32 as *const u32) }
+}
+
+fn lock() {
+    if LOCK.compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+        return;
+    }
+    while LOCK.swap(2, Ordering::Acquire) != 0 {
+        futex_wait(word(&LOCK), 2, None);
+    }
+}
+
+fn unlock() {
+    if LOCK.swap(0, Ordering::Release) == 2 {
+        futex_wake(word(&LOCK), 1);
+    }
+}
+
+fn worker(_: usize) -> i32 {
+    for _ in 0..ROUNDS {
+        lock();
+        unsafe {
+            let value = (&COUNTER as *const usize).read_volatile();
+            (&mut COUNTER as *mut usize).write_volatile(value + 1);
+        }
+        unlock();
+    }
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the word does not match, return at once
+    let value: u32 = 0;
+    assert_eq!(futex_wait(&value, 1, None), -EAGAIN);
+    // nobody wakes us up
+    let start = get_time();
+    assert_eq!(futex_wait(&value, 0, Some(100)), -ETIMEDOUT);
+    assert!(get_time() - start >= 100);
+    assert_eq!(futex_wake(&value, 1), 0);
+    // a malformed timeout
+    let timeout = TimeSpec { sec: 0, nsec: 1_000_000_000 };
+    assert_eq!(futex_wait_timespec(&value, 0, Some(&timeout)), -EINVAL);
+    let timeout = TimeSpec { sec: -1isize as usize, nsec: 0 };
+    assert_eq!(futex_wait_timespec(&value, 0, Some(&timeout)), -EINVAL);
+
+    // threads of one process
+    let mut tids = [0u32; THREAD_NUM];
+    for (id, tid) in tids.iter_mut().enumerate() {
+        assert!(thread_create(worker, id, unsafe { &mut STACKS[id] }, tid) > 0);
+    }
+    for tid in tids.iter() {
+        thread_join(tid);
+    }
+    assert_eq!(unsafe { COUNTER }, THREAD_NUM * ROUNDS);
+
+    // processes sharing a page
+    let page = mmap(
+        0,
+        4096,
+        ProtFlags::READ | ProtFlags::WRITE,
+        MapFlags::SHARED | MapFlags::ANONYMOUS,
+        usize::MAX,
+        0,
+    );
+    assert!(page > 0);
+    let shared = unsafe { &*(page as *const AtomicU32) };
+    let pid = fork();
+    if pid == 0 {
+        while shared.load(Ordering::Acquire) == 0 {
+            futex_wait(word(shared), 0, None);
+        }
+        exit(0);
+    }
+    sleep(50);
+    shared.store(1, Ordering::Release);
+    futex_wake(word(shared), 1);
+    let mut exit_code: i32 = 0;
+    assert_eq!(pid, wait(&mut exit_code));
+    assert_eq!(exit_code, 0);
+    assert_eq!(munmap(page as usize, 4096), 0);
+    println!("futex passed!");
+    0
+}
@@ -0,0 +1,125 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use user_lib::errno::{EINVAL, ENOMEM};
+use user_lib::{
+    close, exit, fork, mmap, msync, munmap, open, pipe, read, wait, write, MapFlags, OpenFlags,
+    ProtFlags,
+};
+
+const LEN: usize = 4096 * 2;
+/// the mapped file ends inside the second page
+const FILE_SIZE: usize = 5000;
+
+/// Contents of the file at `path`
+fn read_file(path: &str) -> Vec<u8> {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut content = vec![0u8; LEN];
+    let len = read(fd as usize, &mut content);
+    assert!(len >= 0);
+    content.truncate(len as usize);
+    close(fd as usize);
+    content
+}
+
+/// Stores to a shared file mapping reach the file on msync and munmap
+fn file_mapping() {
+    let path = "mmap_shared_file\0";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, &vec![b'a'; FILE_SIZE]), FILE_SIZE as isize);
+    let start = mmap(0, LEN, ProtFlags::READ | ProtFlags::WRITE, MapFlags::SHARED, fd as usize, 0);
+    assert!(start > 0);
+    close(fd as usize);
+    let start = start as usize;
+    let mapped = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, LEN) };
+    assert!(mapped[..FILE_SIZE].iter().all(|byte| *byte == b'a'));
+    assert!(mapped[FILE_SIZE..].iter().all(|byte| *byte == 0));
+    mapped[..10].fill(b'b');
+    mapped[4096..4100].fill(b'c');
+    // past the end of the file, not written back
+    mapped[LEN - 1] = b'x';
+    assert_eq!(msync(start + 1, 10), -EINVAL);
+    assert_eq!(msync(start, LEN), 0);
+    let content = read_file(path);
+    assert_eq!(content.len(), FILE_SIZE);
+    assert_eq!(&content[..11], b"bbbbbbbbbba");
+    assert_eq!(&content[4095..4101], b"acccca");
+    mapped[1] = b'd';
+    assert_eq!(munmap(start, LEN), 0);
+    assert_eq!(read_file(path)[..3], *b"bdb");
+    // nothing is mapped there any more
+    assert_eq!(msync(start, LEN), -ENOMEM);
+}
+
+/// Pages first touched after fork are shared as well
+fn touched_after_fork() {
+    let start = mmap(
+        0,
+        LEN,
+        ProtFlags::READ | ProtFlags::WRITE,
+        MapFlags::SHARED | MapFlags::ANONYMOUS,
+        usize::MAX,
+        0,
+    );
+    assert!(start > 0);
+    let shared = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, LEN) };
+    let mut fds = [0u32; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[1] as usize);
+        // the parent has written the second page
+        let mut buf = [0u8; 1];
+        assert_eq!(read(fds[0] as usize, &mut buf), 1);
+        assert!(shared[4096..].iter().all(|byte| *byte == 3));
+        shared[..4096].fill(2);
+        exit(0);
+    }
+    close(fds[0] as usize);
+    shared[4096..].fill(3);
+    assert_eq!(write(fds[1] as usize, b"w"), 1);
+    close(fds[1] as usize);
+    let mut exit_code: i32 = 0;
+    assert_eq!(pid, wait(&mut exit_code));
+    assert_eq!(exit_code, 0);
+    assert!(shared[..4096].iter().all(|byte| *byte == 2));
+    assert_eq!(munmap(start as usize, LEN), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = mmap(
+        0,
+        LEN,
+        ProtFlags::READ | ProtFlags::WRITE,
+        MapFlags::SHARED | MapFlags::ANONYMOUS,
+        usize::MAX,
+        0,
+    );
+    assert!(start > 0);
+    let shared = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, LEN) };
+    shared.fill(1);
+    let pid = fork();
+    if pid == 0 {
+        // stores of the child are visible to the parent
+        shared.fill(2);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(pid, wait(&mut exit_code));
+    assert_eq!(exit_code, 0);
+    assert!(shared.iter().all(|byte| *byte == 2));
+    assert_eq!(munmap(start as usize, LEN), 0);
+    touched_after_fork();
+    file_mapping();
+    println!("mmap_shared passed!");
+    0
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EBUSY, EINVAL, ENODEV, ENOENT};
+use user_lib::{close, mkdir, mount, open, read, umount, OpenFlags};
+
+/// Whether `path` opens and starts with the ELF magic
+fn is_elf(path: &str) -> bool {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return false;
+    }
+    let mut magic = [0u8; 4];
+    let len = read(fd as usize, &mut magic);
+    close(fd as usize);
+    len == 4 && magic == *b"\x7fELF"
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("/mnt\0"), 0);
+    assert_eq!(mount("/dev/vda2\0", "/mnt\0", "nofs\0", 0), -ENODEV);
+    assert_eq!(mount("/dev/vda2\0", "/no_such_dir\0", "vfat\0", 0), -ENOENT);
+
+    // the disk mounted again at /mnt, its files show up below it
+    assert!(!is_elf("/mnt/initproc\0"));
+    assert_eq!(mount("/dev/vda2\0", "/mnt\0", "vfat\0", 0), 0);
+    assert_eq!(mount("/dev/vda2\0", "/mnt\0", "vfat\0", 0), -EBUSY);
+    assert!(is_elf("/mnt/initproc\0"));
+    assert!(is_elf("/mnt/./../mnt/initproc\0"));
+
+    assert_eq!(umount("/\0"), -EBUSY);
+    assert_eq!(umount("/mnt\0"), 0);
+    assert!(!is_elf("/mnt/initproc\0"));
+    assert_eq!(umount("/mnt\0"), -EINVAL);
+    println!("mount passed!");
+    0
+}
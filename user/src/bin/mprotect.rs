@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, mmap, mprotect, wait, MapFlags, ProtFlags, SIGSEGV};
+
+const PAGE_SIZE: usize = 4096;
+const LEN: usize = PAGE_SIZE * 3;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = mmap(
+        0,
+        LEN,
+        ProtFlags::READ | ProtFlags::WRITE,
+        MapFlags::PRIVATE | MapFlags::ANONYMOUS,
+        usize::MAX,
+        0,
+    );
+    assert!(start > 0);
+    let start = start as usize;
+    let area = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, LEN) };
+    area.fill(3);
+    // only the middle page becomes read-only
+    assert_eq!(mprotect(start + PAGE_SIZE, PAGE_SIZE, ProtFlags::READ), 0);
+    assert!(area.iter().all(|byte| *byte == 3));
+    area[0] = 4;
+    area[2 * PAGE_SIZE] = 4;
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            ((start + PAGE_SIZE) as *mut u8).write_volatile(5);
+        }
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(pid, wait(&mut exit_code));
+    assert_eq!(exit_code, -(SIGSEGV as i32));
+    assert_eq!(area[PAGE_SIZE], 3);
+    assert_eq!(mprotect(start + PAGE_SIZE, PAGE_SIZE, ProtFlags::READ | ProtFlags::WRITE), 0);
+    area[PAGE_SIZE] = 5;
+    assert_eq!(area[PAGE_SIZE], 5);
+    // write-only is readable as well
+    assert_eq!(mprotect(start + PAGE_SIZE, PAGE_SIZE, ProtFlags::WRITE), 0);
+    area[PAGE_SIZE] = 6;
+    assert_eq!(area[PAGE_SIZE], 6);
+    println!("mprotect passed!");
+    0
+}
@@ -0,0 +1,105 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EIO, EPERM, ESRCH};
+use user_lib::{
+    exit, fork, getpgid, getpid, getsid, kill, read, setpgid, setsid, sigaction, sleep, tcgetpgrp, tcsetpgrp,
+    wait4, waitpid, wifstopped, wstopsig, SignalAction, SIGCONT, SIGSTOP, SIGTERM, SIGTTIN, SIGUSR1, SIG_IGN,
+    WUNTRACED,
+};
+
+/// no process or group has this id
+const NO_SUCH_ID: usize = 99999;
+
+fn idle() -> ! {
+    loop {
+        sleep(10);
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    let pgid = getpgid(0);
+    assert!(pgid > 0);
+    assert_eq!(getpgid(pid), pgid);
+    assert!(getsid(0) > 0);
+    assert_eq!(getpgid(NO_SUCH_ID), -ESRCH);
+    assert_eq!(setpgid(0, NO_SUCH_ID), -EPERM);
+    assert_eq!(kill(-(NO_SUCH_ID as isize), 0), -ESRCH);
+    assert_eq!(tcsetpgrp(0, NO_SUCH_ID), -ESRCH);
+    assert!(tcgetpgrp(0) > 0);
+
+    // a new session can't take in processes of another one
+    let child = fork();
+    if child == 0 {
+        let me = getpid();
+        assert_eq!(setsid(), me);
+        assert_eq!(getsid(0), me);
+        assert_eq!(getpgid(0), me);
+        // already leading its group
+        assert_eq!(setsid(), -EPERM);
+        assert_eq!(setpgid(0, pgid as usize), -EPERM);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+
+    // a group of two killed at once
+    let leader = fork();
+    if leader == 0 {
+        setpgid(0, 0);
+        idle();
+    }
+    assert_eq!(setpgid(leader as usize, 0), 0);
+    assert_eq!(getpgid(leader as usize), leader);
+    let member = fork();
+    if member == 0 {
+        assert_eq!(setpgid(0, leader as usize), 0);
+        idle();
+    }
+    assert_eq!(setpgid(member as usize, leader as usize), 0);
+    assert_eq!(kill(-leader, SIGUSR1), 0);
+    for pid in [leader, member] {
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, -(SIGUSR1 as i32));
+    }
+
+    // reading the terminal from the background fails when SIGTTIN can not
+    // stop the reader
+    let child = fork();
+    if child == 0 {
+        assert_eq!(setpgid(0, 0), 0);
+        let action = SignalAction {
+            handler: SIG_IGN,
+            ..Default::default()
+        };
+        assert_eq!(sigaction(SIGTTIN, Some(&action), None), 0);
+        let mut buf = [0u8; 1];
+        assert_eq!(read(0, &mut buf), -EIO);
+        exit(0);
+    }
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+
+    // stopped, reported by WUNTRACED, continued and killed
+    let child = fork();
+    if child == 0 {
+        idle();
+    }
+    assert_eq!(kill(child, SIGSTOP), 0);
+    let mut status: i32 = 0;
+    assert_eq!(wait4(child, &mut status, WUNTRACED), child);
+    assert!(wifstopped(status));
+    assert_eq!(wstopsig(status), SIGSTOP);
+    assert_eq!(kill(child, SIGCONT), 0);
+    assert_eq!(kill(child, SIGTERM), 0);
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, -(SIGTERM as i32));
+    println!("pgrp passed!");
+    0
+}
@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EINVAL, ESRCH};
+use user_lib::{exit, fork, getpid, getpriority, setpriority, wait, yield_, PRIO_PROCESS};
+
+const CHILDREN: usize = 4;
+const ROUNDS: usize = 20000;
+
+fn spin(rounds: usize) -> usize {
+    let mut sum = 0usize;
+    for i in 0..rounds {
+        sum = sum.wrapping_add(i * i);
+        if i % 5000 == 0 {
+            yield_();
+        }
+    }
+    sum
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a new process has nice 0
+    assert_eq!(getpriority(PRIO_PROCESS, 0), 20);
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 5), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, getpid() as usize), 15);
+    // out of range values are clamped
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 100), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, 0), 1);
+    assert_eq!(setpriority(PRIO_PROCESS, 0, -100), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, 0), 40);
+    // only PRIO_PROCESS is supported
+    assert_eq!(getpriority(1, 0), -EINVAL);
+    assert_eq!(setpriority(2, 0, 0), -EINVAL);
+    // no such process
+    assert_eq!(getpriority(PRIO_PROCESS, 100000), -ESRCH);
+
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 3), 0);
+    let pid = fork();
+    if pid == 0 {
+        // inherited from the parent
+        exit(if getpriority(PRIO_PROCESS, 0) == 17 { 0 } else { 1 });
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 0), 0);
+
+    // processes of every priority finish, whatever the scheduler is
+    for i in 0..CHILDREN {
+        let pid = fork();
+        if pid == 0 {
+            setpriority(PRIO_PROCESS, 0, i as isize * 10 - 20);
+            spin(ROUNDS);
+            exit(0);
+        }
+        assert!(pid > 0);
+    }
+    for _ in 0..CHILDREN {
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    println!("priority passed!");
+    0
+}
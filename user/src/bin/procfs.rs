@@ -0,0 +1,94 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::errno::ENOENT;
+use user_lib::{close, exit, fork, getdents, getpid, open, read, sleep, waitpid, Dirent, OpenFlags};
+
+/// The content of `path` as text
+fn read_file(path: &str) -> String {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut data = Vec::new();
+    let mut buf = [0u8; 64];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..len as usize]);
+    }
+    close(fd as usize);
+    String::from_utf8(data).unwrap()
+}
+
+/// The names in the directory `path`
+fn list_dir(path: &str) -> Vec<String> {
+    let fd = open(path, OpenFlags::RDONLY | OpenFlags::DIRECTORY);
+    assert!(fd >= 0);
+    let mut names = Vec::new();
+    let mut dirents = [Dirent::empty(); 4];
+    loop {
+        let count = getdents(fd as usize, &mut dirents);
+        assert!(count >= 0);
+        if count == 0 {
+            break;
+        }
+        names.extend(dirents[..count as usize].iter().map(|dirent| String::from(dirent.name())));
+    }
+    close(fd as usize);
+    names
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    let names = list_dir("/proc\0");
+    for name in ["meminfo", "mounts", "uptime"] {
+        assert!(names.iter().any(|entry| entry == name));
+    }
+    assert!(names.contains(&format!("{}", pid)));
+    assert_eq!(
+        list_dir(&format!("/proc/{}\0", pid)),
+        ["stat", "status", "cmdline", "cwd", "maps"]
+    );
+
+    let status = read_file(&format!("/proc/{}/status\0", pid));
+    assert!(status.starts_with("Name:\tprocfs\n"));
+    assert!(status.contains(&format!("\nPid:\t{}\n", pid)));
+    assert!(status.contains("\nThreads:\t1\n"));
+    assert_eq!(read_file(&format!("/proc/{}/cmdline\0", pid)), "procfs\0");
+    assert_eq!(read_file(&format!("/proc/{}/cwd\0", pid)), "/\n");
+    let stat = read_file(&format!("/proc/{}/stat\0", pid));
+    assert!(stat.starts_with(&format!("{} (procfs) R ", pid)));
+    let maps = read_file(&format!("/proc/{}/maps\0", pid));
+    assert!(maps.lines().any(|map| map.ends_with("[stack]")));
+    assert!(maps.lines().any(|map| map.contains(" r-xp ")));
+
+    assert!(read_file("/proc/meminfo\0").starts_with("MemTotal:"));
+    let mounts = read_file("/proc/mounts\0");
+    assert!(mounts.starts_with("/dev/vda / vfat "));
+    assert!(mounts.contains(" /proc proc "));
+    assert!(read_file("/proc/uptime\0").ends_with(" 0.00\n"));
+
+    // a sleeping child, gone once it has been waited for
+    let child = fork();
+    if child == 0 {
+        sleep(100);
+        exit(0);
+    }
+    let status = read_file(&format!("/proc/{}/status\0", child));
+    assert!(status.contains(&format!("\nPPid:\t{}\n", pid)));
+    let mut exit_code = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(open(&format!("/proc/{}/stat\0", child), OpenFlags::RDONLY), -ENOENT);
+    println!("procfs passed!");
+    0
+}
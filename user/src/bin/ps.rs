@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::{close, getdents, open, read, Dirent, OpenFlags};
+
+/// The content of `path`, `None` if it can not be opened
+fn read_file(path: &str) -> Option<Vec<u8>> {
+    let fd = open(path, OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut data = Vec::new();
+    let mut buf = [0u8; 256];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..len as usize]);
+    }
+    close(fd as usize);
+    Some(data)
+}
+
+/// The pids in /proc, in ascending order
+fn pids() -> Vec<usize> {
+    let fd = open("/proc\0", OpenFlags::RDONLY | OpenFlags::DIRECTORY);
+    assert!(fd >= 0, "ps: /proc is not mounted");
+    let mut pids = Vec::new();
+    let mut dirents = [Dirent::empty(); 8];
+    loop {
+        let count = getdents(fd as usize, &mut dirents);
+        if count <= 0 {
+            break;
+        }
+        for dirent in &dirents[..count as usize] {
+            if let Ok(pid) = dirent.name().parse() {
+                pids.push(pid);
+            }
+        }
+    }
+    close(fd as usize);
+    pids.sort();
+    pids
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("{:>5} {:>5} {:>5} S {:>8} CMD", "PID", "PPID", "PGID", "RSS");
+    for pid in pids() {
+        // 进程可能在读取时退出
+        let stat = match read_file(&format!("/proc/{}/stat\0", pid)) {
+            Some(stat) if !stat.is_empty() => String::from_utf8_lossy(&stat).into_owned(),
+            _ => continue,
+        };
+        // comm 在括号中，可能带空格
+        let comm_end = stat.rfind(')').unwrap();
+        let comm = &stat[stat.find('(').unwrap() + 1..comm_end];
+        let fields: Vec<&str> = stat[comm_end + 1..].split_whitespace().collect();
+        // fields[0] 是第 3 项 state
+        let (state, ppid, pgid, rss) = (fields[0], fields[1], fields[2], fields[21]);
+        let cmdline = read_file(&format!("/proc/{}/cmdline\0", pid)).unwrap_or_default();
+        let args: Vec<String> = cmdline
+            .split(|byte| *byte == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+        let cmd = if args.is_empty() { format!("[{}]", comm) } else { args.join(" ") };
+        println!("{:>5} {:>5} {:>5} {} {:>8} {}", pid, ppid, pgid, state, rss, cmd);
+    }
+    0
+}
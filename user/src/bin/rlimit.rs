@@ -0,0 +1,128 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use core::ptr::null;
+use user_lib::errno::{EAGAIN, EINVAL, EMFILE, ENOMEM, EPERM, ESRCH};
+use user_lib::{
+    brk, close, exec, exit, fork, getpid, getrlimit, mmap, open, prlimit, read, setrlimit, wait, MapFlags,
+    OpenFlags, ProtFlags, RLimit, RLIMIT_AS, RLIMIT_DATA, RLIMIT_NOFILE, RLIMIT_NPROC, RLIMIT_STACK, RLIM_INFINITY,
+};
+
+const PAGE_SIZE: usize = 4096;
+/// much more than the default stack of 8 KiB
+const STACK_USE: usize = 32 * 1024;
+
+/// Run after `execve` with a larger RLIMIT_STACK
+fn deep_stack() -> u8 {
+    let mut buf = [0u8; STACK_USE];
+    for i in (0..STACK_USE).step_by(PAGE_SIZE) {
+        unsafe { (&mut buf[i] as *mut u8).write_volatile(i as u8) };
+    }
+    unsafe { (&buf[STACK_USE - PAGE_SIZE] as *const u8).read_volatile() }
+}
+
+/// VmRSS of the calling process in kB
+fn vm_rss() -> usize {
+    let fd = open(&format!("/proc/{}/status\0", getpid()), OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 512];
+    let len = read(fd as usize, &mut buf);
+    assert!(len > 0);
+    close(fd as usize);
+    let status = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    let line = status.lines().find(|line| line.starts_with("VmRSS:")).unwrap();
+    line["VmRSS:".len()..].trim().trim_end_matches(" kB").parse().unwrap()
+}
+
+/// Exec this program with `arg` and a stack of `cur` bytes, the hard limit if `None`
+fn exec_with_stack(cur: Option<usize>, arg: &str) {
+    let mut stack = RLimit::default();
+    getrlimit(RLIMIT_STACK, &mut stack);
+    stack.cur = cur.unwrap_or(stack.max);
+    assert_eq!(setrlimit(RLIMIT_STACK, &stack), 0);
+    exec("rlimit\0", &["rlimit\0".as_ptr(), arg.as_ptr(), null()]);
+    panic!("unreachable!");
+}
+
+/// Run `f` in a child process and check that it exits with 0
+fn in_child(f: fn()) {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 {
+        match argv[1] {
+            "stack" => {
+                deep_stack();
+            }
+            // only the pages touched are allocated, not the whole stack
+            "rss" => assert!(vm_rss() < 1024),
+            _ => panic!("unknown mode"),
+        }
+        return 0;
+    }
+    let mut limit = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_NOFILE, &mut limit), 0);
+    assert_eq!(limit, RLimit { cur: 1024, max: 4096 });
+    assert_eq!(prlimit(getpid() as usize, RLIMIT_NOFILE, None, Some(&mut limit)), 0);
+    assert_eq!(limit.cur, 1024);
+    assert_eq!(prlimit(99999, RLIMIT_NOFILE, None, Some(&mut limit)), -ESRCH);
+    assert_eq!(getrlimit(99, &mut limit), -EINVAL);
+    // the soft limit stays below the hard one, which can't be raised
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: 5000, max: 4096 }), -EINVAL);
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: 1024, max: 8192 }), -EPERM);
+
+    // no fd can reach RLIMIT_NOFILE
+    let fd = open("rlimit\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let nofile = RLimit { cur: fd as usize + 1, max: 4096 };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &nofile), 0);
+    assert_eq!(open("rlimit\0", OpenFlags::RDONLY), -EMFILE);
+    assert_eq!(close(fd as usize), 0);
+    assert_eq!(open("rlimit\0", OpenFlags::RDONLY), fd);
+    assert_eq!(close(fd as usize), 0);
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: 1024, max: 4096 }), 0);
+
+    // the heap is bounded by RLIMIT_DATA
+    let bottom = brk(0) as usize;
+    let data = RLimit { cur: 8 * PAGE_SIZE, max: RLIM_INFINITY };
+    assert_eq!(setrlimit(RLIMIT_DATA, &data), 0);
+    assert_eq!(brk(bottom + 4 * PAGE_SIZE), (bottom + 4 * PAGE_SIZE) as isize);
+    assert_eq!(brk(bottom + 16 * PAGE_SIZE), -ENOMEM);
+    assert_eq!(brk(bottom), bottom as isize);
+
+    // the new program gets a stack of RLIMIT_STACK
+    in_child(|| exec_with_stack(Some(4 * STACK_USE), "stack\0"));
+    in_child(|| exec_with_stack(None, "rss\0"));
+
+    // fork fails once RLIMIT_NPROC processes are running
+    in_child(|| {
+        assert_eq!(setrlimit(RLIMIT_NPROC, &RLimit { cur: 1, max: RLIM_INFINITY }), 0);
+        assert_eq!(fork(), -EAGAIN);
+    });
+
+    // neither mmap nor brk can grow the address space past RLIMIT_AS
+    in_child(|| {
+        assert_eq!(setrlimit(RLIMIT_AS, &RLimit { cur: PAGE_SIZE, max: RLIM_INFINITY }), 0);
+        let prot = ProtFlags::READ | ProtFlags::WRITE;
+        let flags = MapFlags::PRIVATE | MapFlags::ANONYMOUS;
+        assert_eq!(mmap(0, PAGE_SIZE, prot, flags, usize::MAX, 0), -ENOMEM);
+        let bottom = brk(0) as usize;
+        assert_eq!(brk(bottom + PAGE_SIZE), -ENOMEM);
+    });
+    println!("rlimit passed!");
+    0
+}
@@ -0,0 +1,113 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EFBIG, EINVAL, ESPIPE};
+use user_lib::{
+    close, lseek, open, pipe, pread, pwrite, read, unlink, write, OpenFlags, SEEK_CUR, SEEK_END,
+    SEEK_SET,
+};
+
+/// Random access to a file of the filesystem at `path`
+fn test_file(path: &str) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"0123456789"), 10);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 10);
+    assert_eq!(lseek(fd, 2, SEEK_SET), 2);
+    let mut buf = [0u8; 4];
+    assert_eq!(read(fd, &mut buf), 4);
+    assert_eq!(&buf, b"2345");
+    assert_eq!(lseek(fd, -3, SEEK_END), 7);
+    assert_eq!(read(fd, &mut buf), 3);
+    assert_eq!(&buf[..3], b"789");
+    assert_eq!(lseek(fd, -1, SEEK_SET), -EINVAL);
+    assert_eq!(lseek(fd, 0, 3), -EINVAL);
+
+    // pread and pwrite leave the offset alone
+    assert_eq!(lseek(fd, 1, SEEK_SET), 1);
+    assert_eq!(pread(fd, &mut buf, 6), 4);
+    assert_eq!(&buf, b"6789");
+    assert_eq!(pwrite(fd, b"ab", 4), 2);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 1);
+    assert_eq!(read(fd, &mut buf), 4);
+    assert_eq!(&buf, b"123a");
+
+    // the gap left by seeking past the end reads as zeros
+    let end = 3000;
+    assert_eq!(lseek(fd, end, SEEK_SET), end);
+    assert_eq!(write(fd, b"end"), 3);
+    assert_eq!(lseek(fd, 0, SEEK_END), end + 3);
+    let mut gap = [0xffu8; 512];
+    let mut pos = 10;
+    while pos < end as usize {
+        let len = pread(fd, &mut gap, pos);
+        assert!(len > 0);
+        let len = (len as usize).min(end as usize - pos);
+        assert!(gap[..len].iter().all(|byte| *byte == 0));
+        pos += len;
+    }
+    assert_eq!(pread(fd, &mut buf[..3], end as usize), 3);
+    assert_eq!(&buf[..3], b"end");
+    close(fd);
+    assert_eq!(unlink(path), 0);
+}
+
+/// A gap spanning clusters which held another file reads as zeros, and
+/// files stop at the 4 GiB - 1 limit of FAT32
+fn test_fat32() {
+    const GAP_END: usize = 5 * 4096 + 100;
+    let mut buf = [0xaau8; 4096];
+    let fd = open("/seek_stale\0", OpenFlags::CREATE | OpenFlags::WRONLY) as usize;
+    for _ in 0..GAP_END / buf.len() + 1 {
+        assert_eq!(write(fd, &buf), buf.len() as isize);
+    }
+    close(fd);
+    assert_eq!(unlink("/seek_stale\0"), 0);
+
+    let fd = open("/seek_gap\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"start"), 5);
+    assert_eq!(lseek(fd, GAP_END as isize, SEEK_SET), GAP_END as isize);
+    assert_eq!(write(fd, b"end"), 3);
+    buf.fill(0xff);
+    let mut pos = 5;
+    while pos < GAP_END {
+        let len = (GAP_END - pos).min(buf.len());
+        assert_eq!(pread(fd, &mut buf[..len], pos), len as isize);
+        assert!(buf[..len].iter().all(|byte| *byte == 0));
+        pos += len;
+    }
+    assert_eq!(pread(fd, &mut buf[..3], GAP_END), 3);
+    assert_eq!(&buf[..3], b"end");
+
+    let limit = u32::MAX as isize;
+    assert_eq!(lseek(fd, limit, SEEK_SET), limit);
+    assert_eq!(write(fd, b"x"), -EFBIG);
+    assert_eq!(pwrite(fd, b"xy", limit as usize - 1), -EFBIG);
+    assert_eq!(lseek(fd, 0, SEEK_END), GAP_END as isize + 3);
+    close(fd);
+    assert_eq!(unlink("/seek_gap\0"), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    test_file("/seek_test\0");
+    test_fat32();
+    test_file("/tmp/seek_test\0");
+
+    let mut pipe_fd = [0u32; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let mut buf = [0u8; 1];
+    assert_eq!(lseek(pipe_fd[0] as usize, 0, SEEK_SET), -ESPIPE);
+    assert_eq!(pread(pipe_fd[0] as usize, &mut buf, 0), -ESPIPE);
+    assert_eq!(pwrite(pipe_fd[1] as usize, b"x", 0), -ESPIPE);
+    close(pipe_fd[0] as usize);
+    close(pipe_fd[1] as usize);
+    println!("seek passed!");
+    0
+}
@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use core::ptr::null;
+use user_lib::errno::{E2BIG, ENOEXEC};
+use user_lib::{close, exec, exit, fork, open, wait, write, OpenFlags};
+
+/// Write `content` to a new file at `path`
+fn create(path: &str, content: &[u8]) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, content), content.len() as isize);
+    close(fd as usize);
+}
+
+/// A bare ELF header for `machine` with `phnum` program headers at `phoff`
+fn elf_header(machine: u16, phoff: u64, phnum: u16) -> [u8; 64] {
+    let mut header = [0u8; 64];
+    header[..8].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+    header[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    header[18..20].copy_from_slice(&machine.to_le_bytes());
+    header[20..24].copy_from_slice(&1u32.to_le_bytes());
+    header[32..40].copy_from_slice(&phoff.to_le_bytes());
+    header[52..54].copy_from_slice(&64u16.to_le_bytes());
+    header[54..56].copy_from_slice(&56u16.to_le_bytes());
+    header[56..58].copy_from_slice(&phnum.to_le_bytes());
+    header
+}
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc > 1 {
+        // run as the interpreter of the script
+        assert_eq!(argv, ["/shebang", "an  argument", "shebang_script", "extra"]);
+        println!("shebang passed!");
+        return 0;
+    }
+    create("shebang_script\0", b"#! /shebang   an  argument \necho never run\n");
+    create("shebang_garbage\0", b"neither an ELF image nor a script\n");
+    create("shebang_empty\0", b"#!\n");
+    // an image for another machine, and one whose program headers are cut off
+    create("shebang_x86\0", &elf_header(0x3e, 64, 0));
+    create("shebang_truncated\0", &elf_header(0xf3, 0x1000, 1));
+    // formats the kernel can't run are refused, the caller goes on
+    for path in ["shebang_garbage\0", "shebang_empty\0", "shebang_x86\0", "shebang_truncated\0"] {
+        assert_eq!(exec(path, &[path.as_ptr(), null()]), -ENOEXEC);
+    }
+    // argv fits, but not once the interpreter and its argument are added
+    let mut script = vec![];
+    script.extend_from_slice(b"#!/shebang ");
+    script.extend_from_slice(&[b'x'; 200]);
+    script.push(b'\n');
+    create("shebang_long\0", &script);
+    let mut long_arg = vec![b'y'; 3900];
+    long_arg.push(0);
+    assert_eq!(
+        exec("shebang_long\0", &["script\0".as_ptr(), long_arg.as_ptr(), null()]),
+        -E2BIG
+    );
+    let pid = fork();
+    if pid == 0 {
+        exec(
+            "shebang_script\0",
+            &["script\0".as_ptr(), "extra\0".as_ptr(), null()],
+        );
+        panic!("unreachable!");
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    if exit_code != 0 {
+        exit(exit_code);
+    }
+    0
+}
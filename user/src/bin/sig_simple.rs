@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, getpid, kill, sigaction, sigprocmask, sleep, waitpid, SignalAction, SIGTERM,
+    SIGTRAP, SIGUSR1, SIGUSR2, SIG_BLOCK, SIG_UNBLOCK,
+};
+
+static mut LAST_SIGNAL: usize = 0;
+
+extern "C" fn on_signal(signum: usize) {
+    unsafe {
+        LAST_SIGNAL = signum;
+    }
+}
+
+fn last_signal() -> usize {
+    unsafe { core::ptr::read_volatile(&LAST_SIGNAL) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let action = SignalAction {
+        handler: on_signal as usize,
+        ..Default::default()
+    };
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), 0);
+    assert_eq!(sigaction(SIGUSR2, Some(&action), None), 0);
+    // the handler runs before kill returns
+    assert_eq!(kill(getpid(), SIGUSR1), 0);
+    assert_eq!(last_signal(), SIGUSR1);
+    // a blocked signal stays pending until it is unblocked
+    let set: u64 = 1 << (SIGUSR2 - 1);
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(&set), None), 0);
+    assert_eq!(kill(getpid(), SIGUSR2), 0);
+    assert_eq!(last_signal(), SIGUSR1);
+    assert_eq!(sigprocmask(SIG_UNBLOCK, Some(&set), None), 0);
+    assert_eq!(last_signal(), SIGUSR2);
+    // the default action of SIGTERM terminates a sleeping child
+    let pid = fork();
+    if pid == 0 {
+        sleep(10000);
+        exit(0);
+    }
+    sleep(10);
+    assert_eq!(kill(pid, SIGTERM), 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -(SIGTERM as i32));
+    // a breakpoint ends the process with SIGTRAP instead of the kernel
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            core::arch::asm!("ebreak");
+        }
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -(SIGTRAP as i32));
+    println!("sig_simple passed!");
+    0
+}
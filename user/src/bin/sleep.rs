@@ -4,7 +4,10 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{exit, fork, get_time, sleep, waitpid};
+use user_lib::errno::{EINTR, EINVAL};
+use user_lib::{
+    exit, fork, get_time, kill, nanosleep, sigaction, sleep, waitpid, SignalAction, TimeSpec, SIGUSR1,
+};
 
 fn sleepy() {
     let time: usize = 100;
@@ -15,6 +18,22 @@ fn sleepy() {
     exit(0);
 }
 
+extern "C" fn on_sigusr1(_signum: usize) {}
+
+/// A caught signal cuts a 1 second `nanosleep` short, leaving the rest in `rem`
+fn interrupted() {
+    let action = SignalAction {
+        handler: on_sigusr1 as usize,
+        ..Default::default()
+    };
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), 0);
+    let req = TimeSpec { sec: 1, nsec: 0 };
+    let mut rem = TimeSpec::default();
+    assert_eq!(nanosleep(&req, Some(&mut rem)), -EINTR);
+    assert!(rem.sec == 0 && rem.nsec > 0);
+    exit(0);
+}
+
 #[no_mangle]
 pub fn main() -> i32 {
     let current_time = get_time();
@@ -25,6 +44,20 @@ pub fn main() -> i32 {
     }
     assert!(waitpid(pid as usize, &mut exit_code) == pid && exit_code == 0);
     println!("use {} msecs.", get_time() - current_time);
+
+    // nanoseconds, not microseconds
+    let start = get_time();
+    assert_eq!(nanosleep(&TimeSpec { sec: 0, nsec: 200_000_000 }, None), 0);
+    assert!(get_time() - start >= 200);
+    assert_eq!(nanosleep(&TimeSpec { sec: 0, nsec: 1_000_000_000 }, None), -EINVAL);
+
+    let pid = fork();
+    if pid == 0 {
+        interrupted();
+    }
+    sleep(100);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert!(waitpid(pid as usize, &mut exit_code) == pid && exit_code == 0);
     println!("sleep pass.");
     0
 }
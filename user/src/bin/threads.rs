@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use user_lib::{getpid, gettid, thread_create, thread_create_with_tls, thread_join, yield_};
+
+const THREAD_NUM: usize = 4;
+const STACK_SIZE: usize = 4096 * 2;
+const ROUNDS: usize = 1000;
+
+static mut STACKS: [[u8; STACK_SIZE]; THREAD_NUM] = [[0; STACK_SIZE]; THREAD_NUM];
+static mut COUNTS: [usize; THREAD_NUM] = [0; THREAD_NUM];
+static mut PIDS: [isize; THREAD_NUM] = [0; THREAD_NUM];
+/// thread local storage of the thread created with `CLONE_SETTLS`
+static mut TLS_BLOCK: [usize; 4] = [0; 4];
+static mut TLS_SEEN: usize = 0;
+
+fn tp() -> usize {
+    let tp: usize;
+    unsafe {
+        asm!("mv {}, tp", out(reg) tp);
+    }
+    tp
+}
+
+fn tls_worker(_: usize) -> i32 {
+    unsafe {
+        TLS_SEEN = tp();
+    }
+    0
+}
+
+fn worker(id: usize) -> i32 {
+    for _ in 0..ROUNDS {
+        // the main thread sees the stores, the address space is shared
+        unsafe {
+            COUNTS[id] += 1;
+        }
+        if unsafe { COUNTS[id] } % 100 == 0 {
+            yield_();
+        }
+    }
+    unsafe {
+        PIDS[id] = getpid();
+    }
+    assert_ne!(gettid(), getpid());
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut tids = [0u32; THREAD_NUM];
+    for (id, tid) in tids.iter_mut().enumerate() {
+        let stack = unsafe { &mut STACKS[id] };
+        let ret = thread_create(worker, id, stack, tid);
+        assert!(ret > 0);
+        assert_eq!(*tid, ret as u32);
+    }
+    for tid in tids.iter() {
+        thread_join(tid);
+    }
+    for id in 0..THREAD_NUM {
+        unsafe {
+            assert_eq!(COUNTS[id], ROUNDS);
+            assert_eq!(PIDS[id], getpid());
+        }
+    }
+    assert_eq!(gettid(), getpid());
+    // CLONE_SETTLS gives the new thread its tp, ours stays
+    let own_tp = tp();
+    let tls = unsafe { TLS_BLOCK.as_ptr() as usize };
+    let stack = unsafe { &mut STACKS[0] };
+    let mut tid = 0;
+    assert!(thread_create_with_tls(tls_worker, 0, stack, &mut tid, tls) > 0);
+    thread_join(&tid);
+    assert_eq!(unsafe { TLS_SEEN }, tls);
+    assert_eq!(tp(), own_tp);
+    println!("threads passed!");
+    0
+}
@@ -0,0 +1,116 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use user_lib::errno::{EINVAL, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY};
+use user_lib::{
+    close, ftruncate, lseek, mkdir, mount, open, pread, read, rmdir, umount, unlink, write, OpenFlags,
+    SEEK_END, SEEK_SET,
+};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Read the whole of `path`, at most `buf.len()` bytes
+fn read_file(path: &str, buf: &mut [u8]) -> usize {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    let mut total = 0;
+    loop {
+        let len = read(fd as usize, &mut buf[total..]);
+        assert!(len >= 0);
+        if len == 0 || total + len as usize == buf.len() {
+            total += len as usize;
+            break;
+        }
+        total += len as usize;
+    }
+    close(fd as usize);
+    total
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // a file spanning pages
+    // 用户栈只有两页，缓冲区放在堆上
+    let mut data = vec![0u8; 3 * PAGE_SIZE / 2];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    let fd = open("/tmp/a\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, &data), data.len() as isize);
+    close(fd as usize);
+    let mut buf = vec![0u8; 2 * PAGE_SIZE];
+    assert_eq!(read_file("/tmp/a\0", &mut buf), data.len());
+    assert_eq!(&buf[..data.len()], &data[..]);
+
+    // truncated when opened with O_TRUNC
+    let fd = open("/tmp/a\0", OpenFlags::WRONLY | OpenFlags::TRUNC);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"short"), 5);
+    close(fd as usize);
+    assert_eq!(read_file("/tmp/a\0", &mut buf), 5);
+    assert_eq!(&buf[..5], b"short");
+
+    // ftruncate shrinks and grows with zeros
+    let fd = open("/tmp/a\0", OpenFlags::RDWR) as usize;
+    assert_eq!(ftruncate(fd, 2), 0);
+    assert_eq!(ftruncate(fd, PAGE_SIZE as isize + 1), 0);
+    assert_eq!(lseek(fd, 0, SEEK_END), PAGE_SIZE as isize + 1);
+    buf.fill(0xff);
+    assert_eq!(pread(fd, &mut buf, 0), PAGE_SIZE as isize + 1);
+    assert_eq!(&buf[..2], b"sh");
+    assert!(buf[2..PAGE_SIZE + 1].iter().all(|byte| *byte == 0));
+    assert_eq!(ftruncate(fd, -1), -EINVAL);
+    close(fd);
+
+    // a far write leaves a hole which takes no pages and reads as zeros
+    let fd = open("/tmp/sparse\0", OpenFlags::CREATE | OpenFlags::RDWR) as usize;
+    let far = 1isize << 32;
+    assert_eq!(lseek(fd, far, SEEK_SET), far);
+    assert_eq!(write(fd, b"!"), 1);
+    assert_eq!(lseek(fd, 0, SEEK_END), far + 1);
+    buf.fill(0xff);
+    assert_eq!(pread(fd, &mut buf[..PAGE_SIZE], far as usize / 2), PAGE_SIZE as isize);
+    assert!(buf[..PAGE_SIZE].iter().all(|byte| *byte == 0));
+    assert_eq!(pread(fd, &mut buf[..2], far as usize), 1);
+    assert_eq!(buf[0], b'!');
+    close(fd);
+    assert_eq!(unlink("/tmp/sparse\0"), 0);
+
+    // directories
+    assert_eq!(mkdir("/tmp/d\0"), 0);
+    let fd = open("/tmp/d/f\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"kept"), 4);
+    assert_eq!(rmdir("/tmp/d\0"), -ENOTEMPTY);
+    assert_eq!(unlink("/tmp/d\0"), -EISDIR);
+    assert_eq!(rmdir("/tmp/d/f\0"), -ENOTDIR);
+
+    // an unlinked file lives on while it is open
+    assert_eq!(unlink("/tmp/d/f\0"), 0);
+    assert_eq!(open("/tmp/d/f\0", OpenFlags::RDONLY), -ENOENT);
+    assert_eq!(write(fd as usize, b"!"), 1);
+    close(fd as usize);
+    assert_eq!(rmdir("/tmp/d\0"), 0);
+    assert_eq!(unlink("/tmp/a\0"), 0);
+    assert_eq!(open("/tmp/a\0", OpenFlags::RDONLY), -ENOENT);
+
+    // every mount is a new filesystem, gone with its files once unmounted
+    assert_eq!(mkdir("/tmp/m\0"), 0);
+    assert_eq!(mount("tmpfs\0", "/tmp/m\0", "tmpfs\0", 0), 0);
+    let fd = open("/tmp/m/f\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"mounted"), 7);
+    close(fd as usize);
+    assert_eq!(read_file("/tmp/m/f\0", &mut buf), 7);
+    assert_eq!(umount("/tmp/m\0"), 0);
+    assert_eq!(open("/tmp/m/f\0", OpenFlags::RDONLY), -ENOENT);
+    assert_eq!(rmdir("/tmp/m\0"), 0);
+    println!("tmpfs passed!");
+    0
+}
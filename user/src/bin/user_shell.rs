@@ -13,37 +13,187 @@ const DL: u8 = 0x7fu8;
 const BS: u8 = 0x08u8;
 
 use alloc::string::String;
+use alloc::vec::Vec;
 use user_lib::console::getchar;
-use user_lib::{exec, fork, waitpid};
+use user_lib::{
+    exec, fork, getpid, kill, setpgid, sigaction, tcsetpgrp, wait4, wexitcode, wifstopped, wstopsig, SignalAction,
+    SIGCONT, SIGINT, SIGTSTP, SIGTTIN, SIGTTOU, SIG_DFL, SIG_IGN, WNOHANG, WUNTRACED,
+};
+
+const STDIN: usize = 0;
+/// job control signals the shell itself must survive
+const JOB_SIGNALS: [usize; 4] = [SIGINT, SIGTSTP, SIGTTIN, SIGTTOU];
+
+/// A job running in the background or stopped, each in its own process group
+struct Job {
+    pid: usize,
+    line: String,
+    stopped: bool,
+}
+
+fn set_job_signals(handler: usize) {
+    let action = SignalAction {
+        handler,
+        ..Default::default()
+    };
+    for signum in JOB_SIGNALS {
+        sigaction(signum, Some(&action), None);
+    }
+}
+
+/// Give the terminal to `pid` and wait until it exits or stops, a stopped
+/// one is kept in `jobs`
+fn wait_foreground(pid: usize, line: String, jobs: &mut Vec<Job>) {
+    tcsetpgrp(STDIN, pid);
+    let mut status: i32 = 0;
+    let exit_pid = wait4(pid as isize, &mut status, WUNTRACED);
+    assert_eq!(pid as isize, exit_pid);
+    tcsetpgrp(STDIN, getpid() as usize);
+    if wifstopped(status) {
+        println!("");
+        println!("[{}] Stopped by signal {}: {}", pid, wstopsig(status), line);
+        jobs.push(Job {
+            pid,
+            line,
+            stopped: true,
+        });
+    } else {
+        println!("Shell: Process {} exited with code {}", pid, wexitcode(status));
+    }
+}
+
+/// Report the background jobs which have exited or stopped meanwhile
+fn reap_jobs(jobs: &mut Vec<Job>) {
+    loop {
+        let mut status: i32 = 0;
+        let pid = wait4(-1, &mut status, WNOHANG | WUNTRACED);
+        if pid <= 0 {
+            return;
+        }
+        let index = match jobs.iter().position(|job| job.pid == pid as usize) {
+            Some(index) => index,
+            None => continue,
+        };
+        if wifstopped(status) {
+            jobs[index].stopped = true;
+            println!("[{}] Stopped by signal {}: {}", pid, wstopsig(status), jobs[index].line);
+        } else {
+            let job = jobs.remove(index);
+            println!("[{}] Done with code {}: {}", pid, wexitcode(status), job.line);
+        }
+    }
+}
+
+/// The job named by `fg`/`bg`, the latest one if no pid is given
+fn find_job(jobs: &[Job], arg: Option<&str>) -> Option<usize> {
+    match arg {
+        Some(pid) => {
+            let pid: usize = pid.parse().ok()?;
+            jobs.iter().position(|job| job.pid == pid)
+        }
+        None => jobs.len().checked_sub(1),
+    }
+}
+
+/// Run the builtins `jobs`, `fg` and `bg`, return false if `args` is none of them
+fn run_builtin(args: &[&str], jobs: &mut Vec<Job>) -> bool {
+    match args[0] {
+        "jobs" => {
+            for job in jobs.iter() {
+                let state = if job.stopped { "Stopped" } else { "Running" };
+                println!("[{}] {} {}", job.pid, state, job.line);
+            }
+        }
+        "fg" => match find_job(jobs, args.get(1).copied()) {
+            Some(index) => {
+                let job = jobs.remove(index);
+                println!("{}", job.line);
+                // continue it after it owns the terminal, or it stops again on reading
+                tcsetpgrp(STDIN, job.pid);
+                kill(-(job.pid as isize), SIGCONT);
+                wait_foreground(job.pid, job.line, jobs);
+            }
+            None => println!("fg: no such job"),
+        },
+        "bg" => match find_job(jobs, args.get(1).copied()) {
+            Some(index) => {
+                let job = &mut jobs[index];
+                job.stopped = false;
+                kill(-(job.pid as isize), SIGCONT);
+                println!("[{}] {} &", job.pid, job.line);
+            }
+            None => println!("bg: no such job"),
+        },
+        _ => return false,
+    }
+    true
+}
 
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Rust user shell");
+    // lead a process group of our own, Ctrl-C and Ctrl-Z are for the jobs
+    setpgid(0, 0);
+    tcsetpgrp(STDIN, getpid() as usize);
+    set_job_signals(SIG_IGN);
+    let mut jobs: Vec<Job> = Vec::new();
     let mut line: String = String::new();
     print!(">> ");
     loop {
         let c = getchar();
         match c {
+            // the read was interrupted by a signal, nothing was typed
+            0 => {}
             LF | CR => {
                 println!("");
-                if !line.is_empty() {
-                    line.push('\0');
+                let mut words: Vec<&str> = line.split_whitespace().collect();
+                // a trailing `&` runs the job in the background
+                let background = words.last() == Some(&"&");
+                if background {
+                    words.pop();
+                }
+                if !words.is_empty() && !run_builtin(&words, &mut jobs) {
+                    // the program and its arguments, NUL terminated
+                    let args: Vec<String> = words
+                        .iter()
+                        .map(|arg| {
+                            let mut arg = String::from(*arg);
+                            arg.push('\0');
+                            arg
+                        })
+                        .collect();
+                    let mut argv: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+                    argv.push(core::ptr::null());
                     let pid = fork();
                     if pid == 0 {
                         // child process
-                        if exec(line.as_str()) == -1 {
+                        setpgid(0, 0);
+                        if !background {
+                            tcsetpgrp(STDIN, getpid() as usize);
+                        }
+                        set_job_signals(SIG_DFL);
+                        if exec(args[0].as_str(), argv.as_slice()) < 0 {
                             println!("Error when executing!");
                             return -4;
                         }
                         unreachable!();
+                    }
+                    // also done here, whichever of us runs first
+                    setpgid(pid as usize, pid as usize);
+                    let job_line = words.join(" ");
+                    if background {
+                        println!("[{}] {} &", pid, job_line);
+                        jobs.push(Job {
+                            pid: pid as usize,
+                            line: job_line,
+                            stopped: false,
+                        });
                     } else {
-                        let mut exit_code: i32 = 0;
-                        let exit_pid = waitpid(pid as usize, &mut exit_code);
-                        assert_eq!(pid, exit_pid);
-                        println!("Shell: Process {} exited with code {}", pid, exit_code);
+                        wait_foreground(pid as usize, job_line, &mut jobs);
                     }
-                    line.clear();
                 }
+                line.clear();
+                reap_jobs(&mut jobs);
                 print!(">> ");
             }
             BS | DL => {
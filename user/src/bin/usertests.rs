@@ -10,28 +10,54 @@ extern crate user_lib;
 // item of TESTS : app_name(argv_0), argv_1, argv_2, argv_3, exit_code
 static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("filetest_simple\0", "\0", "\0", "\0", 0),
+    ("args\0", "\0", "\0", "\0", 0),
+    ("shebang\0", "\0", "\0", "\0", 0),
+    ("errno\0", "\0", "\0", "\0", 0),
+    ("efault\0", "\0", "\0", "\0", 0),
+    ("brk_lazy\0", "\0", "\0", "\0", 0),
     ("cat_filea\0", "\0", "\0", "\0", 0),
+    ("cputime\0", "\0", "\0", "\0", 0),
+    ("devfs\0", "\0", "\0", "\0", 0),
+    ("elf_load\0", "\0", "\0", "\0", 0),
+    ("exec_thread\0", "\0", "\0", "\0", 0),
     ("exit\0", "\0", "\0", "\0", 0),
+    ("exit_group\0", "\0", "\0", "\0", 0),
     ("fantastic_text\0", "\0", "\0", "\0", 0),
+    ("futex\0", "\0", "\0", "\0", 0),
     ("forktest_simple\0", "\0", "\0", "\0", 0),
     ("forktest\0", "\0", "\0", "\0", 0),
     ("forktest2\0", "\0", "\0", "\0", 0),
+    ("forktest_cow\0", "\0", "\0", "\0", 0),
     ("forktree\0", "\0", "\0", "\0", 0),
     ("hello_world\0", "\0", "\0", "\0", 0),
     ("huge_write\0", "\0", "\0", "\0", 0),
     ("matrix\0", "\0", "\0", "\0", 0),
+    ("mmap_shared\0", "\0", "\0", "\0", 0),
+    ("mprotect\0", "\0", "\0", "\0", 0),
+    ("priority\0", "\0", "\0", "\0", 0),
+    ("seek\0", "\0", "\0", "\0", 0),
+    ("sig_simple\0", "\0", "\0", "\0", 0),
     ("sleep_simple\0", "\0", "\0", "\0", 0),
     ("sleep\0", "\0", "\0", "\0", 0),
+    ("threads\0", "\0", "\0", "\0", 0),
+    ("mount\0", "\0", "\0", "\0", 0),
+    ("pgrp\0", "\0", "\0", "\0", 0),
+    ("procfs\0", "\0", "\0", "\0", 0),
+    ("rlimit\0", "\0", "\0", "\0", 0),
+    ("tmpfs\0", "\0", "\0", "\0", 0),
+    ("wait_nohang\0", "\0", "\0", "\0", 0),
     ("yield\0", "\0", "\0", "\0", 0),
 ];
 
-static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[("stack_overflow\0", "\0", "\0", "\0", -2)];
+static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[("stack_overflow\0", "\0", "\0", "\0", -11)];
 
 use user_lib::{exec, fork, waitpid};
 
 fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
     let mut pass_num = 0;
-    let mut arr: [*const u8; 4] = [
+    // argv ends with a null pointer
+    let mut arr: [*const u8; 5] = [
+        core::ptr::null::<u8>(),
         core::ptr::null::<u8>(),
         core::ptr::null::<u8>(),
         core::ptr::null::<u8>(),
@@ -65,7 +91,7 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
 
         let pid = fork();
         if pid == 0 {
-            exec(test.0);
+            exec(test.0, &arr);
             panic!("unreachable!");
         } else {
             let mut exit_code: i32 = Default::default();
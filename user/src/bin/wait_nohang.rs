@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::ECHILD;
+use user_lib::{exit, fork, sleep, waitpid, waitpid_with, WNOHANG};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        sleep(100);
+        exit(-7);
+    }
+    let mut exit_code: i32 = 0;
+    // the child is still sleeping
+    assert_eq!(waitpid_with(pid, &mut exit_code, WNOHANG), 0);
+    // block until it exits
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -7);
+    // no child is left
+    assert_eq!(waitpid_with(-1, &mut exit_code, WNOHANG), -ECHILD);
+    println!("wait_nohang passed!");
+    0
+}
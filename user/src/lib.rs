@@ -5,6 +5,7 @@
 
 #[macro_use]
 pub mod console;
+pub mod errno;
 mod lang_items;
 mod syscall;
 
@@ -12,7 +13,9 @@ extern crate alloc;
 #[macro_use]
 extern crate bitflags;
 
+use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
+use core::mem::size_of;
 use syscall::*;
 
 const USER_HEAP_SIZE: usize = 32768;
@@ -27,35 +30,176 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
+// sp points to argc, then argv, envp and the auxiliary vector
+core::arch::global_asm!(
+    ".section .text.entry",
+    ".globl _start",
+    "_start:",
+    "    mv a0, sp",
+    "    call rust_start",
+);
+
+/// `envp` on the initial stack, followed by the auxiliary vector
+static mut ENVP: *const usize = core::ptr::null();
+
+/// The NUL terminated string at `ptr`
+unsafe fn c_str(ptr: usize) -> &'static str {
+    let len = (0usize..)
+        .find(|i| ((ptr + *i) as *const u8).read_volatile() == 0)
+        .unwrap();
+    core::str::from_utf8(core::slice::from_raw_parts(ptr as *const u8, len)).unwrap()
+}
+
 #[no_mangle]
-#[link_section = ".text.entry"]
-pub extern "C" fn _start() -> ! {
+extern "C" fn rust_start(sp: usize) -> ! {
     unsafe {
         HEAP.lock()
             .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
     }
-    exit(main());
+    let argc = unsafe { *(sp as *const usize) };
+    let argv = (sp + size_of::<usize>()) as *const usize;
+    let mut args: Vec<&'static str> = Vec::new();
+    for i in 0..argc {
+        args.push(unsafe { c_str(*argv.add(i)) });
+    }
+    unsafe {
+        ENVP = argv.add(argc + 1);
+    }
+    exit(main(argc, args.as_slice()));
 }
 
 #[linkage = "weak"]
 #[no_mangle]
-fn main() -> i32 {
+fn main(_argc: usize, _argv: &[&str]) -> i32 {
     panic!("Cannot find main!");
 }
 
+/// The environment strings, `NAME=value`
+pub fn environ() -> impl Iterator<Item = &'static str> {
+    let envp = unsafe { ENVP };
+    (0..)
+        .map(move |i| unsafe { *envp.add(i) })
+        .take_while(|ptr| *ptr != 0)
+        .map(|ptr| unsafe { c_str(ptr) })
+}
+
+/// The value of the environment variable `name`
+pub fn getenv(name: &str) -> Option<&'static str> {
+    environ().find_map(|env| {
+        env.strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+    })
+}
+
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_BASE: usize = 7;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+/// The value of `aux_type` in the auxiliary vector
+pub fn getauxval(aux_type: usize) -> Option<usize> {
+    let envp = unsafe { ENVP };
+    let envc = environ().count();
+    // the auxiliary vector follows the NULL ending envp
+    let mut aux = unsafe { envp.add(envc + 1) };
+    loop {
+        let (ty, value) = unsafe { (*aux, *aux.add(1)) };
+        match ty {
+            0 => return None,
+            ty if ty == aux_type => return Some(value),
+            _ => aux = unsafe { aux.add(2) },
+        }
+    }
+}
+
 bitflags! {
     pub struct OpenFlags: u32 {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
-        const CREATE = 1 << 9;
+        const CREATE = 1 << 6;
         const TRUNC = 1 << 10;
+        const CLOEXEC = 1 << 19;
+        const DIRECTORY = 1 << 21;
+    }
+}
+
+bitflags! {
+    pub struct ProtFlags: u32 {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    pub struct MapFlags: u32 {
+        const SHARED = 1 << 0;
+        const PRIVATE = 1 << 1;
+        const FIXED = 1 << 4;
+        const ANONYMOUS = 1 << 5;
     }
 }
 
 pub fn open(path: &str, flags: OpenFlags) -> isize {
     sys_open(path, flags.bits)
 }
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdir(path)
+}
+pub fn unlink(path: &str) -> isize {
+    sys_unlinkat(path, 0)
+}
+pub fn rmdir(path: &str) -> isize {
+    sys_unlinkat(path, AT_REMOVEDIR)
+}
+/// Attach the filesystem `fstype` on `special` at the directory `dir`
+pub fn mount(special: &str, dir: &str, fstype: &str, flags: usize) -> isize {
+    sys_mount(special, dir, fstype, flags)
+}
+pub fn umount(target: &str) -> isize {
+    sys_umount2(target, 0)
+}
+/// An entry of a directory as the kernel gives it, `name` ends with a 0
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Dirent {
+    pub inode: usize,
+    pub offset: isize,
+    pub reclen: u16,
+    pub dtype: u8,
+    pub name: [u8; 128],
+}
+impl Dirent {
+    pub const fn empty() -> Self {
+        Self {
+            inode: 0,
+            offset: 0,
+            reclen: 0,
+            dtype: 0,
+            name: [0; 128],
+        }
+    }
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|byte| *byte == 0).unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+}
+/// Read the next entries of the directory `fd` into `dirents`, the number
+/// read is returned, 0 at the end
+pub fn getdents(fd: usize, dirents: &mut [Dirent]) -> isize {
+    let buf = unsafe {
+        core::slice::from_raw_parts_mut(dirents.as_mut_ptr() as *mut u8, dirents.len() * size_of::<Dirent>())
+    };
+    let len = sys_getdents64(fd, buf);
+    if len < 0 {
+        return len;
+    }
+    len / size_of::<Dirent>() as isize
+}
 pub fn close(fd: usize) -> isize {
     sys_close(fd)
 }
@@ -65,54 +209,397 @@ pub fn read(fd: usize, buf: &mut [u8]) -> isize {
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+/// Cut the file `fd` to `length` bytes or extend it with zeros
+pub fn ftruncate(fd: usize, length: isize) -> isize {
+    sys_ftruncate(fd, length)
+}
+/// Move the offset of `fd`, the new offset is returned
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+/// Read at `offset` without moving the offset of `fd`
+pub fn pread(fd: usize, buf: &mut [u8], offset: usize) -> isize {
+    sys_pread64(fd, buf, offset)
+}
+/// Write at `offset` without moving the offset of `fd`
+pub fn pwrite(fd: usize, buf: &[u8], offset: usize) -> isize {
+    sys_pwrite64(fd, buf, offset)
+}
+/// End the process, all of its threads included
 pub fn exit(exit_code: i32) -> ! {
+    sys_exit_group(exit_code);
+}
+/// End the calling thread only
+pub fn exit_thread(exit_code: i32) -> ! {
     sys_exit(exit_code);
 }
+/// Create a pipe, `pipe[0]` is the read end and `pipe[1]` the write end
+pub fn pipe(pipe: &mut [u32; 2]) -> isize {
+    sys_pipe(pipe)
+}
 pub fn yield_() -> isize {
     sys_yield()
 }
+pub const PRIO_PROCESS: usize = 0;
+/// Set the nice value of process `who`, 0 for the caller
+pub fn setpriority(which: usize, who: usize, nice: isize) -> isize {
+    sys_setpriority(which, who, nice)
+}
+/// `20 - nice` of process `who`, 0 for the caller, like the raw Linux syscall
+pub fn getpriority(which: usize, who: usize) -> isize {
+    sys_getpriority(which, who)
+}
+/// Milliseconds since boot
 pub fn get_time() -> isize {
-    sys_get_time()
+    // seconds and microseconds
+    let mut time = [0usize; 2];
+    sys_get_time(&mut time);
+    (time[0] * 1000 + time[1] / 1000) as isize
+}
+
+/// CPU times in clock ticks of `1 / CLOCKS_PER_SEC` seconds
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct Tms {
+    pub tms_utime: isize,
+    pub tms_stime: isize,
+    pub tms_cutime: isize,
+    pub tms_cstime: isize,
+}
+pub const CLOCKS_PER_SEC: isize = 100;
+/// Fill `buf` and return the clock ticks since boot
+pub fn times(buf: &mut Tms) -> isize {
+    sys_times(buf as *mut _)
+}
+
+/// Only the user and system time are filled in, as seconds and microseconds
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct Rusage {
+    pub ru_utime: [usize; 2],
+    pub ru_stime: [usize; 2],
+    pub others: [isize; 14],
+}
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+pub const RUSAGE_THREAD: isize = 1;
+pub fn getrusage(who: isize, buf: &mut Rusage) -> isize {
+    sys_getrusage(who, buf as *mut _)
+}
+
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+impl TimeSpec {
+    pub fn as_ns(&self) -> usize {
+        self.sec * 1_000_000_000 + self.nsec
+    }
+}
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+pub const CLOCK_THREAD_CPUTIME_ID: usize = 3;
+pub fn clock_gettime(clockid: usize, tp: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clockid, tp as *mut _)
 }
 pub fn getpid() -> isize {
     sys_getpid()
 }
+pub fn gettid() -> isize {
+    sys_gettid()
+}
 pub fn fork() -> isize {
     sys_fork()
 }
-pub fn exec(path: &str) -> isize {
-    sys_exec(path)
+/// Run `path` with the NUL terminated strings in `args`, which ends with a null pointer
+pub fn exec(path: &str, args: &[*const u8]) -> isize {
+    sys_execve(path, args, &[core::ptr::null()])
+}
+/// `exec` with the environment `envs`, which ends with a null pointer
+pub fn execve(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
+    sys_execve(path, args, envs)
 }
 
 pub fn brk(a:usize) -> isize{
     sys_brk(a)
 }
-pub fn wait(exit_code: &mut i32) -> isize {
+pub fn mmap(start: usize, len: usize, prot: ProtFlags, flags: MapFlags, fd: usize, offset: usize) -> isize {
+    sys_mmap(start, len, prot.bits, flags.bits, fd, offset)
+}
+pub fn munmap(start: usize, len: usize) -> isize {
+    sys_munmap(start, len)
+}
+pub fn mprotect(start: usize, len: usize, prot: ProtFlags) -> isize {
+    sys_mprotect(start, len, prot.bits)
+}
+pub fn msync(start: usize, len: usize) -> isize {
+    sys_msync(start, len, 0)
+}
+pub const CLONE_VM: usize = 0x0000_0100;
+pub const CLONE_FILES: usize = 0x0000_0400;
+pub const CLONE_THREAD: usize = 0x0001_0000;
+pub const CLONE_SETTLS: usize = 0x0008_0000;
+pub const CLONE_PARENT_SETTID: usize = 0x0010_0000;
+pub const CLONE_CHILD_CLEARTID: usize = 0x0020_0000;
+pub const CLONE_CHILD_SETTID: usize = 0x0100_0000;
+
+/// Start a thread running `entry(arg)` on `stack`, return its tid.
+/// `tid` holds the tid until the thread exits, then it is cleared.
+pub fn thread_create(entry: fn(usize) -> i32, arg: usize, stack: &mut [u8], tid: &mut u32) -> isize {
+    // the stack grows down from its 16 bytes aligned top
+    let stack_top = (stack.as_mut_ptr() as usize + stack.len()) & !0xf;
+    let tid = tid as *mut u32;
+    sys_clone(
+        CLONE_VM | CLONE_FILES | CLONE_THREAD | CLONE_CHILD_SETTID | CLONE_CHILD_CLEARTID,
+        stack_top,
+        core::ptr::null_mut(),
+        0,
+        tid,
+        entry as usize,
+        arg,
+    )
+}
+
+/// `thread_create` whose thread starts with `tls` in the `tp` register
+pub fn thread_create_with_tls(
+    entry: fn(usize) -> i32,
+    arg: usize,
+    stack: &mut [u8],
+    tid: &mut u32,
+    tls: usize,
+) -> isize {
+    let stack_top = (stack.as_mut_ptr() as usize + stack.len()) & !0xf;
+    let tid = tid as *mut u32;
+    sys_clone(
+        CLONE_VM | CLONE_FILES | CLONE_THREAD | CLONE_SETTLS | CLONE_CHILD_SETTID | CLONE_CHILD_CLEARTID,
+        stack_top,
+        core::ptr::null_mut(),
+        tls,
+        tid,
+        entry as usize,
+        arg,
+    )
+}
+
+/// Wait until the thread owning `tid` exits.
+pub fn thread_join(tid: &u32) {
     loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
+        let current = unsafe { (tid as *const u32).read_volatile() };
+        if current == 0 {
+            break;
         }
+        // the kernel clears `tid` and wakes us up when the thread exits
+        futex_wait(tid, current, None);
     }
 }
 
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+
+/// Block while `*uaddr == val`, for at most `timeout_ms` milliseconds if given.
+/// Return 0 when woken up by `futex_wake`, -1 otherwise.
+pub fn futex_wait(uaddr: &u32, val: u32, timeout_ms: Option<usize>) -> isize {
+    let timeout = timeout_ms.map(|ms| TimeSpec {
+        sec: ms / 1000,
+        nsec: ms % 1000 * 1_000_000,
+    });
+    futex_wait_timespec(uaddr, val, timeout.as_ref())
+}
+
+/// Like `futex_wait`, with the timeout given as a `TimeSpec`
+pub fn futex_wait_timespec(uaddr: &u32, val: u32, timeout: Option<&TimeSpec>) -> isize {
+    sys_futex(uaddr, FUTEX_WAIT, val, timeout.map_or(core::ptr::null(), |t| t as *const _))
+}
+
+/// Wake at most `count` tasks blocked on `uaddr`, return how many are woken up.
+pub fn futex_wake(uaddr: &u32, count: u32) -> isize {
+    sys_futex(uaddr, FUTEX_WAKE, count, core::ptr::null())
+}
+
+pub const WNOHANG: u32 = 1;
+/// also report children stopped by a signal
+pub const WUNTRACED: u32 = 2;
+
+/// Block until a child exits and store its exit code.
+pub fn wait(exit_code: &mut i32) -> isize {
+    waitpid_with(-1, exit_code, 0)
+}
+
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
-        }
+    waitpid_with(pid as isize, exit_code, 0)
+}
+
+/// `wait4` with `options`, return 0 if `WNOHANG` is given and no child has exited.
+/// A child killed by a signal gets the negated signal number as exit code.
+pub fn waitpid_with(pid: isize, exit_code: &mut i32, options: u32) -> isize {
+    let mut status: i32 = 0;
+    let ret = wait4(pid, &mut status, options);
+    if ret > 0 {
+        *exit_code = wexitcode(status);
     }
+    ret
 }
-pub fn sleep(period_ms: usize) {
-    let start = sys_get_time();
-    while sys_get_time() < start + period_ms as isize {
-        sys_yield();
+
+/// `wait4` keeping the raw status. `pid` is -1 for any child, 0 for the
+/// children in our process group and `-pgid` for those in group `pgid`.
+pub fn wait4(pid: isize, status: &mut i32, options: u32) -> isize {
+    sys_wait4(pid, status as *mut _, options)
+}
+
+/// Exit code of a child from its raw status, the negated signal number if
+/// it was killed by a signal
+pub fn wexitcode(status: i32) -> i32 {
+    if status & 0x7f != 0 {
+        -(status & 0x7f)
+    } else {
+        // the exit code is kept in bits 8..16 of the status
+        (status >> 8) as i8 as i32
     }
 }
+
+/// The child was stopped instead of exiting, only with `WUNTRACED`
+pub fn wifstopped(status: i32) -> bool {
+    status & 0xff == 0x7f
+}
+
+/// The signal which stopped the child
+pub fn wstopsig(status: i32) -> usize {
+    ((status >> 8) & 0xff) as usize
+}
+
+pub const RLIMIT_DATA: usize = 2;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+/// `struct rlimit`, the soft limit `cur` can be raised up to the hard limit `max`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    sys_getrlimit(resource, rlim as *mut _)
+}
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    sys_setrlimit(resource, rlim as *const _)
+}
+/// Set the limit of process `pid` (0 for the caller) if `new_limit` is
+/// given, the old one is stored in `old_limit`
+pub fn prlimit(pid: usize, resource: usize, new_limit: Option<&RLimit>, old_limit: Option<&mut RLimit>) -> isize {
+    sys_prlimit64(
+        pid,
+        resource,
+        new_limit.map_or(core::ptr::null(), |l| l as *const _),
+        old_limit.map_or(core::ptr::null_mut(), |l| l as *mut _),
+    )
+}
+
+/// Move process `pid` into process group `pgid`, 0 meaning the caller and a
+/// new group named after `pid` respectively
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+/// Start a new session and process group led by the caller
+pub fn setsid() -> isize {
+    sys_setsid()
+}
+pub fn getsid(pid: usize) -> isize {
+    sys_getsid(pid)
+}
+
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+/// The foreground process group of the terminal `fd`
+pub fn tcgetpgrp(fd: usize) -> isize {
+    let mut pgrp: i32 = 0;
+    let ret = sys_ioctl(fd, TIOCGPGRP, &mut pgrp as *mut i32 as usize);
+    if ret < 0 {
+        ret
+    } else {
+        pgrp as isize
+    }
+}
+/// Make `pgrp` the foreground process group of the terminal `fd`, it gets
+/// the input and the signals of Ctrl-C and Ctrl-Z
+pub fn tcsetpgrp(fd: usize, pgrp: usize) -> isize {
+    let pgrp = pgrp as i32;
+    sys_ioctl(fd, TIOCSPGRP, &pgrp as *const i32 as usize)
+}
+
+pub const SIGINT: usize = 2;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGBUS: usize = 7;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// `struct sigaction` of the kernel, `mask` has bit `n - 1` set for signal `n`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SignalAction {
+    pub handler: usize,
+    pub flags: usize,
+    pub mask: u64,
+}
+
+pub fn kill(pid: isize, signum: usize) -> isize {
+    sys_kill(pid, signum)
+}
+
+pub fn sigaction(signum: usize, action: Option<&SignalAction>, old_action: Option<&mut SignalAction>) -> isize {
+    sys_sigaction(
+        signum,
+        action.map_or(core::ptr::null(), |a| a as *const _),
+        old_action.map_or(core::ptr::null_mut(), |a| a as *mut _),
+    )
+}
+
+pub fn sigprocmask(how: usize, set: Option<&u64>, old_set: Option<&mut u64>) -> isize {
+    sys_sigprocmask(
+        how,
+        set.map_or(core::ptr::null(), |s| s as *const _),
+        old_set.map_or(core::ptr::null_mut(), |s| s as *mut _),
+    )
+}
+pub fn sleep(period_ms: usize) {
+    let req = TimeSpec {
+        sec: period_ms / 1000,
+        nsec: (period_ms % 1000) * 1_000_000,
+    };
+    sys_nanosleep(&req, core::ptr::null_mut());
+}
+/// Sleep for `req`, on `EINTR` the time left is stored in `rem`
+pub fn nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> isize {
+    sys_nanosleep(req, rem.map_or(core::ptr::null_mut(), |r| r as *mut _))
+}